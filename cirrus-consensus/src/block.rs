@@ -1,7 +1,12 @@
+use crate::merkle::merkle_root;
+use crate::tx::Transaction;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use cashcontracts::serialize::{read_var_int, write_var_int};
 use cashcontracts::{double_sha256, tx_hash_to_hex};
+use cirrus_crypto::Network;
 use hex_literal::hex;
 use std::{
+    cmp::Ordering,
     io,
     io::{Read, Write},
 };
@@ -16,6 +21,12 @@ pub struct BlockHeader {
     pub nonce: u32,
 }
 
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub txs: Vec<Transaction>,
+}
+
 pub const GENESIS: BlockHeader = BlockHeader {
     version: 1,
     prev_block: [0; 32],
@@ -25,6 +36,86 @@ pub const GENESIS: BlockHeader = BlockHeader {
     nonce: 2_083_236_893,
 };
 
+pub const TESTNET_GENESIS: BlockHeader = BlockHeader {
+    timestamp: 1_296_688_602,
+    nonce: 414_098_458,
+    ..GENESIS
+};
+
+pub const REGTEST_GENESIS: BlockHeader = BlockHeader {
+    timestamp: 1_296_688_602,
+    bits: 0x207f_ffff,
+    nonce: 2,
+    ..GENESIS
+};
+
+pub fn genesis(network: Network) -> BlockHeader {
+    match network {
+        Network::Mainnet => GENESIS,
+        Network::Testnet => TESTNET_GENESIS,
+        Network::Regtest => REGTEST_GENESIS,
+    }
+}
+
+/// Number of blocks whose timestamps make up the median time past.
+pub const MEDIAN_TIME_SPAN: usize = 11;
+/// How far, in seconds, a header's timestamp may be ahead of the
+/// network-adjusted time.
+pub const MAX_FUTURE_BLOCK_TIME: i64 = 2 * 60 * 60;
+
+/// Median of the last `MEDIAN_TIME_SPAN` timestamps, i.e. the median time
+/// past of the block with the last timestamp. Blocks have to be newer than
+/// the median time past of their parent. 0 if `timestamps` is empty.
+pub fn median_time_past(timestamps: &[u32]) -> u32 {
+    let start = timestamps.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut timestamps = timestamps[start..].to_vec();
+    timestamps.sort_unstable();
+    timestamps.get(timestamps.len() / 2).cloned().unwrap_or(0)
+}
+
+/// Compact form of the largest target allowed on `network`.
+pub fn pow_limit_bits(network: Network) -> u32 {
+    match network {
+        Network::Mainnet | Network::Testnet => 0x1d00_ffff,
+        Network::Regtest => 0x207f_ffff,
+    }
+}
+
+/// Decodes the compact `bits` into a 256-bit target, little endian like
+/// header hashes. None if the target is negative, zero or overflows.
+pub fn target_from_bits(bits: u32) -> Option<[u8; 32]> {
+    if bits & 0x0080_0000 != 0 {
+        return None;
+    }
+    let exponent = (bits >> 24) as usize;
+    let mut mantissa = bits & 0x007f_ffff;
+    let mut offset = 0;
+    if exponent <= 3 {
+        mantissa >>= 8 * (3 - exponent);
+    } else {
+        offset = exponent - 3;
+    }
+    let mut target = [0; 32];
+    for (i, byte) in mantissa.to_le_bytes()[..3].iter().enumerate() {
+        if *byte == 0 {
+            continue;
+        }
+        match target.get_mut(offset + i) {
+            Some(target_byte) => *target_byte = *byte,
+            None => return None,
+        }
+    }
+    if mantissa == 0 {
+        return None;
+    }
+    Some(target)
+}
+
+/// Compares two little endian 256-bit numbers.
+fn cmp_le(a: &[u8; 32], b: &[u8; 32]) -> Ordering {
+    a.iter().rev().cmp(b.iter().rev())
+}
+
 impl BlockHeader {
     pub fn from_stream(stream: &mut impl Read) -> io::Result<BlockHeader> {
         let version = stream.read_i32::<LittleEndian>()?;
@@ -60,6 +151,44 @@ impl BlockHeader {
         self.write_to_stream(&mut ser).unwrap();
         double_sha256(&ser)
     }
+
+    /// Whether the hash meets the target of `bits`, and the target is
+    /// within the proof of work limit of `network`.
+    pub fn check_proof_of_work(&self, network: Network) -> bool {
+        let target = match target_from_bits(self.bits) {
+            Some(target) => target,
+            None => return false,
+        };
+        let pow_limit = target_from_bits(pow_limit_bits(network)).unwrap();
+        cmp_le(&target, &pow_limit) != Ordering::Greater
+            && cmp_le(&self.hash(), &target) != Ordering::Greater
+    }
+
+    /// Expected number of hashes to find the header, 2^256 / target. The
+    /// +1 of the exact formula is dropped, which is negligible for comparing
+    /// the work of chains. Saturates at `u128::MAX`, 0 for invalid `bits`.
+    pub fn work(&self) -> u128 {
+        if target_from_bits(self.bits).is_none() {
+            return 0;
+        }
+        let exponent = (self.bits >> 24) as usize;
+        let mut mantissa = (self.bits & 0x007f_ffff) as u128;
+        let mut shift = 256;
+        if exponent <= 3 {
+            mantissa >>= 8 * (3 - exponent);
+        } else {
+            shift -= 8 * (exponent - 3);
+        }
+        if shift <= 127 {
+            return (1 << shift) / mantissa;
+        }
+        let work = (1 << 127) / mantissa;
+        if work.leading_zeros() as usize >= shift - 127 {
+            work << (shift - 127)
+        } else {
+            u128::MAX
+        }
+    }
 }
 
 impl std::fmt::Display for BlockHeader {
@@ -74,3 +203,96 @@ impl std::fmt::Display for BlockHeader {
         Ok(())
     }
 }
+
+impl Block {
+    pub fn from_stream(stream: &mut impl Read) -> io::Result<Block> {
+        let header = BlockHeader::from_stream(stream)?;
+        let num_txs = read_var_int(stream)?;
+        let mut txs = Vec::new();
+        for _ in 0..num_txs {
+            txs.push(Transaction::from_stream(stream)?);
+        }
+        Ok(Block { header, txs })
+    }
+
+    pub fn write_to_stream(&self, stream: &mut impl Write) -> io::Result<()> {
+        self.header.write_to_stream(stream)?;
+        write_var_int(stream, self.txs.len() as u64)?;
+        for tx in self.txs.iter() {
+            tx.write_to_stream(stream)?;
+        }
+        Ok(())
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        self.header.hash()
+    }
+
+    pub fn tx_hashes(&self) -> Vec<[u8; 32]> {
+        self.txs.iter().map(Transaction::hash).collect()
+    }
+
+    pub fn check_merkle_root(&self) -> bool {
+        merkle_root(&self.tx_hashes()) == self.header.merkle_root
+    }
+}
+
+#[test]
+fn test_proof_of_work() {
+    let genesis_hashes = [
+        (
+            Network::Mainnet,
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+        ),
+        (
+            Network::Testnet,
+            "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+        ),
+        (
+            Network::Regtest,
+            "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+        ),
+    ];
+    for (network, hash) in genesis_hashes.iter() {
+        let header = genesis(*network);
+        assert_eq!(tx_hash_to_hex(&header.hash()), *hash);
+        assert!(header.check_proof_of_work(*network));
+    }
+    assert!(!REGTEST_GENESIS.check_proof_of_work(Network::Mainnet));
+    let mut header = GENESIS;
+    header.nonce += 1;
+    assert!(!header.check_proof_of_work(Network::Mainnet));
+
+    let mut target = [0; 32];
+    target[26..29].copy_from_slice(&[0xff, 0xff, 0x00]);
+    assert_eq!(target_from_bits(0x1d00_ffff), Some(target));
+    assert_eq!(
+        target_from_bits(0x0300_0001).map(|target| target[0]),
+        Some(1)
+    );
+    assert_eq!(
+        target_from_bits(0x0200_0100).map(|target| target[0]),
+        Some(1)
+    );
+    assert_eq!(target_from_bits(0x0100_0001), None);
+    assert_eq!(target_from_bits(0x0480_0001), None);
+    assert_eq!(target_from_bits(0x2200_ffff), None);
+    assert!(target_from_bits(0x2100_00ff).is_some());
+
+    // difficulty 1 takes 2^32 hashes, give or take 2^16
+    assert_eq!(GENESIS.work(), 0x1_0001_0001);
+    assert_eq!(REGTEST_GENESIS.work(), 2);
+    header.bits = 0x0300_0001;
+    assert_eq!(header.work(), u128::MAX);
+}
+
+#[test]
+fn test_median_time_past() {
+    assert_eq!(median_time_past(&[]), 0);
+    assert_eq!(median_time_past(&[5]), 5);
+    assert_eq!(median_time_past(&[5, 1]), 5);
+    assert_eq!(median_time_past(&[3, 1, 2]), 2);
+    // only the last 11 count
+    let timestamps = (0..20).rev().collect::<Vec<_>>();
+    assert_eq!(median_time_past(&timestamps), 5);
+}
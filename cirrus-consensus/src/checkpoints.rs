@@ -0,0 +1,220 @@
+use cirrus_crypto::Network;
+
+/// Block hash hardcoded for a height. Headers at that height have to match
+/// and the chain may not be reorganized below it.
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint {
+    pub height: i32,
+    /// In display byte order, like block explorers show it.
+    pub hash_hex: &'static str,
+}
+
+const MAINNET_CHECKPOINTS: &[Checkpoint] = &[
+    Checkpoint {
+        height: 11_111,
+        hash_hex: "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d",
+    },
+    Checkpoint {
+        height: 33_333,
+        hash_hex: "000000002dd5588a74784eaa7ab0507a18ad16a236e7b1ce69f00d7ddfb5d0a6",
+    },
+    Checkpoint {
+        height: 74_000,
+        hash_hex: "0000000000573993a3c9e41ce34471c079dcf5f52a0e824a81e7f953b8661a20",
+    },
+    Checkpoint {
+        height: 105_000,
+        hash_hex: "00000000000291ce28027faea320c8d2b054b2e0fe44a773f3eefb151d6bdc97",
+    },
+    Checkpoint {
+        height: 134_444,
+        hash_hex: "00000000000005b12ffd4cd315cd34ffd4a594f430ac814c91184a0d42d2b0fe",
+    },
+    Checkpoint {
+        height: 168_000,
+        hash_hex: "000000000000099e61ea72015e79632f216fe6cb33d7899acb35b75c8303b763",
+    },
+    Checkpoint {
+        height: 193_000,
+        hash_hex: "000000000000059f452a5f7340de6682a977387c17010ff6e6c3bd83ca8b1317",
+    },
+    Checkpoint {
+        height: 210_000,
+        hash_hex: "000000000000048b95347e83192f69cf0366076336c639f9b7228e9ba171342e",
+    },
+    Checkpoint {
+        height: 216_116,
+        hash_hex: "00000000000001b4f4b433e81ee46494af945cf96014816a4e2370f11b23df4e",
+    },
+    Checkpoint {
+        height: 225_430,
+        hash_hex: "00000000000001c108384350f74090433e7fcf79a606b8e797f065b130575932",
+    },
+    Checkpoint {
+        height: 250_000,
+        hash_hex: "000000000000003887df1f29024b06fc2200b55f8af8f35453d7be294df2d214",
+    },
+    Checkpoint {
+        height: 279_000,
+        hash_hex: "0000000000000001ae8c72a0b0c301f67e3afca10e819efa9041e458e9bd7e40",
+    },
+    Checkpoint {
+        height: 295_000,
+        hash_hex: "00000000000000004d9b4ef50f0f9d686fd69db2e03af35a100370c64632a983",
+    },
+    // last common block of BTC and BCH
+    Checkpoint {
+        height: 478_558,
+        hash_hex: "0000000000000000011865af4122fe3b144e2cbeea86142e8ff2fb4107352d43",
+    },
+    // UAHF, first block of BCH
+    Checkpoint {
+        height: 478_559,
+        hash_hex: "000000000000000000651ef99cb9fcbe0dadde1d424bd9f15ff20136191a5eec",
+    },
+    // November 2017, new difficulty adjustment
+    Checkpoint {
+        height: 504_031,
+        hash_hex: "0000000000000000011ebf65b60d0a3de80b8175be709d653b4c1a1beeb6ab9c",
+    },
+    // May 2018 upgrade
+    Checkpoint {
+        height: 530_359,
+        hash_hex: "0000000000000000011ada8bd08f46074f44a8f155396f43e38acf9501c49103",
+    },
+    // November 2018 upgrade
+    Checkpoint {
+        height: 556_767,
+        hash_hex: "0000000000000000004626ff6e3b936941d341c5932ece4357eeccac44e6d56c",
+    },
+    // May 2019 upgrade
+    Checkpoint {
+        height: 582_680,
+        hash_hex: "000000000000000001b4b8e36aec7d4f9671a47872cb9a74dc16ca398c7dcc18",
+    },
+    // November 2019 upgrade
+    Checkpoint {
+        height: 609_136,
+        hash_hex: "000000000000000000b48bb207faac5ac655c313e41ac909322eaa694f5bc5b1",
+    },
+    // May 2020 upgrade
+    Checkpoint {
+        height: 635_259,
+        hash_hex: "00000000000000000033dfef1fc2d6a5d5520b078c55193a9bf498c5b27530f7",
+    },
+    // November 2020 upgrade
+    Checkpoint {
+        height: 661_648,
+        hash_hex: "0000000000000000029e471c41818d24b8b74c911071c4ef0b4a0509f9b5a8ce",
+    },
+];
+
+const TESTNET_CHECKPOINTS: &[Checkpoint] = &[
+    Checkpoint {
+        height: 546,
+        hash_hex: "000000002a936ca763904c3c35fce2f3556c559c0214345d31b1bcebf76acb70",
+    },
+    // UAHF
+    Checkpoint {
+        height: 1_155_875,
+        hash_hex: "00000000f17c850672894b9a75b63a1e72830bbd5f4c8889b5c1a80e7faef138",
+    },
+    // November 2017, new difficulty adjustment
+    Checkpoint {
+        height: 1_188_697,
+        hash_hex: "0000000000170ed0918077bde7b4d36cc4c91be69fa09211f748240dabe047fb",
+    },
+    // May 2018 upgrade
+    Checkpoint {
+        height: 1_233_070,
+        hash_hex: "0000000000000253c6201a2076663cfe4722e4c75f537552cc4ce989d15f7cd5",
+    },
+    // November 2018 upgrade
+    Checkpoint {
+        height: 1_267_996,
+        hash_hex: "00000000000001fae0095cd4bea16f1ce8ab63f3f660a03c6d8171485f484b24",
+    },
+    // May 2019 upgrade
+    Checkpoint {
+        height: 1_303_885,
+        hash_hex: "00000000000000479138892ef0e4fa478ccc938fb94df862ef5bde7e8dee23d3",
+    },
+    // November 2019 upgrade
+    Checkpoint {
+        height: 1_341_711,
+        hash_hex: "00000000fffc44ea2e202bd905a9fbbb9491ef9e9d5a9eed4039079229afa35b",
+    },
+    // May 2020 upgrade
+    Checkpoint {
+        height: 1_378_461,
+        hash_hex: "0000000099f5509b5f36b1926bcf82b21d936ebeadee811030dfbbb7fae915d7",
+    },
+    // November 2020 upgrade
+    Checkpoint {
+        height: 1_421_482,
+        hash_hex: "0000000023e0680a8a062b3cc289a4a341124ce7fcb6340ede207e194d73b60a",
+    },
+];
+
+impl Checkpoint {
+    /// The hash in the byte order of `BlockHeader::hash`.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hash = [0; 32];
+        hex::decode_to_slice(self.hash_hex, &mut hash).unwrap();
+        hash.reverse();
+        hash
+    }
+}
+
+/// Checkpoints of `network`, ordered by height.
+pub fn checkpoints(network: Network) -> &'static [Checkpoint] {
+    match network {
+        Network::Mainnet => MAINNET_CHECKPOINTS,
+        Network::Testnet => TESTNET_CHECKPOINTS,
+        Network::Regtest => &[],
+    }
+}
+
+pub fn checkpoint_at(network: Network, height: i32) -> Option<&'static Checkpoint> {
+    let checkpoints = checkpoints(network);
+    checkpoints
+        .binary_search_by_key(&height, |checkpoint| checkpoint.height)
+        .ok()
+        .map(|idx| &checkpoints[idx])
+}
+
+/// The highest checkpoint at or below `height`.
+pub fn last_checkpoint(network: Network, height: i32) -> Option<&'static Checkpoint> {
+    checkpoints(network)
+        .iter()
+        .rev()
+        .find(|checkpoint| checkpoint.height <= height)
+}
+
+#[test]
+fn test_checkpoints() {
+    use cashcontracts::tx_hash_to_hex;
+
+    for network in &[Network::Mainnet, Network::Testnet] {
+        let checkpoints = checkpoints(*network);
+        for pair in checkpoints.windows(2) {
+            assert!(pair[0].height < pair[1].height);
+        }
+        for checkpoint in checkpoints {
+            assert_eq!(tx_hash_to_hex(&checkpoint.hash()), checkpoint.hash_hex);
+            assert_eq!(&checkpoint.hash()[28..], &[0; 4]);
+        }
+    }
+    let fork_block = checkpoint_at(Network::Mainnet, 478_559).unwrap();
+    assert_eq!(
+        fork_block.hash_hex,
+        "000000000000000000651ef99cb9fcbe0dadde1d424bd9f15ff20136191a5eec"
+    );
+    assert!(checkpoint_at(Network::Mainnet, 478_560).is_none());
+    assert_eq!(
+        last_checkpoint(Network::Mainnet, 500_000).unwrap().height,
+        478_559
+    );
+    assert!(last_checkpoint(Network::Mainnet, 11_110).is_none());
+    assert!(last_checkpoint(Network::Regtest, 1_000_000).is_none());
+}
@@ -0,0 +1,218 @@
+use crate::block::{pow_limit_bits, target_from_bits};
+use cirrus_crypto::Network;
+use std::cmp::Ordering;
+
+/// Target time between blocks, in seconds.
+pub const TARGET_BLOCK_TIME: i64 = 10 * 60;
+
+/// Block the ASERT difficulty adjustment of the November 2020 upgrade is
+/// anchored at: the first block whose median time past reached the
+/// activation. Blocks after it use ASERT.
+#[derive(Clone, Copy, Debug)]
+pub struct AsertAnchor {
+    pub height: i32,
+    pub bits: u32,
+    /// Timestamp of the anchor's parent.
+    pub prev_timestamp: u32,
+    /// Time, in seconds, in which the target doubles or halves if blocks
+    /// come a half-life late or early.
+    pub half_life: i64,
+}
+
+const MAINNET_ASERT_ANCHOR: AsertAnchor = AsertAnchor {
+    height: 661_647,
+    bits: 0x1804_dafe,
+    prev_timestamp: 1_605_447_844,
+    half_life: 2 * 24 * 60 * 60,
+};
+
+const TESTNET_ASERT_ANCHOR: AsertAnchor = AsertAnchor {
+    height: 1_421_481,
+    bits: 0x1d00_ffff,
+    prev_timestamp: 1_605_445_400,
+    half_life: 60 * 60,
+};
+
+/// ASERT anchor of `network`, None on regtest, which doesn't adjust the
+/// difficulty.
+pub fn asert_anchor(network: Network) -> Option<AsertAnchor> {
+    match network {
+        Network::Mainnet => Some(MAINNET_ASERT_ANCHOR),
+        Network::Testnet => Some(TESTNET_ASERT_ANCHOR),
+        Network::Regtest => None,
+    }
+}
+
+/// The `bits` required for a block with `timestamp` whose parent is at
+/// `prev_height` with `prev_timestamp`. None if ASERT doesn't apply to it,
+/// i.e. on regtest and below the anchor, where checkpoints pin the chain.
+pub fn next_bits(
+    network: Network,
+    prev_height: i32,
+    prev_timestamp: u32,
+    timestamp: u32,
+) -> Option<u32> {
+    let anchor = asert_anchor(network)?;
+    if prev_height < anchor.height {
+        return None;
+    }
+    // testnet allows min difficulty blocks if no block was found for 20 min
+    if network == Network::Testnet
+        && i64::from(timestamp) > i64::from(prev_timestamp) + 2 * TARGET_BLOCK_TIME
+    {
+        return Some(pow_limit_bits(network));
+    }
+    let pow_limit = U256::from_bits(pow_limit_bits(network))?;
+    let anchor_target = U256::from_bits(anchor.bits)?;
+    let time_diff = i64::from(prev_timestamp) - i64::from(anchor.prev_timestamp);
+    let height_diff = i64::from(prev_height - anchor.height);
+    let exponent = ((time_diff - TARGET_BLOCK_TIME * (height_diff + 1)) * 65536) / anchor.half_life;
+    let mut shifts = exponent >> 16;
+    let frac = u64::from(exponent as u16);
+    // 65536 * 2^(frac / 65536), approximated by a cubic polynomial
+    let factor = 65536
+        + (195_766_423_245_049u64
+            .wrapping_mul(frac)
+            .wrapping_add(971_821_376u64.wrapping_mul(frac * frac))
+            .wrapping_add(5127u64.wrapping_mul(frac * frac * frac))
+            .wrapping_add(1 << 47)
+            >> 48);
+    let mut target = anchor_target.mul_u64(factor);
+    shifts -= 16;
+    if shifts <= 0 {
+        target = target.shr(-shifts as u32);
+    } else {
+        let shifted = target.shl(shifts as u32);
+        target = if shifted.shr(shifts as u32) == target {
+            shifted
+        } else {
+            pow_limit
+        };
+    }
+    if target == U256::ZERO {
+        target = U256::ONE;
+    } else if target.cmp(&pow_limit) == Ordering::Greater {
+        target = pow_limit;
+    }
+    Some(target.to_bits())
+}
+
+/// Unsigned 256-bit integer, least significant limb first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct U256([u64; 4]);
+
+impl U256 {
+    const ZERO: U256 = U256([0; 4]);
+    const ONE: U256 = U256([1, 0, 0, 0]);
+
+    fn from_bits(bits: u32) -> Option<U256> {
+        let target = target_from_bits(bits)?;
+        let mut limbs = [0; 4];
+        for (limb, bytes) in limbs.iter_mut().zip(target.chunks(8)) {
+            let mut limb_bytes = [0; 8];
+            limb_bytes.copy_from_slice(bytes);
+            *limb = u64::from_le_bytes(limb_bytes);
+        }
+        Some(U256(limbs))
+    }
+
+    /// Compact form, rounding down like bitcoind's `GetCompact`.
+    fn to_bits(self) -> u32 {
+        let mut size = self.num_bits().div_ceil(8);
+        let mut compact = if size <= 3 {
+            (self.0[0] << (8 * (3 - size))) as u32
+        } else {
+            self.shr(8 * (size - 3)).0[0] as u32
+        };
+        if compact & 0x0080_0000 != 0 {
+            compact >>= 8;
+            size += 1;
+        }
+        compact | (size << 24)
+    }
+
+    fn num_bits(self) -> u32 {
+        for (i, limb) in self.0.iter().enumerate().rev() {
+            if *limb != 0 {
+                return 64 * i as u32 + 64 - limb.leading_zeros();
+            }
+        }
+        0
+    }
+
+    fn mul_u64(self, factor: u64) -> U256 {
+        let mut result = [0; 4];
+        let mut carry = 0u128;
+        for (result, limb) in result.iter_mut().zip(self.0.iter()) {
+            let product = u128::from(*limb) * u128::from(factor) + carry;
+            *result = product as u64;
+            carry = product >> 64;
+        }
+        U256(result)
+    }
+
+    fn shl(self, shift: u32) -> U256 {
+        let mut result = [0; 4];
+        let (limb_shift, bit_shift) = ((shift / 64) as usize, shift % 64);
+        for (i, limb) in result.iter_mut().enumerate().skip(limb_shift) {
+            *limb = self.0[i - limb_shift] << bit_shift;
+            if bit_shift > 0 && i > limb_shift {
+                *limb |= self.0[i - limb_shift - 1] >> (64 - bit_shift);
+            }
+        }
+        U256(result)
+    }
+
+    fn shr(self, shift: u32) -> U256 {
+        let mut result = [0; 4];
+        let (limb_shift, bit_shift) = ((shift / 64) as usize, shift % 64);
+        let num_limbs = 4usize.saturating_sub(limb_shift);
+        for (i, limb) in result.iter_mut().enumerate().take(num_limbs) {
+            *limb = self.0[i + limb_shift] >> bit_shift;
+            if bit_shift > 0 && i + limb_shift + 1 < 4 {
+                *limb |= self.0[i + limb_shift + 1] << (64 - bit_shift);
+            }
+        }
+        U256(result)
+    }
+
+    fn cmp(&self, other: &U256) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+#[test]
+fn test_next_bits() {
+    let anchor = MAINNET_ASERT_ANCHOR;
+    let next = |height_diff: i32, time_diff: i64| {
+        let prev_timestamp = (i64::from(anchor.prev_timestamp) + time_diff) as u32;
+        next_bits(
+            Network::Mainnet,
+            anchor.height + height_diff,
+            prev_timestamp,
+            prev_timestamp + 600,
+        )
+    };
+    // on schedule, the target stays; a half-life off, it doubles or halves
+    assert_eq!(next(0, 600), Some(anchor.bits));
+    assert_eq!(next(1000, 600 * 1001), Some(anchor.bits));
+    assert_eq!(next(0, 600 + anchor.half_life), Some(0x1809_b5fc));
+    assert_eq!(next(0, 600 - anchor.half_life), Some(0x1802_6d7f));
+    // the target is capped at the proof of work limit
+    assert_eq!(next(0, 600 + 100 * anchor.half_life), Some(0x1d00_ffff));
+    let slightly_late = next(0, 700).unwrap();
+    assert!(slightly_late > anchor.bits && slightly_late < 0x1809_b5fc);
+    assert_eq!(next(-1, 600), None);
+    assert_eq!(next_bits(Network::Regtest, 1_000_000, 0, 600), None);
+
+    let testnet = TESTNET_ASERT_ANCHOR;
+    // a half-life early, unless no block was found for 20 min
+    let prev_timestamp = testnet.prev_timestamp + 600 - 3600;
+    let next = |timestamp| next_bits(Network::Testnet, testnet.height, prev_timestamp, timestamp);
+    assert_eq!(next(prev_timestamp + 1200), Some(0x1c7f_ff80));
+    assert_eq!(next(prev_timestamp + 1201), Some(0x1d00_ffff));
+
+    for bits in &[0x1804_dafe, 0x1d00_ffff, 0x207f_ffff, 0x0301_0000] {
+        assert_eq!(U256::from_bits(*bits).unwrap().to_bits(), *bits);
+    }
+}
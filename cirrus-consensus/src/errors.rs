@@ -0,0 +1,59 @@
+pub mod script {
+    use error_chain::error_chain;
+
+    error_chain! {
+        errors {
+            EvalFalse {}
+            OpReturn {}
+            ScriptSize {}
+            PushSize {}
+            OpCount {}
+            StackSize {}
+            SigCount {}
+            PubkeyCount {}
+            InvalidOperandSize {}
+            InvalidNumberRange {}
+            IntegerOverflow {}
+            ImpossibleEncoding {}
+            InvalidSplitRange {}
+            Verify {}
+            EqualVerify {}
+            CheckMultisigVerify {}
+            CheckSigVerify {}
+            CheckDataSigVerify {}
+            NumEqualVerify {}
+            BadOpcode {}
+            DisabledOpcode {}
+            InvalidStackOperation {}
+            InvalidAltstackOperation {}
+            UnbalancedConditional {}
+            NegativeLockTime {}
+            UnsatisfiedLockTime {}
+            InvalidSigHashType {}
+            SigDer {}
+            MinimalData {}
+            SigPushOnly {}
+            SigHighS {}
+            SigNullDummy {}
+            PubkeyType {}
+            CleanStack {}
+            MinimalIf {}
+            SigNullFail {}
+            DiscourageUpgradableNops {}
+            NonCompressedPubkey {}
+            IllegalForkId {}
+            MustUseForkId {}
+            DivByZero {}
+            ModByZero {}
+            SigBadLength {}
+            SigNonSchnorr {}
+            InvalidBitfieldSize {}
+            InvalidBitRange {}
+            InvalidBitCount {}
+            InputSigChecks {}
+            ContextNotPresent {}
+            InvalidTxInputIndex {}
+            InvalidTxOutputIndex {}
+        }
+    }
+}
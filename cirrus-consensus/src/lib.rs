@@ -1,5 +1,21 @@
+#![recursion_limit = "256"]
+
 mod block;
 mod bloom;
+mod checkpoints;
+mod difficulty;
+pub mod errors;
+mod merkle;
+pub mod script;
+mod token;
+mod tx;
+mod upgrades;
 
 pub use block::*;
 pub use bloom::*;
+pub use checkpoints::*;
+pub use difficulty::*;
+pub use merkle::*;
+pub use token::*;
+pub use tx::*;
+pub use upgrades::*;
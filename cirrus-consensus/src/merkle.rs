@@ -0,0 +1,133 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use cashcontracts::double_sha256;
+use cashcontracts::serialize::{read_var_int, read_var_str, write_var_int, write_var_str};
+use std::{
+    io,
+    io::{Read, Write},
+};
+
+#[derive(Clone, Debug)]
+pub struct PartialMerkleTree {
+    pub num_txs: u32,
+    pub hashes: Vec<[u8; 32]>,
+    pub flags: Vec<u8>,
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut concat = [0; 64];
+    concat[..32].copy_from_slice(left);
+    concat[32..].copy_from_slice(right);
+    double_sha256(&concat)
+}
+
+pub fn merkle_root(tx_hashes: &[[u8; 32]]) -> [u8; 32] {
+    if tx_hashes.is_empty() {
+        return [0; 32];
+    }
+    let mut level = tx_hashes.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+    }
+    level[0]
+}
+
+struct Traversal<'a> {
+    tree: &'a PartialMerkleTree,
+    bits_used: usize,
+    hashes_used: usize,
+    matches: Vec<[u8; 32]>,
+}
+
+impl PartialMerkleTree {
+    pub fn from_stream(stream: &mut impl Read) -> io::Result<PartialMerkleTree> {
+        let num_txs = stream.read_u32::<LittleEndian>()?;
+        let num_hashes = read_var_int(stream)?;
+        let mut hashes = Vec::new();
+        for _ in 0..num_hashes {
+            let mut hash = [0; 32];
+            stream.read_exact(&mut hash)?;
+            hashes.push(hash);
+        }
+        let flags = read_var_str(stream)?;
+        Ok(PartialMerkleTree {
+            num_txs,
+            hashes,
+            flags,
+        })
+    }
+
+    pub fn write_to_stream(&self, stream: &mut impl Write) -> io::Result<()> {
+        stream.write_u32::<LittleEndian>(self.num_txs)?;
+        write_var_int(stream, self.hashes.len() as u64)?;
+        for hash in self.hashes.iter() {
+            stream.write_all(hash)?;
+        }
+        write_var_str(stream, &self.flags)?;
+        Ok(())
+    }
+
+    fn tree_width(&self, height: u32) -> usize {
+        (self.num_txs as usize + (1 << height) - 1) >> height
+    }
+
+    /// Walks the tree and returns the computed merkle root together with the
+    /// matched tx hashes, or `None` if the tree is malformed.
+    pub fn extract_matches(&self) -> Option<([u8; 32], Vec<[u8; 32]>)> {
+        if self.num_txs == 0 || self.hashes.len() > self.num_txs as usize {
+            return None;
+        }
+        if self.flags.len() * 8 < self.hashes.len() {
+            return None;
+        }
+        let mut height = 0;
+        while self.tree_width(height) > 1 {
+            height += 1;
+        }
+        let mut traversal = Traversal {
+            tree: self,
+            bits_used: 0,
+            hashes_used: 0,
+            matches: Vec::new(),
+        };
+        let root = traversal.traverse(height, 0)?;
+        if traversal.bits_used.div_ceil(8) != self.flags.len()
+            || traversal.hashes_used != self.hashes.len()
+        {
+            return None;
+        }
+        Some((root, traversal.matches))
+    }
+}
+
+impl<'a> Traversal<'a> {
+    fn traverse(&mut self, height: u32, pos: usize) -> Option<[u8; 32]> {
+        let flags = &self.tree.flags;
+        if self.bits_used >= flags.len() * 8 {
+            return None;
+        }
+        let flag = (flags[self.bits_used / 8] >> (self.bits_used % 8)) & 1 == 1;
+        self.bits_used += 1;
+        if height == 0 || !flag {
+            let hash = *self.tree.hashes.get(self.hashes_used)?;
+            self.hashes_used += 1;
+            if height == 0 && flag {
+                self.matches.push(hash);
+            }
+            return Some(hash);
+        }
+        let left = self.traverse(height - 1, pos * 2)?;
+        let right = if pos * 2 + 1 < self.tree.tree_width(height - 1) {
+            let right = self.traverse(height - 1, pos * 2 + 1)?;
+            if right == left {
+                return None;
+            }
+            right
+        } else {
+            left
+        };
+        Some(hash_pair(&left, &right))
+    }
+}
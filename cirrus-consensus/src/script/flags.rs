@@ -0,0 +1,127 @@
+use bitflags::bitflags;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct ScriptFlags: u32 {
+        const P2SH = 1 << 0;
+        const STRICTENC = 1 << 1;
+        const DERSIG = 1 << 2;
+        const LOW_S = 1 << 3;
+        const NULLDUMMY = 1 << 4;
+        const SIGPUSHONLY = 1 << 5;
+        const MINIMALDATA = 1 << 6;
+        const DISCOURAGE_UPGRADABLE_NOPS = 1 << 7;
+        const CLEANSTACK = 1 << 8;
+        const CHECKLOCKTIMEVERIFY = 1 << 9;
+        const CHECKSEQUENCEVERIFY = 1 << 10;
+        const MINIMALIF = 1 << 13;
+        const NULLFAIL = 1 << 14;
+        const COMPRESSED_PUBKEYTYPE = 1 << 15;
+        const ENABLE_SIGHASH_FORKID = 1 << 16;
+        const ENABLE_CHECKDATASIG = 1 << 18;
+        const DISALLOW_SEGWIT_RECOVERY = 1 << 20;
+        const ENABLE_SCHNORR_MULTISIG = 1 << 21;
+        const INPUT_SIGCHECKS = 1 << 22;
+        const ENABLE_REVERSEBYTES = 1 << 23;
+        const INTEGERS_64_BIT = 1 << 24;
+        const NATIVE_INTROSPECTION = 1 << 25;
+        const ENABLE_P2SH_32 = 1 << 26;
+        const ENABLE_TOKENS = 1 << 27;
+    }
+}
+
+/// First block heights of the mainnet consensus upgrades affecting script.
+pub const BIP16_HEIGHT: i32 = 173_805;
+pub const BIP66_HEIGHT: i32 = 363_725;
+pub const BIP65_HEIGHT: i32 = 388_381;
+pub const CSV_HEIGHT: i32 = 419_328;
+pub const UAHF_HEIGHT: i32 = 478_559;
+pub const DAA_HEIGHT: i32 = 504_031;
+pub const MAGNETIC_ANOMALY_HEIGHT: i32 = 556_767;
+pub const GREAT_WALL_HEIGHT: i32 = 582_680;
+pub const GRAVITON_HEIGHT: i32 = 609_136;
+pub const PHONON_HEIGHT: i32 = 635_259;
+pub const UPGRADE8_HEIGHT: i32 = 740_238;
+pub const UPGRADE9_HEIGHT: i32 = 792_773;
+
+impl ScriptFlags {
+    /// Consensus script flags for a block at `height` on mainnet.
+    pub fn for_height(height: i32) -> ScriptFlags {
+        let mut flags = ScriptFlags::empty();
+        if height >= BIP16_HEIGHT {
+            flags |= ScriptFlags::P2SH;
+        }
+        if height >= BIP66_HEIGHT {
+            flags |= ScriptFlags::DERSIG;
+        }
+        if height >= BIP65_HEIGHT {
+            flags |= ScriptFlags::CHECKLOCKTIMEVERIFY;
+        }
+        if height >= CSV_HEIGHT {
+            flags |= ScriptFlags::CHECKSEQUENCEVERIFY;
+        }
+        if height >= UAHF_HEIGHT {
+            flags |= ScriptFlags::STRICTENC | ScriptFlags::ENABLE_SIGHASH_FORKID;
+        }
+        if height >= DAA_HEIGHT {
+            flags |= ScriptFlags::LOW_S | ScriptFlags::NULLFAIL;
+        }
+        if height >= MAGNETIC_ANOMALY_HEIGHT {
+            flags |= ScriptFlags::ENABLE_CHECKDATASIG
+                | ScriptFlags::SIGPUSHONLY
+                | ScriptFlags::CLEANSTACK;
+        }
+        if height >= GRAVITON_HEIGHT {
+            flags |= ScriptFlags::ENABLE_SCHNORR_MULTISIG | ScriptFlags::MINIMALDATA;
+        }
+        if height >= PHONON_HEIGHT {
+            flags |= ScriptFlags::ENABLE_REVERSEBYTES | ScriptFlags::INPUT_SIGCHECKS;
+        }
+        if height >= UPGRADE8_HEIGHT {
+            flags |= ScriptFlags::INTEGERS_64_BIT | ScriptFlags::NATIVE_INTROSPECTION;
+        }
+        if height >= UPGRADE9_HEIGHT {
+            flags |= ScriptFlags::ENABLE_P2SH_32 | ScriptFlags::ENABLE_TOKENS;
+        }
+        flags
+    }
+
+    pub fn from_names(names: &str) -> Option<ScriptFlags> {
+        let mut flags = ScriptFlags::empty();
+        for name in names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            flags |= match name {
+                "NONE" => ScriptFlags::empty(),
+                "P2SH" => ScriptFlags::P2SH,
+                "STRICTENC" => ScriptFlags::STRICTENC,
+                "DERSIG" => ScriptFlags::DERSIG,
+                "LOW_S" => ScriptFlags::LOW_S,
+                "NULLDUMMY" => ScriptFlags::NULLDUMMY,
+                "SIGPUSHONLY" => ScriptFlags::SIGPUSHONLY,
+                "MINIMALDATA" => ScriptFlags::MINIMALDATA,
+                "DISCOURAGE_UPGRADABLE_NOPS" => ScriptFlags::DISCOURAGE_UPGRADABLE_NOPS,
+                "CLEANSTACK" => ScriptFlags::CLEANSTACK,
+                "CHECKLOCKTIMEVERIFY" => ScriptFlags::CHECKLOCKTIMEVERIFY,
+                "CHECKSEQUENCEVERIFY" => ScriptFlags::CHECKSEQUENCEVERIFY,
+                "MINIMALIF" => ScriptFlags::MINIMALIF,
+                "NULLFAIL" => ScriptFlags::NULLFAIL,
+                "COMPRESSED_PUBKEYTYPE" => ScriptFlags::COMPRESSED_PUBKEYTYPE,
+                "SIGHASH_FORKID" => ScriptFlags::ENABLE_SIGHASH_FORKID,
+                "CHECKDATASIG" => ScriptFlags::ENABLE_CHECKDATASIG,
+                "DISALLOW_SEGWIT_RECOVERY" => ScriptFlags::DISALLOW_SEGWIT_RECOVERY,
+                "SCHNORR_MULTISIG" => ScriptFlags::ENABLE_SCHNORR_MULTISIG,
+                "INPUT_SIGCHECKS" => ScriptFlags::INPUT_SIGCHECKS,
+                "REVERSEBYTES" => ScriptFlags::ENABLE_REVERSEBYTES,
+                "64_BIT_INTEGERS" => ScriptFlags::INTEGERS_64_BIT,
+                "NATIVE_INTROSPECTION" => ScriptFlags::NATIVE_INTROSPECTION,
+                "P2SH_32" => ScriptFlags::ENABLE_P2SH_32,
+                "TOKENS" => ScriptFlags::ENABLE_TOKENS,
+                _ => return None,
+            };
+        }
+        Some(flags)
+    }
+}
@@ -0,0 +1,1016 @@
+use crate::errors::script::{Error, ErrorKind::*, Result};
+use crate::script::num::*;
+use crate::script::opcodes::*;
+use crate::script::ops::*;
+use crate::script::sig_encoding::*;
+use crate::script::sighash::*;
+use crate::script::ScriptFlags;
+use crate::token::TokenData;
+use crate::tx::{Transaction, TxOutput};
+use cirrus_crypto::PublicKey;
+use error_chain::bail;
+use ripemd160::Ripemd160;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+pub const MAX_STACK_SIZE: usize = 1000;
+pub const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+pub const LOCKTIME_THRESHOLD: i64 = 500_000_000;
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
+
+/// The tx being validated, used by the locktime and introspection opcodes.
+#[derive(Clone, Copy, Debug)]
+pub struct ScriptExecutionContext<'a> {
+    pub tx: &'a Transaction,
+    pub input_index: usize,
+    /// The outputs spent by the tx's inputs, in input order.
+    pub spent_outputs: &'a [TxOutput],
+    /// Shared between the inputs of `tx`, computed per signature if absent.
+    pub sighash_cache: Option<&'a SigHashCache>,
+}
+
+impl<'a> ScriptExecutionContext<'a> {
+    /// Whether `input_index` is an input of `tx` and `spent_outputs` has an
+    /// output for each input. Inconsistent contexts are treated as absent.
+    pub fn is_consistent(&self) -> bool {
+        self.input_index < self.tx.inputs.len() && self.spent_outputs.len() == self.tx.inputs.len()
+    }
+
+    pub fn spent_output(&self) -> Option<&'a TxOutput> {
+        self.spent_outputs.get(self.input_index)
+    }
+
+    pub fn sighash(
+        &self,
+        script_code: &[u8],
+        sighash_type: SigHashType,
+        flags: ScriptFlags,
+    ) -> Result<[u8; 32]> {
+        if !self.is_consistent() {
+            bail!(ContextNotPresent);
+        }
+        let spent_output = &self.spent_outputs[self.input_index];
+        let computed_cache;
+        let cache = match self.sighash_cache {
+            Some(cache) => cache,
+            None => {
+                computed_cache = SigHashCache::with_spent_outputs(self.tx, self.spent_outputs);
+                &computed_cache
+            }
+        };
+        sighash_spending(
+            cache,
+            self.tx,
+            self.input_index,
+            script_code,
+            spent_output,
+            sighash_type,
+            flags,
+        )
+    }
+}
+
+/// The context of `checker`, if it's consistent.
+fn consistent_context<C: SignatureChecker + ?Sized>(
+    checker: &C,
+) -> Option<&ScriptExecutionContext<'_>> {
+    checker.context().filter(|context| context.is_consistent())
+}
+
+/// Checks whatever needs data beyond the script itself. The defaults
+/// implement the consensus rules; checks that need the spending tx fail
+/// unless `context` is provided.
+pub trait SignatureChecker {
+    /// Verifies an ECDSA (DER) or Schnorr (64 byte) signature of `msg_hash`.
+    fn verify_signature(&self, sig: &[u8], pubkey: &[u8], msg_hash: &[u8; 32]) -> bool {
+        match PublicKey::from_slice(pubkey) {
+            Ok(pubkey) => pubkey.verify(msg_hash, sig),
+            Err(_) => false,
+        }
+    }
+
+    /// Checks a tx signature with appended sighash type.
+    fn check_sig(&self, sig: &[u8], pubkey: &[u8], script_code: &[u8], flags: ScriptFlags) -> bool {
+        let context = match consistent_context(self) {
+            Some(context) => context,
+            None => return false,
+        };
+        let (sighash_type, sig) = match sig.split_last() {
+            Some((sighash_type, sig)) => (SigHashType::from(*sighash_type), sig),
+            None => return false,
+        };
+        match context.sighash(script_code, sighash_type, flags) {
+            Ok(msg_hash) => self.verify_signature(sig, pubkey, &msg_hash),
+            Err(_) => false,
+        }
+    }
+
+    fn context(&self) -> Option<&ScriptExecutionContext<'_>> {
+        None
+    }
+
+    fn check_lock_time(&self, lock_time: i64) -> bool {
+        let context = match consistent_context(self) {
+            Some(context) => context,
+            None => return false,
+        };
+        let tx_lock_time = context.tx.lock_time as i64;
+        // Lock by block height and by time must not be mixed.
+        if (tx_lock_time < LOCKTIME_THRESHOLD) != (lock_time < LOCKTIME_THRESHOLD) {
+            return false;
+        }
+        if lock_time > tx_lock_time {
+            return false;
+        }
+        // A final input disables nLockTime, which would bypass the check.
+        context.tx.inputs[context.input_index].sequence != SEQUENCE_FINAL
+    }
+
+    fn check_sequence(&self, sequence: i64) -> bool {
+        let context = match consistent_context(self) {
+            Some(context) => context,
+            None => return false,
+        };
+        let tx_sequence = context.tx.inputs[context.input_index].sequence as i64;
+        if (context.tx.version as u32) < 2 {
+            return false;
+        }
+        if tx_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG as i64 != 0 {
+            return false;
+        }
+        let mask = (SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK) as i64;
+        let tx_sequence_masked = tx_sequence & mask;
+        let sequence_masked = sequence & mask;
+        let type_flag = SEQUENCE_LOCKTIME_TYPE_FLAG as i64;
+        if (tx_sequence_masked < type_flag) != (sequence_masked < type_flag) {
+            return false;
+        }
+        sequence_masked <= tx_sequence_masked
+    }
+}
+
+pub struct BaseSignatureChecker;
+
+impl SignatureChecker for BaseSignatureChecker {}
+
+impl<'a> SignatureChecker for ScriptExecutionContext<'a> {
+    fn context(&self) -> Option<&ScriptExecutionContext<'_>> {
+        Some(self)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ScriptMetrics {
+    pub sig_checks: usize,
+}
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>> {
+    match stack.pop() {
+        Some(item) => Ok(item),
+        None => bail!(InvalidStackOperation),
+    }
+}
+
+/// Item at `depth` from the top of the stack, 1 being the topmost item.
+fn top(stack: &[Vec<u8>], depth: usize) -> Result<&Vec<u8>> {
+    if depth == 0 || depth > stack.len() {
+        bail!(InvalidStackOperation);
+    }
+    Ok(&stack[stack.len() - depth])
+}
+
+fn require_depth(stack: &[Vec<u8>], depth: usize) -> Result<()> {
+    if stack.len() < depth {
+        bail!(InvalidStackOperation);
+    }
+    Ok(())
+}
+
+fn encode_bool(value: bool) -> Vec<u8> {
+    if value {
+        vec![1]
+    } else {
+        vec![]
+    }
+}
+
+fn is_opcode_disabled(opcode: u8, flags: ScriptFlags) -> bool {
+    match opcode {
+        OP_INVERT | OP_2MUL | OP_2DIV | OP_LSHIFT | OP_RSHIFT => true,
+        OP_MUL => !flags.contains(ScriptFlags::INTEGERS_64_BIT),
+        _ => false,
+    }
+}
+
+fn cleanup_script_code(script_code: Vec<u8>, sig: &[u8], flags: ScriptFlags) -> Vec<u8> {
+    let sighash_type = SigHashType::from_sig(sig);
+    if !flags.contains(ScriptFlags::ENABLE_SIGHASH_FORKID) || !sighash_type.has_forkid() {
+        return find_and_delete(&script_code, sig);
+    }
+    script_code
+}
+
+fn decode_bitfield(bitfield: &[u8], size: usize) -> Result<u32> {
+    if size.div_ceil(8) != bitfield.len() {
+        bail!(InvalidBitfieldSize);
+    }
+    let mut bits = 0u32;
+    for (i, byte) in bitfield.iter().enumerate() {
+        bits |= (*byte as u32) << (8 * i);
+    }
+    let mask = ((1u64 << size) - 1) as u32;
+    if bits & mask != bits {
+        bail!(InvalidBitRange);
+    }
+    Ok(bits)
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+    Sha256::digest(data).to_vec()
+}
+
+fn ripemd160(data: &[u8]) -> Vec<u8> {
+    Ripemd160::digest(data).to_vec()
+}
+
+struct Interpreter<'a> {
+    flags: ScriptFlags,
+    checker: &'a dyn SignatureChecker,
+    metrics: &'a mut ScriptMetrics,
+    require_minimal: bool,
+    max_num_size: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    fn pop_num(&self, stack: &mut Vec<Vec<u8>>) -> Result<i64> {
+        decode_num(&pop(stack)?, self.require_minimal, self.max_num_size)
+    }
+
+    fn top_num(&self, stack: &[Vec<u8>], depth: usize) -> Result<i64> {
+        decode_num(top(stack, depth)?, self.require_minimal, self.max_num_size)
+    }
+
+    /// Results of arithmetic must be representable with the allowed number size.
+    fn check_num(&self, num: Option<i64>) -> Result<i64> {
+        match num {
+            Some(num) if num != i64::MIN => Ok(num),
+            _ => bail!(IntegerOverflow),
+        }
+    }
+
+    fn context(&self) -> Result<&ScriptExecutionContext<'_>> {
+        if !self.flags.contains(ScriptFlags::NATIVE_INTROSPECTION) {
+            bail!(BadOpcode);
+        }
+        consistent_context(self.checker).ok_or_else(|| Error::from(ContextNotPresent))
+    }
+
+    fn eval(&mut self, stack: &mut Vec<Vec<u8>>, script: &[u8]) -> Result<()> {
+        if script.len() > MAX_SCRIPT_SIZE {
+            bail!(ScriptSize);
+        }
+        let flags = self.flags;
+        let mut pc = 0;
+        let mut begin_code = 0;
+        let mut cond_stack = Vec::<bool>::new();
+        let mut alt_stack = Vec::<Vec<u8>>::new();
+        let mut op_count = 0;
+        while pc < script.len() {
+            let exec = !cond_stack.contains(&false);
+            let (opcode, data) = read_op(script, &mut pc)?;
+            if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                bail!(PushSize);
+            }
+            if opcode > OP_16 {
+                op_count += 1;
+                if op_count > MAX_OPS_PER_SCRIPT {
+                    bail!(OpCount);
+                }
+            }
+            if is_opcode_disabled(opcode, flags) {
+                bail!(DisabledOpcode);
+            }
+            if exec && opcode <= OP_PUSHDATA4 {
+                if self.require_minimal && !is_minimal_push(opcode, data) {
+                    bail!(MinimalData);
+                }
+                stack.push(data.to_vec());
+            } else if exec || (OP_IF..=OP_ENDIF).contains(&opcode) {
+                match opcode {
+                    OP_1NEGATE | OP_1..=OP_16 => {
+                        stack.push(encode_num(opcode as i64 - (OP_1 - 1) as i64));
+                    }
+
+                    OP_NOP => {}
+                    OP_NOP1 | OP_NOP4..=OP_NOP10 => {
+                        if flags.contains(ScriptFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                            bail!(DiscourageUpgradableNops);
+                        }
+                    }
+                    OP_CHECKLOCKTIMEVERIFY => {
+                        if !flags.contains(ScriptFlags::CHECKLOCKTIMEVERIFY) {
+                            if flags.contains(ScriptFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                                bail!(DiscourageUpgradableNops);
+                            }
+                            continue;
+                        }
+                        // Lock times can exceed 2^31, so 5 byte numbers are allowed here.
+                        let lock_time = decode_num(top(stack, 1)?, self.require_minimal, 5)?;
+                        if lock_time < 0 {
+                            bail!(NegativeLockTime);
+                        }
+                        if !self.checker.check_lock_time(lock_time) {
+                            bail!(UnsatisfiedLockTime);
+                        }
+                    }
+                    OP_CHECKSEQUENCEVERIFY => {
+                        if !flags.contains(ScriptFlags::CHECKSEQUENCEVERIFY) {
+                            if flags.contains(ScriptFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                                bail!(DiscourageUpgradableNops);
+                            }
+                            continue;
+                        }
+                        let sequence = decode_num(top(stack, 1)?, self.require_minimal, 5)?;
+                        if sequence < 0 {
+                            bail!(NegativeLockTime);
+                        }
+                        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG as i64 != 0 {
+                            continue;
+                        }
+                        if !self.checker.check_sequence(sequence) {
+                            bail!(UnsatisfiedLockTime);
+                        }
+                    }
+
+                    OP_IF | OP_NOTIF => {
+                        let mut value = false;
+                        if exec {
+                            let condition = match stack.pop() {
+                                Some(condition) => condition,
+                                None => bail!(UnbalancedConditional),
+                            };
+                            if flags.contains(ScriptFlags::MINIMALIF)
+                                && (condition.len() > 1
+                                    || (condition.len() == 1 && condition[0] != 1))
+                            {
+                                bail!(MinimalIf);
+                            }
+                            value = cast_to_bool(&condition);
+                            if opcode == OP_NOTIF {
+                                value = !value;
+                            }
+                        }
+                        cond_stack.push(value);
+                    }
+                    OP_ELSE => match cond_stack.last_mut() {
+                        Some(value) => *value = !*value,
+                        None => bail!(UnbalancedConditional),
+                    },
+                    OP_ENDIF => {
+                        if cond_stack.pop().is_none() {
+                            bail!(UnbalancedConditional);
+                        }
+                    }
+                    OP_VERIFY => {
+                        if !cast_to_bool(&pop(stack)?) {
+                            bail!(Verify);
+                        }
+                    }
+                    OP_RETURN => bail!(OpReturn),
+
+                    OP_TOALTSTACK => alt_stack.push(pop(stack)?),
+                    OP_FROMALTSTACK => match alt_stack.pop() {
+                        Some(item) => stack.push(item),
+                        None => bail!(InvalidAltstackOperation),
+                    },
+                    OP_2DROP => {
+                        require_depth(stack, 2)?;
+                        stack.truncate(stack.len() - 2);
+                    }
+                    OP_2DUP => {
+                        require_depth(stack, 2)?;
+                        let len = stack.len();
+                        stack.extend_from_within(len - 2..len);
+                    }
+                    OP_3DUP => {
+                        require_depth(stack, 3)?;
+                        let len = stack.len();
+                        stack.extend_from_within(len - 3..len);
+                    }
+                    OP_2OVER => {
+                        require_depth(stack, 4)?;
+                        let len = stack.len();
+                        stack.extend_from_within(len - 4..len - 2);
+                    }
+                    OP_2ROT => {
+                        require_depth(stack, 6)?;
+                        let len = stack.len();
+                        let items = stack.drain(len - 6..len - 4).collect::<Vec<_>>();
+                        stack.extend(items);
+                    }
+                    OP_2SWAP => {
+                        require_depth(stack, 4)?;
+                        let len = stack.len();
+                        stack.swap(len - 4, len - 2);
+                        stack.swap(len - 3, len - 1);
+                    }
+                    OP_IFDUP => {
+                        let item = top(stack, 1)?.clone();
+                        if cast_to_bool(&item) {
+                            stack.push(item);
+                        }
+                    }
+                    OP_DEPTH => stack.push(encode_num(stack.len() as i64)),
+                    OP_DROP => {
+                        pop(stack)?;
+                    }
+                    OP_DUP => {
+                        let item = top(stack, 1)?.clone();
+                        stack.push(item);
+                    }
+                    OP_NIP => {
+                        require_depth(stack, 2)?;
+                        let len = stack.len();
+                        stack.remove(len - 2);
+                    }
+                    OP_OVER => {
+                        let item = top(stack, 2)?.clone();
+                        stack.push(item);
+                    }
+                    OP_PICK | OP_ROLL => {
+                        let n = self.pop_num(stack)?;
+                        if n < 0 || n as usize >= stack.len() {
+                            bail!(InvalidStackOperation);
+                        }
+                        let idx = stack.len() - 1 - n as usize;
+                        let item = if opcode == OP_ROLL {
+                            stack.remove(idx)
+                        } else {
+                            stack[idx].clone()
+                        };
+                        stack.push(item);
+                    }
+                    OP_ROT => {
+                        require_depth(stack, 3)?;
+                        let len = stack.len();
+                        let item = stack.remove(len - 3);
+                        stack.push(item);
+                    }
+                    OP_SWAP => {
+                        require_depth(stack, 2)?;
+                        let len = stack.len();
+                        stack.swap(len - 2, len - 1);
+                    }
+                    OP_TUCK => {
+                        require_depth(stack, 2)?;
+                        let len = stack.len();
+                        let item = stack[len - 1].clone();
+                        stack.insert(len - 2, item);
+                    }
+
+                    OP_CAT => {
+                        require_depth(stack, 2)?;
+                        let second = pop(stack)?;
+                        let first = stack.last_mut().unwrap();
+                        if first.len() + second.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                            bail!(PushSize);
+                        }
+                        first.extend_from_slice(&second);
+                    }
+                    OP_SPLIT => {
+                        require_depth(stack, 2)?;
+                        let position = self.pop_num(stack)?;
+                        let data = stack.last_mut().unwrap();
+                        if position < 0 || position as usize > data.len() {
+                            bail!(InvalidSplitRange);
+                        }
+                        let second = data.split_off(position as usize);
+                        stack.push(second);
+                    }
+                    OP_NUM2BIN => {
+                        require_depth(stack, 2)?;
+                        let size = self.pop_num(stack)?;
+                        if size < 0 || size as usize > MAX_SCRIPT_ELEMENT_SIZE {
+                            bail!(PushSize);
+                        }
+                        let size = size as usize;
+                        let mut num = minimally_encode_num(&pop(stack)?);
+                        if num.len() > size {
+                            bail!(ImpossibleEncoding);
+                        }
+                        if num.len() < size {
+                            let sign = match num.last_mut() {
+                                Some(last) => {
+                                    let sign = *last & 0x80;
+                                    *last &= 0x7f;
+                                    sign
+                                }
+                                None => 0,
+                            };
+                            num.resize(size - 1, 0);
+                            num.push(sign);
+                        }
+                        stack.push(num);
+                    }
+                    OP_BIN2NUM => {
+                        let num = minimally_encode_num(&pop(stack)?);
+                        if num.len() > self.max_num_size {
+                            bail!(InvalidNumberRange);
+                        }
+                        stack.push(num);
+                    }
+                    OP_SIZE => {
+                        let size = top(stack, 1)?.len();
+                        stack.push(encode_num(size as i64));
+                    }
+                    OP_REVERSEBYTES => {
+                        if !flags.contains(ScriptFlags::ENABLE_REVERSEBYTES) {
+                            bail!(BadOpcode);
+                        }
+                        require_depth(stack, 1)?;
+                        stack.last_mut().unwrap().reverse();
+                    }
+
+                    OP_AND | OP_OR | OP_XOR => {
+                        require_depth(stack, 2)?;
+                        let second = pop(stack)?;
+                        let first = stack.last_mut().unwrap();
+                        if first.len() != second.len() {
+                            bail!(InvalidOperandSize);
+                        }
+                        for (a, b) in first.iter_mut().zip(second.iter()) {
+                            match opcode {
+                                OP_AND => *a &= b,
+                                OP_OR => *a |= b,
+                                _ => *a ^= b,
+                            }
+                        }
+                    }
+                    OP_EQUAL | OP_EQUALVERIFY => {
+                        require_depth(stack, 2)?;
+                        let second = pop(stack)?;
+                        let first = pop(stack)?;
+                        let equal = first == second;
+                        if opcode == OP_EQUALVERIFY {
+                            if !equal {
+                                bail!(EqualVerify);
+                            }
+                        } else {
+                            stack.push(encode_bool(equal));
+                        }
+                    }
+
+                    OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                        let num = self.pop_num(stack)?;
+                        let result = match opcode {
+                            OP_1ADD => self.check_num(num.checked_add(1))?,
+                            OP_1SUB => self.check_num(num.checked_sub(1))?,
+                            OP_NEGATE => -num,
+                            OP_ABS => num.abs(),
+                            OP_NOT => (num == 0) as i64,
+                            _ => (num != 0) as i64,
+                        };
+                        stack.push(encode_num(result));
+                    }
+                    OP_ADD
+                    | OP_SUB
+                    | OP_MUL
+                    | OP_DIV
+                    | OP_MOD
+                    | OP_BOOLAND
+                    | OP_BOOLOR
+                    | OP_NUMEQUAL
+                    | OP_NUMEQUALVERIFY
+                    | OP_NUMNOTEQUAL
+                    | OP_LESSTHAN
+                    | OP_GREATERTHAN
+                    | OP_LESSTHANOREQUAL
+                    | OP_GREATERTHANOREQUAL
+                    | OP_MIN
+                    | OP_MAX => {
+                        require_depth(stack, 2)?;
+                        let b = self.top_num(stack, 1)?;
+                        let a = self.top_num(stack, 2)?;
+                        stack.truncate(stack.len() - 2);
+                        let result = match opcode {
+                            OP_ADD => self.check_num(a.checked_add(b))?,
+                            OP_SUB => self.check_num(a.checked_sub(b))?,
+                            OP_MUL => self.check_num(a.checked_mul(b))?,
+                            OP_DIV => {
+                                if b == 0 {
+                                    bail!(DivByZero);
+                                }
+                                a / b
+                            }
+                            OP_MOD => {
+                                if b == 0 {
+                                    bail!(ModByZero);
+                                }
+                                a % b
+                            }
+                            OP_BOOLAND => (a != 0 && b != 0) as i64,
+                            OP_BOOLOR => (a != 0 || b != 0) as i64,
+                            OP_NUMEQUAL | OP_NUMEQUALVERIFY => (a == b) as i64,
+                            OP_NUMNOTEQUAL => (a != b) as i64,
+                            OP_LESSTHAN => (a < b) as i64,
+                            OP_GREATERTHAN => (a > b) as i64,
+                            OP_LESSTHANOREQUAL => (a <= b) as i64,
+                            OP_GREATERTHANOREQUAL => (a >= b) as i64,
+                            OP_MIN => a.min(b),
+                            _ => a.max(b),
+                        };
+                        if opcode == OP_NUMEQUALVERIFY {
+                            if result == 0 {
+                                bail!(NumEqualVerify);
+                            }
+                        } else {
+                            stack.push(encode_num(result));
+                        }
+                    }
+                    OP_WITHIN => {
+                        require_depth(stack, 3)?;
+                        let max = self.top_num(stack, 1)?;
+                        let min = self.top_num(stack, 2)?;
+                        let num = self.top_num(stack, 3)?;
+                        stack.truncate(stack.len() - 3);
+                        stack.push(encode_bool(min <= num && num < max));
+                    }
+
+                    OP_RIPEMD160 | OP_SHA1 | OP_SHA256 | OP_HASH160 | OP_HASH256 => {
+                        let data = pop(stack)?;
+                        let hash = match opcode {
+                            OP_RIPEMD160 => ripemd160(&data),
+                            OP_SHA1 => Sha1::digest(&data).to_vec(),
+                            OP_SHA256 => sha256(&data),
+                            OP_HASH160 => ripemd160(&sha256(&data)),
+                            _ => sha256(&sha256(&data)),
+                        };
+                        stack.push(hash);
+                    }
+                    OP_CODESEPARATOR => begin_code = pc,
+                    OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                        let sig = top(stack, 2)?.clone();
+                        let pubkey = top(stack, 1)?.clone();
+                        check_tx_sig_encoding(&sig, flags)?;
+                        check_pubkey_encoding(&pubkey, flags)?;
+                        let script_code =
+                            cleanup_script_code(script[begin_code..].to_vec(), &sig, flags);
+                        let success = !sig.is_empty()
+                            && self.checker.check_sig(&sig, &pubkey, &script_code, flags);
+                        if !sig.is_empty() {
+                            if !success && flags.contains(ScriptFlags::NULLFAIL) {
+                                bail!(SigNullFail);
+                            }
+                            self.metrics.sig_checks += 1;
+                        }
+                        stack.truncate(stack.len() - 2);
+                        if opcode == OP_CHECKSIGVERIFY {
+                            if !success {
+                                bail!(CheckSigVerify);
+                            }
+                        } else {
+                            stack.push(encode_bool(success));
+                        }
+                    }
+                    OP_CHECKDATASIG | OP_CHECKDATASIGVERIFY => {
+                        if !flags.contains(ScriptFlags::ENABLE_CHECKDATASIG) {
+                            bail!(BadOpcode);
+                        }
+                        let sig = top(stack, 3)?;
+                        let message = top(stack, 2)?;
+                        let pubkey = top(stack, 1)?;
+                        check_data_sig_encoding(sig, flags)?;
+                        check_pubkey_encoding(pubkey, flags)?;
+                        let mut success = false;
+                        if !sig.is_empty() {
+                            let mut msg_hash = [0; 32];
+                            msg_hash.copy_from_slice(&sha256(message));
+                            success = self.checker.verify_signature(sig, pubkey, &msg_hash);
+                            if !success && flags.contains(ScriptFlags::NULLFAIL) {
+                                bail!(SigNullFail);
+                            }
+                            self.metrics.sig_checks += 1;
+                        }
+                        stack.truncate(stack.len() - 3);
+                        if opcode == OP_CHECKDATASIGVERIFY {
+                            if !success {
+                                bail!(CheckDataSigVerify);
+                            }
+                        } else {
+                            stack.push(encode_bool(success));
+                        }
+                    }
+                    OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                        let success =
+                            self.check_multisig(stack, &script[begin_code..], &mut op_count)?;
+                        if opcode == OP_CHECKMULTISIGVERIFY {
+                            if !success {
+                                bail!(CheckMultisigVerify);
+                            }
+                        } else {
+                            stack.push(encode_bool(success));
+                        }
+                    }
+
+                    OP_INPUTINDEX => {
+                        let input_index = self.context()?.input_index;
+                        stack.push(encode_num(input_index as i64));
+                    }
+                    OP_ACTIVEBYTECODE => {
+                        self.context()?;
+                        let bytecode = &script[begin_code..];
+                        if bytecode.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                            bail!(PushSize);
+                        }
+                        stack.push(bytecode.to_vec());
+                    }
+                    OP_TXVERSION => {
+                        let version = self.context()?.tx.version;
+                        stack.push(encode_num(version as i64));
+                    }
+                    OP_TXINPUTCOUNT => {
+                        let num_inputs = self.context()?.tx.inputs.len();
+                        stack.push(encode_num(num_inputs as i64));
+                    }
+                    OP_TXOUTPUTCOUNT => {
+                        let num_outputs = self.context()?.tx.outputs.len();
+                        stack.push(encode_num(num_outputs as i64));
+                    }
+                    OP_TXLOCKTIME => {
+                        let lock_time = self.context()?.tx.lock_time;
+                        stack.push(encode_num(lock_time as i64));
+                    }
+                    OP_UTXOVALUE
+                    | OP_UTXOBYTECODE
+                    | OP_OUTPOINTTXHASH
+                    | OP_OUTPOINTINDEX
+                    | OP_INPUTBYTECODE
+                    | OP_INPUTSEQUENCENUMBER => {
+                        let context = self.context()?;
+                        let index = self.pop_num(stack)?;
+                        if index < 0 || index as usize >= context.tx.inputs.len() {
+                            bail!(InvalidTxInputIndex);
+                        }
+                        let input = &context.tx.inputs[index as usize];
+                        let item = match opcode {
+                            OP_UTXOVALUE => {
+                                encode_num(context.spent_outputs[index as usize].value as i64)
+                            }
+                            OP_UTXOBYTECODE => context.spent_outputs[index as usize].script.clone(),
+                            OP_OUTPOINTTXHASH => input.prev_out.tx_hash.to_vec(),
+                            OP_OUTPOINTINDEX => encode_num(input.prev_out.vout as i64),
+                            OP_INPUTBYTECODE => input.script.clone(),
+                            _ => encode_num(input.sequence as i64),
+                        };
+                        if item.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                            bail!(PushSize);
+                        }
+                        stack.push(item);
+                    }
+                    OP_OUTPUTVALUE | OP_OUTPUTBYTECODE => {
+                        let context = self.context()?;
+                        let index = self.pop_num(stack)?;
+                        if index < 0 || index as usize >= context.tx.outputs.len() {
+                            bail!(InvalidTxOutputIndex);
+                        }
+                        let output = &context.tx.outputs[index as usize];
+                        let item = match opcode {
+                            OP_OUTPUTVALUE => encode_num(output.value as i64),
+                            _ => output.script.clone(),
+                        };
+                        if item.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                            bail!(PushSize);
+                        }
+                        stack.push(item);
+                    }
+                    OP_UTXOTOKENCATEGORY
+                    | OP_UTXOTOKENCOMMITMENT
+                    | OP_UTXOTOKENAMOUNT
+                    | OP_OUTPUTTOKENCATEGORY
+                    | OP_OUTPUTTOKENCOMMITMENT
+                    | OP_OUTPUTTOKENAMOUNT => {
+                        if !flags.contains(ScriptFlags::ENABLE_TOKENS) {
+                            bail!(BadOpcode);
+                        }
+                        let context = self.context()?;
+                        let index = self.pop_num(stack)?;
+                        let token = match opcode {
+                            OP_UTXOTOKENCATEGORY | OP_UTXOTOKENCOMMITMENT | OP_UTXOTOKENAMOUNT => {
+                                if index < 0 || index as usize >= context.tx.inputs.len() {
+                                    bail!(InvalidTxInputIndex);
+                                }
+                                &context.spent_outputs[index as usize].token
+                            }
+                            _ => {
+                                if index < 0 || index as usize >= context.tx.outputs.len() {
+                                    bail!(InvalidTxOutputIndex);
+                                }
+                                &context.tx.outputs[index as usize].token
+                            }
+                        };
+                        let item = match opcode {
+                            OP_UTXOTOKENCATEGORY | OP_OUTPUTTOKENCATEGORY => token
+                                .as_ref()
+                                .map(TokenData::category_with_capability)
+                                .unwrap_or_default(),
+                            OP_UTXOTOKENCOMMITMENT | OP_OUTPUTTOKENCOMMITMENT => token
+                                .as_ref()
+                                .map(|token| token.commitment().to_vec())
+                                .unwrap_or_default(),
+                            _ => encode_num(token.as_ref().map_or(0, |token| token.amount as i64)),
+                        };
+                        stack.push(item);
+                    }
+
+                    _ => bail!(BadOpcode),
+                }
+            }
+            if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+                bail!(StackSize);
+            }
+        }
+        if !cond_stack.is_empty() {
+            bail!(UnbalancedConditional);
+        }
+        Ok(())
+    }
+
+    /// Stack: [dummy] [sig ...] num_of_signatures [pubkey ...] num_of_pubkeys
+    fn check_multisig(
+        &mut self,
+        stack: &mut Vec<Vec<u8>>,
+        script_code: &[u8],
+        op_count: &mut usize,
+    ) -> Result<bool> {
+        let flags = self.flags;
+        let idx_key_count = 1;
+        let num_keys = self.top_num(stack, idx_key_count)?;
+        if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&num_keys) {
+            bail!(PubkeyCount);
+        }
+        let num_keys = num_keys as usize;
+        *op_count += num_keys;
+        if *op_count > MAX_OPS_PER_SCRIPT {
+            bail!(OpCount);
+        }
+        let idx_top_key = idx_key_count + 1;
+        let idx_sig_count = idx_top_key + num_keys;
+        let num_sigs = self.top_num(stack, idx_sig_count)?;
+        if num_sigs < 0 || num_sigs as usize > num_keys {
+            bail!(SigCount);
+        }
+        let num_sigs = num_sigs as usize;
+        let idx_top_sig = idx_sig_count + 1;
+        let idx_dummy = idx_top_sig + num_sigs;
+        require_depth(stack, idx_dummy)?;
+
+        let mut success = true;
+        let dummy = top(stack, idx_dummy)?;
+        if flags.contains(ScriptFlags::ENABLE_SCHNORR_MULTISIG) && !dummy.is_empty() {
+            // The dummy element is a bitfield of which pubkeys to check.
+            let check_bits = decode_bitfield(dummy, num_keys)?;
+            if check_bits.count_ones() as usize != num_sigs {
+                bail!(InvalidBitCount);
+            }
+            let idx_bottom_key = idx_top_key + num_keys - 1;
+            let idx_bottom_sig = idx_top_sig + num_sigs - 1;
+            let mut key_idx = 0;
+            for sig_idx in 0..num_sigs {
+                while (check_bits >> key_idx) & 1 == 0 {
+                    key_idx += 1;
+                }
+                let sig = top(stack, idx_bottom_sig - sig_idx)?;
+                let pubkey = top(stack, idx_bottom_key - key_idx)?;
+                check_tx_schnorr_sig_encoding(sig, flags)?;
+                check_pubkey_encoding(pubkey, flags)?;
+                if !self.checker.check_sig(sig, pubkey, script_code, flags) {
+                    bail!(SigNullFail);
+                }
+                self.metrics.sig_checks += 1;
+                key_idx += 1;
+            }
+            if (check_bits >> key_idx) != 0 {
+                bail!(InvalidBitCount);
+            }
+        } else {
+            if flags.contains(ScriptFlags::NULLDUMMY) && !dummy.is_empty() {
+                bail!(SigNullDummy);
+            }
+            let mut script_code = script_code.to_vec();
+            for k in 0..num_sigs {
+                script_code = cleanup_script_code(script_code, top(stack, idx_top_sig + k)?, flags);
+            }
+            let mut sigs_remaining = num_sigs;
+            let mut keys_remaining = num_keys;
+            while success && sigs_remaining > 0 {
+                let sig = top(stack, idx_top_sig + num_sigs - sigs_remaining)?;
+                let pubkey = top(stack, idx_top_key + num_keys - keys_remaining)?;
+                check_tx_ecdsa_sig_encoding(sig, flags)?;
+                check_pubkey_encoding(pubkey, flags)?;
+                if !sig.is_empty() && self.checker.check_sig(sig, pubkey, &script_code, flags) {
+                    sigs_remaining -= 1;
+                }
+                keys_remaining -= 1;
+                if sigs_remaining > keys_remaining {
+                    success = false;
+                }
+            }
+            let all_sigs_null =
+                (0..num_sigs).all(|k| stack[stack.len() - idx_top_sig - k].is_empty());
+            if !success && flags.contains(ScriptFlags::NULLFAIL) && !all_sigs_null {
+                bail!(SigNullFail);
+            }
+            if !all_sigs_null {
+                // Upper bound of the ECDSA verifications done.
+                self.metrics.sig_checks += num_keys;
+            }
+        }
+        stack.truncate(stack.len() - idx_dummy);
+        Ok(success)
+    }
+}
+
+pub fn eval_script(
+    stack: &mut Vec<Vec<u8>>,
+    script: &[u8],
+    flags: ScriptFlags,
+    checker: &dyn SignatureChecker,
+    metrics: &mut ScriptMetrics,
+) -> Result<()> {
+    let mut interpreter = Interpreter {
+        flags,
+        checker,
+        metrics,
+        require_minimal: flags.contains(ScriptFlags::MINIMALDATA),
+        max_num_size: if flags.contains(ScriptFlags::INTEGERS_64_BIT) {
+            MAX_NUM_SIZE_64_BIT
+        } else {
+            MAX_NUM_SIZE_32_BIT
+        },
+    };
+    interpreter.eval(stack, script)
+}
+
+pub fn verify_script(
+    script_sig: &[u8],
+    script_pubkey: &[u8],
+    flags: ScriptFlags,
+    checker: &dyn SignatureChecker,
+) -> Result<ScriptMetrics> {
+    let mut metrics = ScriptMetrics::default();
+    if flags.contains(ScriptFlags::SIGPUSHONLY) && !is_push_only(script_sig) {
+        bail!(SigPushOnly);
+    }
+    let mut stack = Vec::new();
+    eval_script(&mut stack, script_sig, flags, checker, &mut metrics)?;
+    let stack_copy = if flags.contains(ScriptFlags::P2SH) {
+        stack.clone()
+    } else {
+        vec![]
+    };
+    eval_script(&mut stack, script_pubkey, flags, checker, &mut metrics)?;
+    match stack.last() {
+        Some(item) if cast_to_bool(item) => {}
+        _ => bail!(EvalFalse),
+    }
+
+    let is_p2sh_script = is_p2sh(script_pubkey)
+        || (flags.contains(ScriptFlags::ENABLE_P2SH_32) && is_p2sh32(script_pubkey));
+    if flags.contains(ScriptFlags::P2SH) && is_p2sh_script {
+        if !is_push_only(script_sig) {
+            bail!(SigPushOnly);
+        }
+        stack = stack_copy;
+        let redeem_script = pop(&mut stack)?;
+        // Coins accidentally sent to P2SH-wrapped segwit outputs can be recovered.
+        if !flags.contains(ScriptFlags::DISALLOW_SEGWIT_RECOVERY)
+            && stack.is_empty()
+            && is_witness_program(&redeem_script)
+        {
+            return Ok(metrics);
+        }
+        eval_script(&mut stack, &redeem_script, flags, checker, &mut metrics)?;
+        match stack.last() {
+            Some(item) if cast_to_bool(item) => {}
+            _ => bail!(EvalFalse),
+        }
+    }
+
+    if flags.contains(ScriptFlags::CLEANSTACK) && stack.len() != 1 {
+        bail!(CleanStack);
+    }
+
+    if flags.contains(ScriptFlags::INPUT_SIGCHECKS) {
+        let sig_checks_limit = (script_sig.len() + 60) / 43;
+        if metrics.sig_checks > sig_checks_limit {
+            bail!(InputSigChecks);
+        }
+    }
+    Ok(metrics)
+}
@@ -0,0 +1,14 @@
+mod flags;
+mod interpreter;
+mod num;
+pub mod opcodes;
+mod ops;
+mod sig_encoding;
+mod sighash;
+
+pub use flags::*;
+pub use interpreter::*;
+pub use num::*;
+pub use ops::*;
+pub use sig_encoding::*;
+pub use sighash::*;
@@ -0,0 +1,126 @@
+use crate::errors::script::{ErrorKind::*, Result};
+use error_chain::bail;
+
+pub const MAX_NUM_SIZE_32_BIT: usize = 4;
+pub const MAX_NUM_SIZE_64_BIT: usize = 8;
+
+pub fn is_minimally_encoded_num(bytes: &[u8]) -> bool {
+    match bytes.last() {
+        None => true,
+        Some(last) if last & 0x7f != 0 => true,
+        // The last byte only carries the sign, which is only allowed if the
+        // byte before it would otherwise set the sign bit.
+        Some(_) => bytes.len() > 1 && bytes[bytes.len() - 2] & 0x80 != 0,
+    }
+}
+
+pub fn decode_num(bytes: &[u8], require_minimal: bool, max_size: usize) -> Result<i64> {
+    if bytes.len() > max_size {
+        bail!(InvalidNumberRange);
+    }
+    if require_minimal && !is_minimally_encoded_num(bytes) {
+        bail!(MinimalData);
+    }
+    if bytes.is_empty() {
+        return Ok(0);
+    }
+    let mut magnitude = 0u64;
+    for (i, byte) in bytes.iter().enumerate() {
+        magnitude |= (*byte as u64) << (8 * i);
+    }
+    let sign_bit = 0x80 << (8 * (bytes.len() - 1));
+    if magnitude & sign_bit != 0 {
+        Ok(-((magnitude & !sign_bit) as i64))
+    } else {
+        Ok(magnitude as i64)
+    }
+}
+
+pub fn encode_num(num: i64) -> Vec<u8> {
+    if num == 0 {
+        return vec![];
+    }
+    let negative = num < 0;
+    let mut magnitude = num.unsigned_abs();
+    let mut bytes = Vec::with_capacity(9);
+    while magnitude > 0 {
+        bytes.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+    let last = bytes.len() - 1;
+    if bytes[last] & 0x80 != 0 {
+        bytes.push(if negative { 0x80 } else { 0x00 });
+    } else if negative {
+        bytes[last] |= 0x80;
+    }
+    bytes
+}
+
+/// Strips superfluous zero bytes, keeping the sign bit intact.
+pub fn minimally_encode_num(bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    let last = match bytes.last() {
+        Some(last) => *last,
+        None => return bytes,
+    };
+    if last & 0x7f != 0 {
+        return bytes;
+    }
+    if bytes.len() == 1 {
+        return vec![];
+    }
+    if bytes[bytes.len() - 2] & 0x80 != 0 {
+        return bytes;
+    }
+    let sign = last & 0x80;
+    for i in (1..bytes.len()).rev() {
+        if bytes[i - 1] != 0 {
+            if bytes[i - 1] & 0x80 != 0 {
+                bytes[i] = sign;
+                bytes.truncate(i + 1);
+            } else {
+                bytes[i - 1] |= sign;
+                bytes.truncate(i);
+            }
+            return bytes;
+        }
+    }
+    vec![]
+}
+
+pub fn cast_to_bool(bytes: &[u8]) -> bool {
+    for (i, byte) in bytes.iter().enumerate() {
+        if *byte != 0 {
+            // negative zero is false
+            return !(i == bytes.len() - 1 && *byte == 0x80);
+        }
+    }
+    false
+}
+
+#[test]
+fn test_num_encoding() {
+    let cases: &[(i64, &[u8])] = &[
+        (0, &[]),
+        (1, &[0x01]),
+        (-1, &[0x81]),
+        (127, &[0x7f]),
+        (128, &[0x80, 0x00]),
+        (-128, &[0x80, 0x80]),
+        (255, &[0xff, 0x00]),
+        (256, &[0x00, 0x01]),
+        (-32768, &[0x00, 0x80, 0x80]),
+        (i64::MAX, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]),
+        (-i64::MAX, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+    ];
+    for (num, bytes) in cases {
+        assert_eq!(&encode_num(*num)[..], *bytes);
+        assert_eq!(decode_num(bytes, true, MAX_NUM_SIZE_64_BIT).unwrap(), *num);
+    }
+    assert!(decode_num(&[0x00], true, 4).is_err());
+    assert_eq!(decode_num(&[0x00], false, 4).unwrap(), 0);
+    assert!(decode_num(&[1, 2, 3, 4, 5], false, 4).is_err());
+    assert_eq!(minimally_encode_num(&[0x01, 0x00, 0x00, 0x80]), vec![0x81]);
+    assert_eq!(minimally_encode_num(&[0x80, 0x00, 0x00]), vec![0x80, 0x00]);
+    assert_eq!(minimally_encode_num(&[0x00, 0x80]), Vec::<u8>::new());
+}
@@ -0,0 +1,130 @@
+use crate::errors::script::{ErrorKind::*, Result};
+use crate::script::opcodes::*;
+use byteorder::{ByteOrder, LittleEndian};
+use error_chain::bail;
+
+/// Reads the op at `pc`, returning its opcode and pushed data (empty for
+/// non-push ops), and advances `pc` past it.
+pub fn read_op<'a>(script: &'a [u8], pc: &mut usize) -> Result<(u8, &'a [u8])> {
+    let opcode = match script.get(*pc) {
+        Some(opcode) => *opcode,
+        None => bail!(BadOpcode),
+    };
+    let mut start = *pc + 1;
+    let data_len = match opcode {
+        0x01..=0x4b => opcode as usize,
+        OP_PUSHDATA1 | OP_PUSHDATA2 | OP_PUSHDATA4 => {
+            let size_len = match opcode {
+                OP_PUSHDATA1 => 1,
+                OP_PUSHDATA2 => 2,
+                _ => 4,
+            };
+            if script.len() < start + size_len {
+                bail!(BadOpcode);
+            }
+            let size_bytes = &script[start..start + size_len];
+            start += size_len;
+            match size_len {
+                1 => size_bytes[0] as usize,
+                2 => LittleEndian::read_u16(size_bytes) as usize,
+                _ => LittleEndian::read_u32(size_bytes) as usize,
+            }
+        }
+        _ => 0,
+    };
+    if script.len() - start < data_len {
+        bail!(BadOpcode);
+    }
+    *pc = start + data_len;
+    Ok((opcode, &script[start..start + data_len]))
+}
+
+/// Encodes a push of `data` using the smallest possible push opcode.
+pub fn encode_push(data: &[u8]) -> Vec<u8> {
+    let mut script = Vec::with_capacity(data.len() + 5);
+    if data.len() < OP_PUSHDATA1 as usize {
+        script.push(data.len() as u8);
+    } else if data.len() <= 0xff {
+        script.push(OP_PUSHDATA1);
+        script.push(data.len() as u8);
+    } else if data.len() <= 0xffff {
+        script.push(OP_PUSHDATA2);
+        script.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    } else {
+        script.push(OP_PUSHDATA4);
+        script.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    }
+    script.extend_from_slice(data);
+    script
+}
+
+pub fn is_minimal_push(opcode: u8, data: &[u8]) -> bool {
+    if data.is_empty() {
+        return opcode == OP_0;
+    }
+    if data.len() == 1 && data[0] >= 1 && data[0] <= 16 {
+        return opcode == OP_1 + data[0] - 1;
+    }
+    if data.len() == 1 && data[0] == 0x81 {
+        return opcode == OP_1NEGATE;
+    }
+    if data.len() <= 75 {
+        return opcode as usize == data.len();
+    }
+    if data.len() <= 0xff {
+        return opcode == OP_PUSHDATA1;
+    }
+    if data.len() <= 0xffff {
+        return opcode == OP_PUSHDATA2;
+    }
+    true
+}
+
+pub fn is_push_only(script: &[u8]) -> bool {
+    let mut pc = 0;
+    while pc < script.len() {
+        match read_op(script, &mut pc) {
+            Ok((opcode, _)) if opcode <= OP_16 => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+pub fn is_p2sh(script: &[u8]) -> bool {
+    script.len() == 23 && script[0] == OP_HASH160 && script[1] == 20 && script[22] == OP_EQUAL
+}
+
+pub fn is_p2sh32(script: &[u8]) -> bool {
+    script.len() == 35 && script[0] == OP_HASH256 && script[1] == 32 && script[34] == OP_EQUAL
+}
+
+pub fn is_witness_program(script: &[u8]) -> bool {
+    if script.len() < 4 || script.len() > 42 {
+        return false;
+    }
+    if script[0] != OP_0 && (script[0] < OP_1 || script[0] > OP_16) {
+        return false;
+    }
+    script[1] as usize + 2 == script.len()
+}
+
+/// Removes all pushes of `data` at op boundaries of `script`.
+pub fn find_and_delete(script: &[u8], data: &[u8]) -> Vec<u8> {
+    let pattern = encode_push(data);
+    let mut result = Vec::with_capacity(script.len());
+    let mut pc = 0;
+    let mut copied_until = 0;
+    loop {
+        result.extend_from_slice(&script[copied_until..pc]);
+        while script.len() - pc >= pattern.len() && script[pc..].starts_with(&pattern) {
+            pc += pattern.len();
+        }
+        copied_until = pc;
+        if pc >= script.len() || read_op(script, &mut pc).is_err() {
+            break;
+        }
+    }
+    result.extend_from_slice(&script[copied_until..]);
+    result
+}
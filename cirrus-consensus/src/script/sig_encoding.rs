@@ -0,0 +1,212 @@
+use crate::errors::script::{ErrorKind::*, Result};
+use crate::script::ScriptFlags;
+use error_chain::bail;
+use hex_literal::hex;
+
+pub const SIGHASH_ALL: u8 = 0x01;
+pub const SIGHASH_NONE: u8 = 0x02;
+pub const SIGHASH_SINGLE: u8 = 0x03;
+pub const SIGHASH_UTXOS: u8 = 0x20;
+pub const SIGHASH_FORKID: u8 = 0x40;
+pub const SIGHASH_ANYONECANPAY: u8 = 0x80;
+
+/// Signatures carry a single byte, but the digests serialize the type as 32
+/// bits, which the reference vectors make use of.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SigHashType(pub u32);
+
+impl From<u8> for SigHashType {
+    fn from(sighash_type: u8) -> Self {
+        SigHashType(sighash_type.into())
+    }
+}
+
+impl SigHashType {
+    pub fn from_sig(sig: &[u8]) -> SigHashType {
+        SigHashType::from(sig.last().cloned().unwrap_or(0))
+    }
+
+    /// The byte appended to signatures.
+    pub fn to_byte(self) -> u8 {
+        self.0 as u8
+    }
+
+    pub fn base_type(self) -> u8 {
+        self.to_byte() & !(SIGHASH_FORKID | SIGHASH_ANYONECANPAY | SIGHASH_UTXOS)
+    }
+
+    pub fn is_defined(self) -> bool {
+        let base_type = self.base_type();
+        (SIGHASH_ALL..=SIGHASH_SINGLE).contains(&base_type)
+    }
+
+    pub fn has_forkid(self) -> bool {
+        self.to_byte() & SIGHASH_FORKID != 0
+    }
+
+    pub fn has_anyonecanpay(self) -> bool {
+        self.to_byte() & SIGHASH_ANYONECANPAY != 0
+    }
+
+    pub fn has_utxos(self) -> bool {
+        self.to_byte() & SIGHASH_UTXOS != 0
+    }
+}
+
+/// n / 2 of secp256k1, the largest S value allowed with LOW_S.
+const HALF_ORDER: [u8; 32] =
+    hex!("7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a0");
+
+pub fn is_schnorr_sig(sig: &[u8]) -> bool {
+    sig.len() == 64
+}
+
+/// Format: 0x30 [total-length] 0x02 [R-length] [R] 0x02 [S-length] [S]
+pub fn is_valid_der_encoding(sig: &[u8]) -> bool {
+    if sig.len() < 8 || sig.len() > 72 {
+        return false;
+    }
+    if sig[0] != 0x30 || sig[1] as usize != sig.len() - 2 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 6 != sig.len() {
+        return false;
+    }
+    if sig[2] != 0x02 || len_r == 0 || sig[4] & 0x80 != 0 {
+        return false;
+    }
+    if len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 {
+        return false;
+    }
+    if sig[len_r + 4] != 0x02 || len_s == 0 || sig[len_r + 6] & 0x80 != 0 {
+        return false;
+    }
+    if len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0 {
+        return false;
+    }
+    true
+}
+
+/// Expects a valid DER encoding.
+pub fn is_low_der_s(sig: &[u8]) -> bool {
+    let len_r = sig[3] as usize;
+    let len_s = sig[5 + len_r] as usize;
+    let s = &sig[6 + len_r..6 + len_r + len_s];
+    let first_nonzero = s.iter().position(|b| *b != 0).unwrap_or(s.len());
+    let s = &s[first_nonzero..];
+    if s.len() != HALF_ORDER.len() {
+        return s.len() < HALF_ORDER.len();
+    }
+    s <= &HALF_ORDER[..]
+}
+
+fn check_raw_ecdsa_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<()> {
+    if is_schnorr_sig(sig) {
+        bail!(SigBadLength);
+    }
+    let der_flags = ScriptFlags::DERSIG | ScriptFlags::LOW_S | ScriptFlags::STRICTENC;
+    if flags.intersects(der_flags) && !is_valid_der_encoding(sig) {
+        bail!(SigDer);
+    }
+    if flags.contains(ScriptFlags::LOW_S) && !is_low_der_s(sig) {
+        bail!(SigHighS);
+    }
+    Ok(())
+}
+
+fn check_raw_schnorr_sig_encoding(sig: &[u8], _flags: ScriptFlags) -> Result<()> {
+    if !is_schnorr_sig(sig) {
+        bail!(SigNonSchnorr);
+    }
+    Ok(())
+}
+
+fn check_raw_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<()> {
+    if is_schnorr_sig(sig) {
+        return Ok(());
+    }
+    check_raw_ecdsa_sig_encoding(sig, flags)
+}
+
+fn check_sighash_encoding(sig: &[u8], flags: ScriptFlags) -> Result<()> {
+    if flags.contains(ScriptFlags::STRICTENC) {
+        let sighash_type = SigHashType::from_sig(sig);
+        if !sighash_type.is_defined() {
+            bail!(InvalidSigHashType);
+        }
+        let forkid_enabled = flags.contains(ScriptFlags::ENABLE_SIGHASH_FORKID);
+        if !forkid_enabled && sighash_type.has_forkid() {
+            bail!(IllegalForkId);
+        }
+        if forkid_enabled && !sighash_type.has_forkid() {
+            bail!(MustUseForkId);
+        }
+        if sighash_type.has_utxos() {
+            // SIGHASH_UTXOS commits to all inputs, so ANYONECANPAY is contradictory.
+            if !flags.contains(ScriptFlags::ENABLE_TOKENS) || sighash_type.has_anyonecanpay() {
+                bail!(InvalidSigHashType);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_tx_sig_encoding_with(
+    sig: &[u8],
+    flags: ScriptFlags,
+    check_raw: fn(&[u8], ScriptFlags) -> Result<()>,
+) -> Result<()> {
+    if sig.is_empty() {
+        return Ok(());
+    }
+    check_raw(&sig[..sig.len() - 1], flags)?;
+    check_sighash_encoding(sig, flags)
+}
+
+/// Checks a signature with appended sighash type, either ECDSA or Schnorr.
+pub fn check_tx_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<()> {
+    check_tx_sig_encoding_with(sig, flags, check_raw_sig_encoding)
+}
+
+pub fn check_tx_ecdsa_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<()> {
+    check_tx_sig_encoding_with(sig, flags, check_raw_ecdsa_sig_encoding)
+}
+
+pub fn check_tx_schnorr_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<()> {
+    check_tx_sig_encoding_with(sig, flags, check_raw_schnorr_sig_encoding)
+}
+
+/// Checks a signature without sighash type, as used by OP_CHECKDATASIG.
+pub fn check_data_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<()> {
+    if sig.is_empty() {
+        return Ok(());
+    }
+    check_raw_sig_encoding(sig, flags)
+}
+
+pub fn is_compressed_or_uncompressed_pubkey(pubkey: &[u8]) -> bool {
+    match pubkey.first() {
+        Some(0x02) | Some(0x03) => pubkey.len() == 33,
+        Some(0x04) => pubkey.len() == 65,
+        _ => false,
+    }
+}
+
+pub fn is_compressed_pubkey(pubkey: &[u8]) -> bool {
+    pubkey.len() == 33 && (pubkey[0] == 0x02 || pubkey[0] == 0x03)
+}
+
+pub fn check_pubkey_encoding(pubkey: &[u8], flags: ScriptFlags) -> Result<()> {
+    if flags.contains(ScriptFlags::STRICTENC) && !is_compressed_or_uncompressed_pubkey(pubkey) {
+        bail!(PubkeyType);
+    }
+    if flags.contains(ScriptFlags::COMPRESSED_PUBKEYTYPE) && !is_compressed_pubkey(pubkey) {
+        bail!(NonCompressedPubkey);
+    }
+    Ok(())
+}
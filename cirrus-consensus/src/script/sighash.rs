@@ -0,0 +1,297 @@
+use crate::errors::script::{ErrorKind::*, Result};
+use crate::script::opcodes::OP_CODESEPARATOR;
+use crate::script::ops::read_op;
+use crate::script::sig_encoding::*;
+use crate::script::ScriptFlags;
+use crate::token::TokenData;
+use crate::tx::{Transaction, TxInput, TxOutput};
+use byteorder::{LittleEndian, WriteBytesExt};
+use cashcontracts::double_sha256;
+use cashcontracts::serialize::write_var_str;
+use error_chain::bail;
+
+/// The hashes of a tx shared by the signatures of all of its inputs.
+/// Computing them once keeps signing/verifying large txs linear.
+#[derive(Clone, Debug)]
+pub struct SigHashCache {
+    hash_prevouts: [u8; 32],
+    hash_sequence: [u8; 32],
+    hash_outputs: [u8; 32],
+    hash_utxos: Option<[u8; 32]>,
+}
+
+impl SigHashCache {
+    pub fn new(tx: &Transaction) -> Self {
+        let mut prevouts = Vec::with_capacity(tx.inputs.len() * 36);
+        let mut sequences = Vec::with_capacity(tx.inputs.len() * 4);
+        for input in tx.inputs.iter() {
+            prevouts.extend_from_slice(&input.prev_out.tx_hash);
+            prevouts
+                .write_u32::<LittleEndian>(input.prev_out.vout)
+                .unwrap();
+            sequences.write_u32::<LittleEndian>(input.sequence).unwrap();
+        }
+        SigHashCache {
+            hash_prevouts: double_sha256(&prevouts),
+            hash_sequence: double_sha256(&sequences),
+            hash_outputs: hash_outputs(&tx.outputs),
+            hash_utxos: None,
+        }
+    }
+
+    /// Also caches the hash of the spent outputs, required for SIGHASH_UTXOS.
+    pub fn with_spent_outputs(tx: &Transaction, spent_outputs: &[TxOutput]) -> Self {
+        SigHashCache {
+            hash_utxos: Some(hash_outputs(spent_outputs)),
+            ..SigHashCache::new(tx)
+        }
+    }
+}
+
+fn hash_outputs(outputs: &[TxOutput]) -> [u8; 32] {
+    let mut serialized = Vec::new();
+    for output in outputs {
+        output.write_to_stream(&mut serialized).unwrap();
+    }
+    double_sha256(&serialized)
+}
+
+/// Computes the signature hash of the given input, using the BIP143 digest
+/// if `sighash_type` has SIGHASH_FORKID and the legacy digest otherwise.
+///
+/// `amount` is the value of the spent output. SIGHASH_UTXOS requires the
+/// spent outputs, use `sighash_cached` with `SigHashCache::with_spent_outputs`.
+pub fn sighash(
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    amount: u64,
+    sighash_type: SigHashType,
+) -> Result<[u8; 32]> {
+    let flags = ScriptFlags::ENABLE_SIGHASH_FORKID;
+    let cache = SigHashCache::new(tx);
+    sighash_cached(
+        &cache,
+        tx,
+        input_index,
+        script_code,
+        amount,
+        sighash_type,
+        flags,
+    )
+}
+
+/// Like `sighash`, but with precomputed hashes. The BIP143 digest is only
+/// used if `flags` contains ENABLE_SIGHASH_FORKID.
+pub fn sighash_cached(
+    cache: &SigHashCache,
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    amount: u64,
+    sighash_type: SigHashType,
+    flags: ScriptFlags,
+) -> Result<[u8; 32]> {
+    sighash_with_token(
+        cache,
+        tx,
+        input_index,
+        script_code,
+        amount,
+        None,
+        sighash_type,
+        flags,
+    )
+}
+
+/// Like `sighash_cached`, for an input spending `spent_output`. With
+/// ENABLE_TOKENS, the digest also commits to the tokens of `spent_output`.
+pub fn sighash_spending(
+    cache: &SigHashCache,
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    spent_output: &TxOutput,
+    sighash_type: SigHashType,
+    flags: ScriptFlags,
+) -> Result<[u8; 32]> {
+    let token = match &spent_output.token {
+        Some(token) if flags.contains(ScriptFlags::ENABLE_TOKENS) => Some(token),
+        _ => None,
+    };
+    sighash_with_token(
+        cache,
+        tx,
+        input_index,
+        script_code,
+        spent_output.value,
+        token,
+        sighash_type,
+        flags,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sighash_with_token(
+    cache: &SigHashCache,
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    amount: u64,
+    token: Option<&TokenData>,
+    sighash_type: SigHashType,
+    flags: ScriptFlags,
+) -> Result<[u8; 32]> {
+    if input_index >= tx.inputs.len() {
+        bail!(InvalidTxInputIndex);
+    }
+    if sighash_type.has_forkid() && flags.contains(ScriptFlags::ENABLE_SIGHASH_FORKID) {
+        forkid_sighash(
+            cache,
+            tx,
+            input_index,
+            script_code,
+            amount,
+            token,
+            sighash_type,
+        )
+    } else {
+        Ok(legacy_sighash(tx, input_index, script_code, sighash_type))
+    }
+}
+
+fn forkid_sighash(
+    cache: &SigHashCache,
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    amount: u64,
+    token: Option<&TokenData>,
+    sighash_type: SigHashType,
+) -> Result<[u8; 32]> {
+    let base_type = sighash_type.base_type();
+    let commits_to_all_outputs = base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE;
+    let hash_prevouts = if sighash_type.has_anyonecanpay() {
+        [0; 32]
+    } else {
+        cache.hash_prevouts
+    };
+    let hash_sequence = if !sighash_type.has_anyonecanpay() && commits_to_all_outputs {
+        cache.hash_sequence
+    } else {
+        [0; 32]
+    };
+    let hash_outputs = if commits_to_all_outputs {
+        cache.hash_outputs
+    } else if base_type == SIGHASH_SINGLE && input_index < tx.outputs.len() {
+        hash_outputs(&tx.outputs[input_index..=input_index])
+    } else {
+        [0; 32]
+    };
+    let input = &tx.inputs[input_index];
+
+    let mut preimage = Vec::with_capacity(script_code.len() + 200);
+    preimage.write_i32::<LittleEndian>(tx.version).unwrap();
+    preimage.extend_from_slice(&hash_prevouts);
+    if sighash_type.has_utxos() {
+        match cache.hash_utxos {
+            Some(hash_utxos) => preimage.extend_from_slice(&hash_utxos),
+            None => bail!(ContextNotPresent),
+        }
+    }
+    preimage.extend_from_slice(&hash_sequence);
+    preimage.extend_from_slice(&input.prev_out.tx_hash);
+    preimage
+        .write_u32::<LittleEndian>(input.prev_out.vout)
+        .unwrap();
+    if let Some(token) = token {
+        token.write_to_stream(&mut preimage).unwrap();
+    }
+    write_var_str(&mut preimage, script_code).unwrap();
+    preimage.write_u64::<LittleEndian>(amount).unwrap();
+    preimage.write_u32::<LittleEndian>(input.sequence).unwrap();
+    preimage.extend_from_slice(&hash_outputs);
+    preimage.write_u32::<LittleEndian>(tx.lock_time).unwrap();
+    preimage.write_u32::<LittleEndian>(sighash_type.0).unwrap();
+    Ok(double_sha256(&preimage))
+}
+
+fn without_code_separators(script: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(script.len());
+    let mut pc = 0;
+    while pc < script.len() {
+        let start = pc;
+        match read_op(script, &mut pc) {
+            Ok((OP_CODESEPARATOR, _)) => {}
+            Ok(_) => result.extend_from_slice(&script[start..pc]),
+            Err(_) => {
+                result.extend_from_slice(&script[start..]);
+                break;
+            }
+        }
+    }
+    result
+}
+
+/// The original Satoshi digest, which serializes a modified copy of the tx.
+fn legacy_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    sighash_type: SigHashType,
+) -> [u8; 32] {
+    let base_type = sighash_type.to_byte() & 0x1f;
+    if base_type == SIGHASH_SINGLE && input_index >= tx.outputs.len() {
+        // Bug in the original implementation: the hash "1" gets signed.
+        let mut one = [0; 32];
+        one[0] = 1;
+        return one;
+    }
+    let script_code = without_code_separators(script_code);
+    let inputs = tx
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !sighash_type.has_anyonecanpay() || *i == input_index)
+        .map(|(i, input)| {
+            let is_signed_input = i == input_index;
+            let ignores_sequence = base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE;
+            TxInput {
+                prev_out: input.prev_out.clone(),
+                script: if is_signed_input {
+                    script_code.clone()
+                } else {
+                    vec![]
+                },
+                sequence: if !is_signed_input && ignores_sequence {
+                    0
+                } else {
+                    input.sequence
+                },
+            }
+        })
+        .collect();
+    let outputs = match base_type {
+        SIGHASH_NONE => vec![],
+        SIGHASH_SINGLE => {
+            let blank_output = TxOutput {
+                value: 0xffff_ffff_ffff_ffff,
+                script: vec![],
+                token: None,
+            };
+            let mut outputs = vec![blank_output; input_index];
+            outputs.push(tx.outputs[input_index].clone());
+            outputs
+        }
+        _ => tx.outputs.clone(),
+    };
+    let tx_copy = Transaction {
+        version: tx.version,
+        inputs,
+        outputs,
+        lock_time: tx.lock_time,
+    };
+    let mut preimage = tx_copy.serialize();
+    preimage.write_u32::<LittleEndian>(sighash_type.0).unwrap();
+    double_sha256(&preimage)
+}
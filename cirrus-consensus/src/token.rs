@@ -0,0 +1,293 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use cashcontracts::serialize::{read_var_int, write_var_int};
+use std::{
+    io,
+    io::{Read, Write},
+};
+
+/// First byte of the script field of outputs carrying CashTokens.
+pub const TOKEN_PREFIX: u8 = 0xef;
+pub const MAX_COMMITMENT_LENGTH: usize = 40;
+pub const MAX_TOKEN_AMOUNT: u64 = i64::MAX as u64;
+/// Prefix byte, category, bitfield, the longest commitment with its length
+/// and the amount as a 9 byte var int.
+pub const MAX_TOKEN_PREFIX_SIZE: usize = 1 + 32 + 1 + 1 + MAX_COMMITMENT_LENGTH + 9;
+
+const RESERVED_BIT: u8 = 0x80;
+const HAS_COMMITMENT_LENGTH: u8 = 0x40;
+const HAS_NFT: u8 = 0x20;
+const HAS_AMOUNT: u8 = 0x10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NftCapability {
+    None,
+    Mutable,
+    Minting,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Nft {
+    pub capability: NftCapability,
+    pub commitment: Vec<u8>,
+}
+
+/// Tokens of an output, as of CHIP-2022-02. The category is the hash of
+/// the tx whose input 0 created it, in tx hash byte order.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TokenData {
+    pub category: [u8; 32],
+    pub nft: Option<Nft>,
+    /// Fungible amount, 0 if the output holds only an NFT.
+    pub amount: u64,
+}
+
+fn invalid_prefix(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+fn var_int_size(num: u64) -> usize {
+    match num {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Reads a var int and fails unless it's minimally encoded.
+fn read_minimal_var_int(stream: &mut impl Read) -> io::Result<u64> {
+    let mut first = [0];
+    stream.read_exact(&mut first)?;
+    let num = read_var_int(&mut first.chain(stream))?;
+    let size = match first[0] {
+        0xfd => 3,
+        0xfe => 5,
+        0xff => 9,
+        _ => 1,
+    };
+    if var_int_size(num) != size {
+        return Err(invalid_prefix("non-minimal var int"));
+    }
+    Ok(num)
+}
+
+impl NftCapability {
+    pub fn from_u8(capability: u8) -> Option<Self> {
+        match capability {
+            0x00 => Some(NftCapability::None),
+            0x01 => Some(NftCapability::Mutable),
+            0x02 => Some(NftCapability::Minting),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            NftCapability::None => 0x00,
+            NftCapability::Mutable => 0x01,
+            NftCapability::Minting => 0x02,
+        }
+    }
+}
+
+impl TokenData {
+    /// Reads the token prefix, including the leading `TOKEN_PREFIX`.
+    /// Fails on any encoding the CHIP doesn't allow.
+    pub fn from_stream(stream: &mut impl Read) -> io::Result<TokenData> {
+        if stream.read_u8()? != TOKEN_PREFIX {
+            return Err(invalid_prefix("missing token prefix"));
+        }
+        let mut category = [0; 32];
+        stream.read_exact(&mut category)?;
+        let bitfield = stream.read_u8()?;
+        let has_commitment = bitfield & HAS_COMMITMENT_LENGTH != 0;
+        let has_nft = bitfield & HAS_NFT != 0;
+        let has_amount = bitfield & HAS_AMOUNT != 0;
+        if bitfield & RESERVED_BIT != 0 {
+            return Err(invalid_prefix("reserved token bit set"));
+        }
+        if !has_nft && !has_amount {
+            return Err(invalid_prefix("token prefix without tokens"));
+        }
+        if !has_nft && (has_commitment || bitfield & 0x0f != 0) {
+            return Err(invalid_prefix("NFT fields without NFT"));
+        }
+        let capability = match NftCapability::from_u8(bitfield & 0x0f) {
+            Some(capability) => capability,
+            None => return Err(invalid_prefix("invalid NFT capability")),
+        };
+        let nft = if has_nft {
+            let mut commitment = Vec::new();
+            if has_commitment {
+                let commitment_len = read_minimal_var_int(stream)? as usize;
+                if commitment_len == 0 || commitment_len > MAX_COMMITMENT_LENGTH {
+                    return Err(invalid_prefix("invalid commitment length"));
+                }
+                commitment = vec![0; commitment_len];
+                stream.read_exact(&mut commitment)?;
+            }
+            Some(Nft {
+                capability,
+                commitment,
+            })
+        } else {
+            None
+        };
+        let amount = if has_amount {
+            let amount = read_minimal_var_int(stream)?;
+            if amount == 0 || amount > MAX_TOKEN_AMOUNT {
+                return Err(invalid_prefix("invalid token amount"));
+            }
+            amount
+        } else {
+            0
+        };
+        Ok(TokenData {
+            category,
+            nft,
+            amount,
+        })
+    }
+
+    pub fn write_to_stream(&self, stream: &mut impl Write) -> io::Result<()> {
+        let mut bitfield = 0;
+        if let Some(nft) = &self.nft {
+            bitfield |= HAS_NFT | nft.capability.to_u8();
+            if !nft.commitment.is_empty() {
+                bitfield |= HAS_COMMITMENT_LENGTH;
+            }
+        }
+        if self.amount > 0 {
+            bitfield |= HAS_AMOUNT;
+        }
+        stream.write_u8(TOKEN_PREFIX)?;
+        stream.write_all(&self.category)?;
+        stream.write_u8(bitfield)?;
+        if let Some(nft) = &self.nft {
+            if !nft.commitment.is_empty() {
+                write_var_int(stream, nft.commitment.len() as u64)?;
+                stream.write_all(&nft.commitment)?;
+            }
+        }
+        if self.amount > 0 {
+            write_var_int(stream, self.amount)?;
+        }
+        Ok(())
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut ser = Vec::new();
+        self.write_to_stream(&mut ser).unwrap();
+        ser
+    }
+
+    /// Category followed by the capability if the NFT is mutable or
+    /// minting, as pushed by OP_UTXOTOKENCATEGORY.
+    pub fn category_with_capability(&self) -> Vec<u8> {
+        let mut category = self.category.to_vec();
+        match &self.nft {
+            Some(nft) if nft.capability != NftCapability::None => {
+                category.push(nft.capability.to_u8())
+            }
+            _ => {}
+        }
+        category
+    }
+
+    pub fn commitment(&self) -> &[u8] {
+        match &self.nft {
+            Some(nft) => &nft.commitment,
+            None => &[],
+        }
+    }
+}
+
+/// Splits the script field of an output into its token data and locking
+/// script. A malformed token prefix is left in the script: such outputs
+/// are only invalid once tokens are enabled.
+pub fn split_token_prefix(script_field: &[u8]) -> (Option<TokenData>, &[u8]) {
+    if script_field.first() != Some(&TOKEN_PREFIX) {
+        return (None, script_field);
+    }
+    let mut rest = script_field;
+    match TokenData::from_stream(&mut rest) {
+        Ok(token) => (Some(token), rest),
+        Err(_) => (None, script_field),
+    }
+}
+
+/// Inverse of `split_token_prefix`.
+pub fn join_token_prefix(token: Option<&TokenData>, script: &[u8]) -> Vec<u8> {
+    let mut script_field = match token {
+        Some(token) => token.serialize(),
+        None => Vec::with_capacity(script.len()),
+    };
+    script_field.extend_from_slice(script);
+    script_field
+}
+
+#[test]
+fn test_token_prefix() {
+    let category = [0xaa; 32];
+    let prefix = |bitfield: &str, rest: &str| {
+        hex::decode(format!("ef{}{}{}", hex::encode(category), bitfield, rest)).unwrap()
+    };
+    let script = [0x51];
+    let valid = [
+        (prefix("10", "01"), None, 1),
+        (prefix("10", "fdfd00"), None, 0xfd),
+        (prefix("10", "ffffffffffffffff7f"), None, MAX_TOKEN_AMOUNT),
+        (prefix("20", ""), Some((NftCapability::None, vec![])), 0),
+        (prefix("21", ""), Some((NftCapability::Mutable, vec![])), 0),
+        (
+            prefix("62", "01cc"),
+            Some((NftCapability::Minting, vec![0xcc])),
+            0,
+        ),
+        (
+            prefix("70", "02cccc05"),
+            Some((NftCapability::None, vec![0xcc, 0xcc])),
+            5,
+        ),
+    ];
+    for (bytes, nft, amount) in valid.iter() {
+        let mut script_field = bytes.clone();
+        script_field.extend_from_slice(&script);
+        let (token, rest) = split_token_prefix(&script_field);
+        let token = token.unwrap();
+        assert_eq!(token.category, category);
+        assert_eq!(
+            token.nft,
+            nft.clone().map(|(capability, commitment)| Nft {
+                capability,
+                commitment
+            })
+        );
+        assert_eq!(token.amount, *amount);
+        assert_eq!(rest, &script);
+        assert_eq!(join_token_prefix(Some(&token), rest), script_field);
+    }
+
+    let invalid = [
+        prefix("00", ""),
+        prefix("10", "00"),
+        prefix("10", "fd0100"),
+        prefix("10", "ffffffffffffffff80"),
+        prefix("11", "01"),
+        prefix("23", ""),
+        prefix("40", "01cc"),
+        prefix("60", "00"),
+        prefix("60", "29"),
+        prefix("90", "01"),
+        prefix("60", "02cc"),
+        hex::decode("ef").unwrap(),
+    ];
+    for bytes in invalid.iter() {
+        let (token, rest) = split_token_prefix(bytes);
+        assert_eq!(token, None, "{}", hex::encode(bytes));
+        assert_eq!(rest, &bytes[..]);
+    }
+    let (token, rest) = split_token_prefix(&script);
+    assert_eq!(token, None);
+    assert_eq!(rest, &script);
+}
@@ -0,0 +1,176 @@
+use crate::token::{join_token_prefix, split_token_prefix, TokenData, TOKEN_PREFIX};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use cashcontracts::serialize::{read_var_int, read_var_str, write_var_int, write_var_str};
+use cashcontracts::{double_sha256, tx_hash_to_hex, TxOutpoint};
+use std::{
+    io,
+    io::{Read, Write},
+};
+
+#[derive(Clone, Debug)]
+pub struct TxInput {
+    pub prev_out: TxOutpoint,
+    pub script: Vec<u8>,
+    pub sequence: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct TxOutput {
+    pub value: u64,
+    /// Locking script, without the token prefix.
+    pub script: Vec<u8>,
+    pub token: Option<TokenData>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Transaction {
+    pub version: i32,
+    pub inputs: Vec<TxInput>,
+    pub outputs: Vec<TxOutput>,
+    pub lock_time: u32,
+}
+
+impl TxInput {
+    pub fn from_stream(stream: &mut impl Read) -> io::Result<TxInput> {
+        let mut tx_hash = [0; 32];
+        stream.read_exact(&mut tx_hash)?;
+        let vout = stream.read_u32::<LittleEndian>()?;
+        let script = read_var_str(stream)?;
+        let sequence = stream.read_u32::<LittleEndian>()?;
+        Ok(TxInput {
+            prev_out: TxOutpoint { tx_hash, vout },
+            script,
+            sequence,
+        })
+    }
+
+    pub fn write_to_stream(&self, stream: &mut impl Write) -> io::Result<()> {
+        stream.write_all(&self.prev_out.tx_hash)?;
+        stream.write_u32::<LittleEndian>(self.prev_out.vout)?;
+        write_var_str(stream, &self.script)?;
+        stream.write_u32::<LittleEndian>(self.sequence)?;
+        Ok(())
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.prev_out.tx_hash == [0; 32] && self.prev_out.vout == 0xffff_ffff
+    }
+}
+
+impl TxOutput {
+    pub fn from_stream(stream: &mut impl Read) -> io::Result<TxOutput> {
+        let value = stream.read_u64::<LittleEndian>()?;
+        let script_field = read_var_str(stream)?;
+        let (token, script) = split_token_prefix(&script_field);
+        Ok(TxOutput {
+            value,
+            script: script.to_vec(),
+            token,
+        })
+    }
+
+    pub fn write_to_stream(&self, stream: &mut impl Write) -> io::Result<()> {
+        stream.write_u64::<LittleEndian>(self.value)?;
+        match &self.token {
+            Some(token) => write_var_str(stream, &join_token_prefix(Some(token), &self.script))?,
+            None => write_var_str(stream, &self.script)?,
+        }
+        Ok(())
+    }
+
+    /// Whether the script starts with a malformed token prefix, which makes
+    /// the output invalid once tokens are enabled.
+    pub fn has_invalid_token_prefix(&self) -> bool {
+        self.token.is_none() && self.script.first() == Some(&TOKEN_PREFIX)
+    }
+}
+
+impl Transaction {
+    pub fn from_stream(stream: &mut impl Read) -> io::Result<Transaction> {
+        let version = stream.read_i32::<LittleEndian>()?;
+        let num_inputs = read_var_int(stream)?;
+        let mut inputs = Vec::new();
+        for _ in 0..num_inputs {
+            inputs.push(TxInput::from_stream(stream)?);
+        }
+        let num_outputs = read_var_int(stream)?;
+        let mut outputs = Vec::new();
+        for _ in 0..num_outputs {
+            outputs.push(TxOutput::from_stream(stream)?);
+        }
+        let lock_time = stream.read_u32::<LittleEndian>()?;
+        Ok(Transaction {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+        })
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> io::Result<Transaction> {
+        Self::from_stream(&mut io::Cursor::new(bytes))
+    }
+
+    pub fn write_to_stream(&self, stream: &mut impl Write) -> io::Result<()> {
+        stream.write_i32::<LittleEndian>(self.version)?;
+        write_var_int(stream, self.inputs.len() as u64)?;
+        for input in self.inputs.iter() {
+            input.write_to_stream(stream)?;
+        }
+        write_var_int(stream, self.outputs.len() as u64)?;
+        for output in self.outputs.iter() {
+            output.write_to_stream(stream)?;
+        }
+        stream.write_u32::<LittleEndian>(self.lock_time)?;
+        Ok(())
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut ser = Vec::new();
+        self.write_to_stream(&mut ser).unwrap();
+        ser
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        double_sha256(&self.serialize())
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.len() == 1 && self.inputs[0].is_coinbase()
+    }
+}
+
+impl std::fmt::Display for Transaction {
+    fn fmt<'a>(&self, f: &mut std::fmt::Formatter<'a>) -> Result<(), std::fmt::Error> {
+        writeln!(f, "Transaction: {}", tx_hash_to_hex(&self.hash()))?;
+        writeln!(f, " version:   {}", self.version)?;
+        for input in self.inputs.iter() {
+            writeln!(
+                f,
+                " input:     {}:{} {}",
+                tx_hash_to_hex(&input.prev_out.tx_hash),
+                input.prev_out.vout,
+                hex::encode(&input.script),
+            )?;
+        }
+        for output in self.outputs.iter() {
+            writeln!(
+                f,
+                " output:    {} {}",
+                output.value,
+                hex::encode(&output.script),
+            )?;
+            if let Some(token) = &output.token {
+                writeln!(
+                    f,
+                    " token:     {} {} {}",
+                    tx_hash_to_hex(&token.category),
+                    token.amount,
+                    hex::encode(token.commitment()),
+                )?;
+            }
+        }
+        writeln!(f, " lock_time: {}", self.lock_time)?;
+        Ok(())
+    }
+}
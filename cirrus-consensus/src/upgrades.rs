@@ -0,0 +1,52 @@
+/// Network upgrades activated by time: they apply to blocks whose parent has
+/// a median time past at or after the activation time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Upgrade {
+    Uahf,
+    Daa,
+    Monolith,
+    MagneticAnomaly,
+    GreatWall,
+    Graviton,
+    Phonon,
+    Axion,
+    Upgrade8,
+    Upgrade9,
+}
+
+pub const UPGRADES: [Upgrade; 10] = [
+    Upgrade::Uahf,
+    Upgrade::Daa,
+    Upgrade::Monolith,
+    Upgrade::MagneticAnomaly,
+    Upgrade::GreatWall,
+    Upgrade::Graviton,
+    Upgrade::Phonon,
+    Upgrade::Axion,
+    Upgrade::Upgrade8,
+    Upgrade::Upgrade9,
+];
+
+impl Upgrade {
+    /// Activation time in unix seconds, the same on all networks.
+    pub fn activation_time(self) -> u32 {
+        match self {
+            Upgrade::Uahf => 1_501_590_000,
+            Upgrade::Daa => 1_510_600_000,
+            Upgrade::Monolith => 1_526_400_000,
+            Upgrade::MagneticAnomaly => 1_542_300_000,
+            Upgrade::GreatWall => 1_557_921_600,
+            Upgrade::Graviton => 1_573_819_200,
+            Upgrade::Phonon => 1_589_544_000,
+            Upgrade::Axion => 1_605_441_600,
+            Upgrade::Upgrade8 => 1_652_616_000,
+            Upgrade::Upgrade9 => 1_684_152_000,
+        }
+    }
+
+    /// Whether the upgrade applies to a block whose parent has the median
+    /// time past `prev_median_time_past`.
+    pub fn is_active(self, prev_median_time_past: u32) -> bool {
+        prev_median_time_past >= self.activation_time()
+    }
+}
@@ -0,0 +1,373 @@
+use cashcontracts::TxOutpoint;
+use cirrus_consensus::errors::script::ErrorKind;
+use cirrus_consensus::script::opcodes::{opcode_by_name, OP_0, OP_1, OP_1NEGATE, OP_CHECKSIG};
+use cirrus_consensus::script::{
+    encode_num, encode_push, verify_script, ScriptExecutionContext, ScriptFlags, SIGHASH_ALL,
+    SIGHASH_FORKID,
+};
+use cirrus_consensus::{Transaction, TxInput, TxOutput};
+
+fn parse_asm(asm: &str) -> Vec<u8> {
+    let mut script = Vec::new();
+    for token in asm.split_whitespace() {
+        let is_num = token
+            .trim_start_matches('-')
+            .chars()
+            .all(|c| c.is_ascii_digit());
+        if is_num && !token.is_empty() && token != "-" {
+            let num: i64 = token.parse().unwrap();
+            match num {
+                0 => script.push(OP_0),
+                -1 => script.push(OP_1NEGATE),
+                1..=16 => script.push(OP_1 + num as u8 - 1),
+                _ => script.extend(encode_push(&encode_num(num))),
+            }
+        } else if let Some(hex_str) = token.strip_prefix("0x") {
+            script.extend(hex::decode(hex_str).expect("invalid hex"));
+        } else if token.len() >= 2 && token.starts_with('\'') && token.ends_with('\'') {
+            script.extend(encode_push(&token.as_bytes()[1..token.len() - 1]));
+        } else {
+            script.push(opcode_by_name(token).unwrap_or_else(|| panic!("bad opcode {}", token)));
+        }
+    }
+    script
+}
+
+fn error_name(kind: &ErrorKind) -> &'static str {
+    use ErrorKind::*;
+    match kind {
+        EvalFalse => "EVAL_FALSE",
+        OpReturn => "OP_RETURN",
+        ScriptSize => "SCRIPT_SIZE",
+        PushSize => "PUSH_SIZE",
+        OpCount => "OP_COUNT",
+        StackSize => "STACK_SIZE",
+        SigCount => "SIG_COUNT",
+        PubkeyCount => "PUBKEY_COUNT",
+        InvalidOperandSize => "OPERAND_SIZE",
+        InvalidNumberRange => "INVALID_NUMBER_RANGE",
+        IntegerOverflow => "INTEGER_OVERFLOW",
+        ImpossibleEncoding => "IMPOSSIBLE_ENCODING",
+        InvalidSplitRange => "SPLIT_RANGE",
+        Verify => "VERIFY",
+        EqualVerify => "EQUALVERIFY",
+        CheckMultisigVerify => "CHECKMULTISIGVERIFY",
+        CheckSigVerify => "CHECKSIGVERIFY",
+        CheckDataSigVerify => "CHECKDATASIGVERIFY",
+        NumEqualVerify => "NUMEQUALVERIFY",
+        BadOpcode => "BAD_OPCODE",
+        DisabledOpcode => "DISABLED_OPCODE",
+        InvalidStackOperation => "INVALID_STACK_OPERATION",
+        InvalidAltstackOperation => "INVALID_ALTSTACK_OPERATION",
+        UnbalancedConditional => "UNBALANCED_CONDITIONAL",
+        NegativeLockTime => "NEGATIVE_LOCKTIME",
+        UnsatisfiedLockTime => "UNSATISFIED_LOCKTIME",
+        InvalidSigHashType => "SIG_HASHTYPE",
+        SigDer => "SIG_DER",
+        MinimalData => "MINIMALDATA",
+        SigPushOnly => "SIG_PUSHONLY",
+        SigHighS => "SIG_HIGH_S",
+        SigNullDummy => "SIG_NULLDUMMY",
+        PubkeyType => "PUBKEYTYPE",
+        CleanStack => "CLEANSTACK",
+        MinimalIf => "MINIMALIF",
+        SigNullFail => "NULLFAIL",
+        DiscourageUpgradableNops => "DISCOURAGE_UPGRADABLE_NOPS",
+        NonCompressedPubkey => "NONCOMPRESSED_PUBKEY",
+        IllegalForkId => "ILLEGAL_FORKID",
+        MustUseForkId => "MUST_USE_FORKID",
+        DivByZero => "DIV_BY_ZERO",
+        ModByZero => "MOD_BY_ZERO",
+        SigBadLength => "SIG_BADLENGTH",
+        SigNonSchnorr => "SIG_NONSCHNORR",
+        InvalidBitfieldSize => "BITFIELD_SIZE",
+        InvalidBitRange => "BIT_RANGE",
+        InvalidBitCount => "INVALID_BIT_COUNT",
+        InputSigChecks => "INPUT_SIGCHECKS",
+        ContextNotPresent => "CONTEXT_NOT_PRESENT",
+        InvalidTxInputIndex => "INVALID_TX_INPUT_INDEX",
+        InvalidTxOutputIndex => "INVALID_TX_OUTPUT_INDEX",
+        _ => "UNKNOWN_ERROR",
+    }
+}
+
+/// Builds the crediting and spending txs the same way the reference tests do.
+fn spend_tx(script_sig: Vec<u8>, script_pubkey: &[u8], value: u64) -> (Transaction, TxOutput) {
+    let credit_output = TxOutput {
+        value,
+        script: script_pubkey.to_vec(),
+        token: None,
+    };
+    let credit = Transaction {
+        version: 1,
+        inputs: vec![TxInput {
+            prev_out: TxOutpoint {
+                tx_hash: [0; 32],
+                vout: 0xffff_ffff,
+            },
+            script: vec![OP_0, OP_0],
+            sequence: 0xffff_ffff,
+        }],
+        outputs: vec![credit_output.clone()],
+        lock_time: 0,
+    };
+    let spend = Transaction {
+        version: 1,
+        inputs: vec![TxInput {
+            prev_out: TxOutpoint {
+                tx_hash: credit.hash(),
+                vout: 0,
+            },
+            script: script_sig,
+            sequence: 0xffff_ffff,
+        }],
+        outputs: vec![TxOutput {
+            value,
+            script: vec![],
+            token: None,
+        }],
+        lock_time: 0,
+    };
+    (spend, credit_output)
+}
+
+/// Runs vectors in the format of Bitcoin Cash Node's script_tests.json and
+/// returns the number of vectors run. Rows may start with `[amount]`, the
+/// value in BCH of the spent output, which signatures commit to.
+fn run_script_vectors(json: &str) -> usize {
+    let vectors: Vec<Vec<serde_json::Value>> = serde_json::from_str(json).unwrap();
+    let mut num_tested = 0;
+    for mut vector in vectors {
+        let mut value = 0;
+        if let Some(amount) = vector.first().and_then(|field| field.as_array()) {
+            let bch = match &amount[0] {
+                serde_json::Value::String(bch) => bch.parse::<f64>().unwrap(),
+                bch => bch.as_f64().unwrap(),
+            };
+            value = (bch * 100_000_000.0).round() as u64;
+            vector.remove(0);
+        }
+        // single element rows are comments
+        if vector.len() < 4 {
+            continue;
+        }
+        let field = |i: usize| vector[i].as_str().unwrap();
+        let script_sig = parse_asm(field(0));
+        let script_pubkey = parse_asm(field(1));
+        let flags = ScriptFlags::from_names(field(2))
+            .unwrap_or_else(|| panic!("unknown flags {}", field(2)));
+        let expected = field(3);
+        let (spend, spent_output) = spend_tx(script_sig.clone(), &script_pubkey, value);
+        let spent_outputs = [spent_output];
+        let context = ScriptExecutionContext {
+            tx: &spend,
+            input_index: 0,
+            spent_outputs: &spent_outputs,
+            sighash_cache: None,
+        };
+        let result = verify_script(&script_sig, &script_pubkey, flags, &context);
+        let actual = match &result {
+            Ok(_) => "OK",
+            Err(err) => error_name(err.kind()),
+        };
+        assert_eq!(actual, expected, "vector {:?}", vector);
+        num_tested += 1;
+    }
+    num_tested
+}
+
+/// Bitcoin Cash Node's src/test/data/script_tests.json, unchanged.
+#[test]
+#[ignore = "needs tests/data/script_tests.json, see scripts/fetch_test_vectors.sh"]
+fn test_bchn_script_vectors() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/script_tests.json");
+    let json = std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("can't read BCHN script tests at {}: {}", path, err));
+    assert!(run_script_vectors(&json) > 1000);
+}
+
+#[test]
+fn test_script_vectors() {
+    let num_tested = run_script_vectors(include_str!("data/script_tests_extra.json"));
+    assert!(num_tested > 100);
+}
+
+#[test]
+fn test_signed_p2pk() {
+    use cirrus_consensus::script::{sighash, SigHashType, UPGRADE9_HEIGHT};
+    use cirrus_crypto::PrivateKey;
+
+    let private_key = PrivateKey::from_slice(&[7; 32]).unwrap();
+    let mut script_pubkey = encode_push(&private_key.public_key().serialize());
+    script_pubkey.push(OP_CHECKSIG);
+    let flags = ScriptFlags::for_height(UPGRADE9_HEIGHT);
+    let sighash_type = SigHashType::from(SIGHASH_ALL | SIGHASH_FORKID);
+    for use_schnorr in &[false, true] {
+        let (mut spend, spent_output) = spend_tx(vec![], &script_pubkey, 1000);
+        let msg_hash = sighash(&spend, 0, &script_pubkey, 1000, sighash_type).unwrap();
+        let mut sig = if *use_schnorr {
+            private_key.sign_schnorr(&msg_hash).to_bytes().to_vec()
+        } else {
+            private_key.sign_ecdsa(&msg_hash).to_der()
+        };
+        sig.push(sighash_type.to_byte());
+        spend.inputs[0].script = encode_push(&sig);
+        let spent_outputs = [spent_output];
+        let verify = |spend: &Transaction| {
+            let context = ScriptExecutionContext {
+                tx: spend,
+                input_index: 0,
+                spent_outputs: &spent_outputs,
+                sighash_cache: None,
+            };
+            verify_script(&spend.inputs[0].script, &script_pubkey, flags, &context)
+        };
+        assert_eq!(verify(&spend).unwrap().sig_checks, 1);
+        // changing the tx invalidates the signature
+        spend.lock_time = 1;
+        match verify(&spend).unwrap_err().kind() {
+            ErrorKind::SigNullFail => {}
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+}
+
+#[test]
+fn test_token_introspection() {
+    use cirrus_consensus::script::{sighash_cached, sighash_spending, SigHashCache, SigHashType};
+    use cirrus_consensus::{Nft, NftCapability, TokenData};
+
+    let category = [0xcc; 32];
+    let mut expected_category = category.to_vec();
+    expected_category.push(0x02);
+    let script_pubkey = parse_asm(&format!(
+        "0 UTXOTOKENCATEGORY 0x21{} EQUALVERIFY \
+         0 UTXOTOKENCOMMITMENT 0x02abcd EQUALVERIFY \
+         0 UTXOTOKENAMOUNT 1000 NUMEQUALVERIFY \
+         0 OUTPUTTOKENCATEGORY 0x20{} EQUALVERIFY \
+         0 OUTPUTTOKENCOMMITMENT 0 EQUALVERIFY \
+         0 OUTPUTTOKENAMOUNT 400 NUMEQUAL",
+        hex::encode(&expected_category),
+        hex::encode(category),
+    ));
+    let (mut spend, mut spent_output) = spend_tx(vec![], &script_pubkey, 1000);
+    spent_output.token = Some(TokenData {
+        category,
+        nft: Some(Nft {
+            capability: NftCapability::Minting,
+            commitment: vec![0xab, 0xcd],
+        }),
+        amount: 1000,
+    });
+    spend.outputs[0].token = Some(TokenData {
+        category,
+        nft: None,
+        amount: 400,
+    });
+    let spent_outputs = [spent_output];
+    let context = ScriptExecutionContext {
+        tx: &spend,
+        input_index: 0,
+        spent_outputs: &spent_outputs,
+        sighash_cache: None,
+    };
+    let flags = ScriptFlags::NATIVE_INTROSPECTION | ScriptFlags::INTEGERS_64_BIT;
+    verify_script(
+        &[],
+        &script_pubkey,
+        flags | ScriptFlags::ENABLE_TOKENS,
+        &context,
+    )
+    .unwrap();
+    match verify_script(&[], &script_pubkey, flags, &context)
+        .unwrap_err()
+        .kind()
+    {
+        ErrorKind::BadOpcode => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    // outputs without tokens push empty categories and zero amounts
+    let script_pubkey =
+        parse_asm("0 OUTPUTTOKENCATEGORY 0 EQUALVERIFY 0 OUTPUTTOKENAMOUNT 0 NUMEQUAL");
+    spend.outputs[0].token = None;
+    let context = ScriptExecutionContext {
+        tx: &spend,
+        input_index: 0,
+        spent_outputs: &spent_outputs,
+        sighash_cache: None,
+    };
+    verify_script(
+        &[],
+        &script_pubkey,
+        flags | ScriptFlags::ENABLE_TOKENS,
+        &context,
+    )
+    .unwrap();
+
+    // the serialized output keeps the token prefix
+    let serialized = spend.serialize();
+    assert_eq!(
+        Transaction::from_slice(&serialized).unwrap().serialize(),
+        serialized
+    );
+    let output = &spent_outputs[0];
+    let mut serialized = Vec::new();
+    output.write_to_stream(&mut serialized).unwrap();
+    let parsed = TxOutput::from_stream(&mut &serialized[..]).unwrap();
+    assert_eq!(parsed.token, output.token);
+    assert_eq!(parsed.script, output.script);
+
+    // signatures commit to the tokens of the spent output
+    let cache = SigHashCache::new(&spend);
+    let sighash_type = SigHashType::from(SIGHASH_ALL | SIGHASH_FORKID);
+    let flags = ScriptFlags::ENABLE_SIGHASH_FORKID;
+    let without_token = sighash_cached(&cache, &spend, 0, &[], 1000, sighash_type, flags).unwrap();
+    let spending = |flags| sighash_spending(&cache, &spend, 0, &[], output, sighash_type, flags);
+    assert_eq!(spending(flags).unwrap(), without_token);
+    assert_ne!(
+        spending(flags | ScriptFlags::ENABLE_TOKENS).unwrap(),
+        without_token
+    );
+}
+
+#[test]
+fn test_inconsistent_context() {
+    let (spend, spent_output) = spend_tx(vec![], &[], 1000);
+    let spent_outputs = [spent_output];
+    let flags = ScriptFlags::NATIVE_INTROSPECTION
+        | ScriptFlags::INTEGERS_64_BIT
+        | ScriptFlags::ENABLE_TOKENS;
+    // fewer spent outputs than inputs, or an input index beyond the inputs
+    let contexts = [
+        ScriptExecutionContext {
+            tx: &spend,
+            input_index: 0,
+            spent_outputs: &[],
+            sighash_cache: None,
+        },
+        ScriptExecutionContext {
+            tx: &spend,
+            input_index: 1,
+            spent_outputs: &spent_outputs,
+            sighash_cache: None,
+        },
+    ];
+    for context in &contexts {
+        assert!(!context.is_consistent());
+        for asm in &[
+            "0 UTXOVALUE",
+            "0 UTXOBYTECODE",
+            "0 UTXOTOKENAMOUNT",
+            "INPUTINDEX",
+        ] {
+            let script_pubkey = parse_asm(asm);
+            match verify_script(&[], &script_pubkey, flags, context)
+                .unwrap_err()
+                .kind()
+            {
+                ErrorKind::ContextNotPresent => {}
+                kind => panic!("unexpected error {:?} for {}", kind, asm),
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use cirrus_consensus::script::{sighash, sighash_cached, ScriptFlags, SigHashCache, SigHashType};
+use cirrus_consensus::{Transaction, TxOutput};
+
+#[test]
+fn test_sighash_vectors() {
+    let json = include_str!("data/sighash_extra.json");
+    let vectors: Vec<Vec<serde_json::Value>> = serde_json::from_str(json).unwrap();
+    let flags = ScriptFlags::ENABLE_SIGHASH_FORKID;
+    let mut num_tested = 0;
+    for vector in vectors {
+        // single element rows are comments
+        if vector.len() < 7 {
+            continue;
+        }
+        let tx =
+            Transaction::from_slice(&hex::decode(vector[0].as_str().unwrap()).unwrap()).unwrap();
+        let script_code = hex::decode(vector[1].as_str().unwrap()).unwrap();
+        let input_index = vector[2].as_u64().unwrap() as usize;
+        let sighash_type = SigHashType(vector[3].as_u64().unwrap() as u32);
+        let amount = vector[4].as_u64().unwrap();
+        let spent_outputs = vector[5]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|output| {
+                let output = hex::decode(output.as_str().unwrap()).unwrap();
+                TxOutput::from_stream(&mut &output[..]).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let expected = vector[6].as_str().unwrap();
+
+        let cache = SigHashCache::with_spent_outputs(&tx, &spent_outputs);
+        let hash = sighash_cached(
+            &cache,
+            &tx,
+            input_index,
+            &script_code,
+            amount,
+            sighash_type,
+            flags,
+        );
+        assert_eq!(hex::encode(hash.unwrap()), expected, "vector {:?}", vector);
+        if !sighash_type.has_utxos() {
+            let hash = sighash(&tx, input_index, &script_code, amount, sighash_type).unwrap();
+            assert_eq!(hex::encode(hash), expected);
+        } else {
+            assert!(sighash(&tx, input_index, &script_code, amount, sighash_type).is_err());
+        }
+        num_tested += 1;
+    }
+    assert!(num_tested >= 100);
+}
+
+/// Bitcoin Cash Node's src/test/data/sighash.json, unchanged.
+///
+/// Rows are [raw_tx, script, input_index, hash_type, sighash, sighash without
+/// FORKID enabled, sighash with replay protection], hashes in display order,
+/// all computed for a spent amount of 0. Replay protection isn't supported.
+#[test]
+#[ignore = "needs tests/data/sighash.json, see scripts/fetch_test_vectors.sh"]
+fn test_bchn_sighash_vectors() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sighash.json");
+    let json = std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("can't read BCHN sighash tests at {}: {}", path, err));
+    let vectors: Vec<Vec<serde_json::Value>> = serde_json::from_str(&json).unwrap();
+    let mut num_tested = 0;
+    for vector in vectors {
+        // single element rows are comments
+        if vector.len() < 6 {
+            continue;
+        }
+        let tx =
+            Transaction::from_slice(&hex::decode(vector[0].as_str().unwrap()).unwrap()).unwrap();
+        let script_code = hex::decode(vector[1].as_str().unwrap()).unwrap();
+        let input_index = vector[2].as_u64().unwrap() as usize;
+        let sighash_type = SigHashType(vector[3].as_i64().unwrap() as u32);
+        let cache = SigHashCache::new(&tx);
+        for (flags, expected) in &[
+            (ScriptFlags::ENABLE_SIGHASH_FORKID, &vector[4]),
+            (ScriptFlags::empty(), &vector[5]),
+        ] {
+            let mut hash = sighash_cached(
+                &cache,
+                &tx,
+                input_index,
+                &script_code,
+                0,
+                sighash_type,
+                *flags,
+            )
+            .unwrap();
+            hash.reverse();
+            assert_eq!(
+                hex::encode(hash),
+                expected.as_str().unwrap(),
+                "vector {:?}",
+                vector
+            );
+        }
+        num_tested += 1;
+    }
+    assert!(num_tested >= 500);
+}
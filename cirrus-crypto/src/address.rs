@@ -0,0 +1,393 @@
+use crate::base58::{from_base58check, to_base58check};
+use crate::cashaddr;
+use crate::errors::{Error, ErrorKind, Result};
+use crate::hash::{double_sha256, hash160};
+use crate::keys::PublicKey;
+use crate::network::Network;
+use std::fmt;
+use std::str::FromStr;
+
+const OP_DUP: u8 = 0x76;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_HASH160: u8 = 0xa9;
+const OP_HASH256: u8 = 0xaa;
+const OP_CHECKSIG: u8 = 0xac;
+
+const TYPE_P2PKH: u8 = 0;
+const TYPE_P2SH: u8 = 1;
+const TYPE_TOKEN_P2PKH: u8 = 2;
+const TYPE_TOKEN_P2SH: u8 = 3;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum AddressType {
+    P2PKH,
+    P2SH,
+}
+
+/// A P2PKH, P2SH20 or P2SH32 address. Token-aware addresses (CashTokens)
+/// use the same output scripts but signal that the wallet accepts tokens.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Address {
+    network: Network,
+    addr_type: AddressType,
+    hash: Vec<u8>,
+    token_aware: bool,
+}
+
+fn cash_addr_prefix(network: Network) -> &'static str {
+    match network {
+        Network::Mainnet => "bitcoincash",
+        Network::Testnet => "bchtest",
+        Network::Regtest => "bchreg",
+    }
+}
+
+fn slp_addr_prefix(network: Network) -> &'static str {
+    match network {
+        Network::Mainnet => "simpleledger",
+        Network::Testnet => "slptest",
+        Network::Regtest => "slpreg",
+    }
+}
+
+fn legacy_version(network: Network, addr_type: AddressType) -> u8 {
+    match (network, addr_type) {
+        (Network::Mainnet, AddressType::P2PKH) => 0x00,
+        (Network::Mainnet, AddressType::P2SH) => 0x05,
+        (_, AddressType::P2PKH) => 0x6f,
+        (_, AddressType::P2SH) => 0xc4,
+    }
+}
+
+impl Address {
+    /// P2PKH addresses take a 20 byte hash, P2SH ones 20 or 32 bytes.
+    pub fn from_hash(network: Network, addr_type: AddressType, hash: &[u8]) -> Result<Address> {
+        match (addr_type, hash.len()) {
+            (AddressType::P2PKH, 20) | (AddressType::P2SH, 20) | (AddressType::P2SH, 32) => {}
+            _ => return Err(ErrorKind::InvalidAddressHash(hash.len()).into()),
+        }
+        Ok(Address {
+            network,
+            addr_type,
+            hash: hash.to_vec(),
+            token_aware: false,
+        })
+    }
+
+    pub fn p2pkh(network: Network, public_key: &PublicKey) -> Address {
+        Address {
+            network,
+            addr_type: AddressType::P2PKH,
+            hash: hash160(&public_key.serialize()).to_vec(),
+            token_aware: false,
+        }
+    }
+
+    /// P2SH20 address, committing to hash160(redeem_script).
+    pub fn p2sh(network: Network, redeem_script: &[u8]) -> Address {
+        Address {
+            network,
+            addr_type: AddressType::P2SH,
+            hash: hash160(redeem_script).to_vec(),
+            token_aware: false,
+        }
+    }
+
+    /// P2SH32 address, committing to double_sha256(redeem_script).
+    pub fn p2sh32(network: Network, redeem_script: &[u8]) -> Address {
+        Address {
+            network,
+            addr_type: AddressType::P2SH,
+            hash: double_sha256(redeem_script).to_vec(),
+            token_aware: false,
+        }
+    }
+
+    /// The same address, signalling support for CashTokens.
+    pub fn with_token_support(self, token_aware: bool) -> Address {
+        Address {
+            token_aware,
+            ..self
+        }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn addr_type(&self) -> AddressType {
+        self.addr_type
+    }
+
+    pub fn hash(&self) -> &[u8] {
+        &self.hash
+    }
+
+    pub fn is_token_aware(&self) -> bool {
+        self.token_aware
+    }
+
+    /// Parses a CashAddr with a bitcoincash, bchtest, bchreg or SLP prefix.
+    /// The prefix is required.
+    pub fn from_cash_addr(address: &str) -> Result<Address> {
+        let invalid = || Error::from(ErrorKind::InvalidAddress(address.to_string()));
+        let (prefix, version, hash) = cashaddr::decode(address)?;
+        let network = [Network::Mainnet, Network::Testnet, Network::Regtest]
+            .iter()
+            .cloned()
+            .find(|network| {
+                prefix == cash_addr_prefix(*network) || prefix == slp_addr_prefix(*network)
+            })
+            .ok_or_else(invalid)?;
+        let (addr_type, token_aware) = match version >> 3 {
+            TYPE_P2PKH => (AddressType::P2PKH, false),
+            TYPE_P2SH => (AddressType::P2SH, false),
+            TYPE_TOKEN_P2PKH => (AddressType::P2PKH, true),
+            TYPE_TOKEN_P2SH => (AddressType::P2SH, true),
+            _ => return Err(invalid()),
+        };
+        Address::from_hash(network, addr_type, &hash)
+            .map(|address| address.with_token_support(token_aware))
+            .map_err(|_| invalid())
+    }
+
+    /// Parses a Base58Check address; testnet and regtest can't be told apart
+    /// and are parsed as testnet.
+    pub fn from_legacy(address: &str) -> Result<Address> {
+        let invalid = || Error::from(ErrorKind::InvalidAddress(address.to_string()));
+        let data = from_base58check(address)?;
+        if data.len() != 21 {
+            return Err(invalid());
+        }
+        let (network, addr_type) = match data[0] {
+            0x00 => (Network::Mainnet, AddressType::P2PKH),
+            0x05 => (Network::Mainnet, AddressType::P2SH),
+            0x6f => (Network::Testnet, AddressType::P2PKH),
+            0xc4 => (Network::Testnet, AddressType::P2SH),
+            _ => return Err(invalid()),
+        };
+        Address::from_hash(network, addr_type, &data[1..])
+    }
+
+    pub fn to_cash_addr(&self) -> String {
+        self.encode_cash_addr(cash_addr_prefix(self.network))
+    }
+
+    /// The CashAddr with the simpleledger (or slptest, slpreg) prefix.
+    pub fn to_slp_addr(&self) -> String {
+        self.encode_cash_addr(slp_addr_prefix(self.network))
+    }
+
+    fn encode_cash_addr(&self, prefix: &str) -> String {
+        let addr_type = match (self.addr_type, self.token_aware) {
+            (AddressType::P2PKH, false) => TYPE_P2PKH,
+            (AddressType::P2SH, false) => TYPE_P2SH,
+            (AddressType::P2PKH, true) => TYPE_TOKEN_P2PKH,
+            (AddressType::P2SH, true) => TYPE_TOKEN_P2SH,
+        };
+        let size_bits = if self.hash.len() == 32 { 3 } else { 0 };
+        cashaddr::encode(prefix, addr_type << 3 | size_bits, &self.hash)
+    }
+
+    /// Fails for P2SH32 and token-aware addresses, which have no legacy encoding.
+    pub fn to_legacy(&self) -> Result<String> {
+        if self.hash.len() != 20 || self.token_aware {
+            return Err(ErrorKind::NoLegacyEncoding.into());
+        }
+        let mut data = vec![legacy_version(self.network, self.addr_type)];
+        data.extend_from_slice(&self.hash);
+        Ok(to_base58check(&data))
+    }
+
+    /// The output script paying to this address.
+    pub fn to_script(&self) -> Vec<u8> {
+        let mut script = Vec::with_capacity(self.hash.len() + 5);
+        match (self.addr_type, self.hash.len()) {
+            (AddressType::P2PKH, _) => {
+                script.extend_from_slice(&[OP_DUP, OP_HASH160, 20]);
+                script.extend_from_slice(&self.hash);
+                script.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+            }
+            (AddressType::P2SH, 32) => {
+                script.extend_from_slice(&[OP_HASH256, 32]);
+                script.extend_from_slice(&self.hash);
+                script.push(OP_EQUAL);
+            }
+            (AddressType::P2SH, _) => {
+                script.extend_from_slice(&[OP_HASH160, 20]);
+                script.extend_from_slice(&self.hash);
+                script.push(OP_EQUAL);
+            }
+        }
+        script
+    }
+
+    /// Recognizes P2PKH, P2SH20 and P2SH32 output scripts.
+    pub fn from_script(network: Network, script: &[u8]) -> Result<Address> {
+        match script {
+            [OP_DUP, OP_HASH160, 20, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG]
+                if hash.len() == 20 =>
+            {
+                Address::from_hash(network, AddressType::P2PKH, hash)
+            }
+            [OP_HASH160, 20, hash @ .., OP_EQUAL] if hash.len() == 20 => {
+                Address::from_hash(network, AddressType::P2SH, hash)
+            }
+            [OP_HASH256, 32, hash @ .., OP_EQUAL] if hash.len() == 32 => {
+                Address::from_hash(network, AddressType::P2SH, hash)
+            }
+            _ => Err(ErrorKind::NonStandardScript.into()),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt<'a>(&self, f: &mut fmt::Formatter<'a>) -> fmt::Result {
+        write!(f, "{}", self.to_cash_addr())
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+
+    /// Parses a CashAddr (with prefix) or a legacy address.
+    fn from_str(address: &str) -> Result<Address> {
+        if address.contains(':') {
+            Address::from_cash_addr(address)
+        } else {
+            Address::from_legacy(address)
+        }
+    }
+}
+
+#[test]
+fn test_address() {
+    use hex_literal::hex;
+    // (legacy, cashaddr) from the CashAddr spec
+    let vectors = [
+        (
+            "1BpEi6DfDAUFd7GtittLSdBeYJvcoaVggu",
+            "bitcoincash:qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6a",
+        ),
+        (
+            "1KXrWXciRDZUpQwQmuM1DbwsKDLYAYsVLR",
+            "bitcoincash:qr95sy3j9xwd2ap32xkykttr4cvcu7as4y0qverfuy",
+        ),
+        (
+            "16w1D5WRVKJuZUsSRzdLp9w3YGcgoxDXb",
+            "bitcoincash:qqq3728yw0y47sqn6l2na30mcw6zm78dzqre909m2r",
+        ),
+        (
+            "3CWFddi6m4ndiGyKqzYvsFYagqDLPVMTzC",
+            "bitcoincash:ppm2qsznhks23z7629mms6s4cwef74vcwvn0h829pq",
+        ),
+        (
+            "3LDsS579y7sruadqu11beEJoTjdFiFCdX4",
+            "bitcoincash:pr95sy3j9xwd2ap32xkykttr4cvcu7as4yc93ky28e",
+        ),
+        (
+            "31nwvkZwyPdgzjBJZXfDmSWsC4ZLKpYyUw",
+            "bitcoincash:pqq3728yw0y47sqn6l2na30mcw6zm78dzq5ucqzc37",
+        ),
+    ];
+    for (legacy, cash_addr) in vectors.iter() {
+        let address = Address::from_legacy(legacy).unwrap();
+        assert_eq!(address.to_cash_addr(), *cash_addr);
+        assert_eq!(cash_addr.parse::<Address>().unwrap(), address);
+        assert_eq!(
+            cash_addr.to_uppercase().parse::<Address>().unwrap(),
+            address
+        );
+        assert_eq!(address.to_legacy().unwrap(), *legacy);
+        assert_eq!(
+            Address::from_script(Network::Mainnet, &address.to_script()).unwrap(),
+            address
+        );
+    }
+
+    let hash = hex!("cb481232299cd5743151ac4b2d63ae198e7bb0a9");
+    let p2pkh = Address::from_hash(Network::Mainnet, AddressType::P2PKH, &hash).unwrap();
+    assert_eq!(
+        p2pkh.to_script(),
+        hex!("76a914cb481232299cd5743151ac4b2d63ae198e7bb0a988ac").to_vec(),
+    );
+    assert_eq!(
+        p2pkh.to_slp_addr(),
+        "simpleledger:qr95sy3j9xwd2ap32xkykttr4cvcu7as4yrm8zkfz6"
+    );
+    let slp = Address::from_cash_addr("simpleledger:qr95sy3j9xwd2ap32xkykttr4cvcu7as4yrm8zkfz6");
+    assert_eq!(slp.unwrap(), p2pkh);
+    let p2sh = Address::from_hash(Network::Mainnet, AddressType::P2SH, &hash).unwrap();
+    assert_eq!(
+        p2sh.to_slp_addr(),
+        "simpleledger:pr95sy3j9xwd2ap32xkykttr4cvcu7as4y576d32e8"
+    );
+
+    // token-aware addresses, the first one from the CashTokens CHIP
+    let address = Address::from_cash_addr("bitcoincash:qr7fzmep8g7h7ymfxy74lgc0v950j3r2959lhtxxsl");
+    let token_address = address.unwrap().with_token_support(true);
+    assert_eq!(
+        token_address.to_cash_addr(),
+        "bitcoincash:zr7fzmep8g7h7ymfxy74lgc0v950j3r295z4y4gq0v",
+    );
+    assert!(token_address.to_legacy().is_err());
+    let token_p2sh = p2sh.clone().with_token_support(true);
+    assert_eq!(
+        token_p2sh.to_cash_addr(),
+        "bitcoincash:rr95sy3j9xwd2ap32xkykttr4cvcu7as4yl0zg2vc2"
+    );
+    let parsed = Address::from_cash_addr("bitcoincash:zr95sy3j9xwd2ap32xkykttr4cvcu7as4yg2l8d0rh");
+    assert!(parsed.unwrap().is_token_aware());
+
+    // other networks
+    let testnet = Address::from_hash(Network::Testnet, AddressType::P2PKH, &hash).unwrap();
+    assert_eq!(
+        testnet.to_cash_addr(),
+        "bchtest:qr95sy3j9xwd2ap32xkykttr4cvcu7as4ytjg7p7mc"
+    );
+    assert_eq!(
+        testnet.to_slp_addr(),
+        "slptest:qr95sy3j9xwd2ap32xkykttr4cvcu7as4ysx09mff9"
+    );
+    assert_eq!(
+        testnet.to_legacy().unwrap(),
+        "mz3ooahhEEzjbXR2VUKP3XACBCwF5zhQBy"
+    );
+    let regtest = Address::from_hash(Network::Regtest, AddressType::P2PKH, &hash).unwrap();
+    assert_eq!(
+        regtest.to_cash_addr(),
+        "bchreg:qr95sy3j9xwd2ap32xkykttr4cvcu7as4y3w7lzdc7"
+    );
+    let legacy_p2sh = Address::from_legacy("2NBn5Vp3BaaPD7NGPa8dUGBJ4g5qRXq92wG").unwrap();
+    assert_eq!(legacy_p2sh.network(), Network::Testnet);
+    assert_eq!(legacy_p2sh.addr_type(), AddressType::P2SH);
+
+    // P2SH32
+    let hash32: Vec<u8> = (0..32).collect();
+    let p2sh32 = Address::from_hash(Network::Mainnet, AddressType::P2SH, &hash32).unwrap();
+    assert_eq!(
+        p2sh32.to_cash_addr(),
+        "bitcoincash:pvqqzqsrqszsvpcgpy9qkrqdpc83qygjzv2p29shrqv35xcur50p7h2c7ctj5",
+    );
+    assert_eq!(p2sh32.to_script()[..2], [OP_HASH256, 32]);
+    assert_eq!(
+        Address::from_script(Network::Mainnet, &p2sh32.to_script()).unwrap(),
+        p2sh32
+    );
+    assert!(p2sh32.to_legacy().is_err());
+    assert!(Address::from_hash(Network::Mainnet, AddressType::P2PKH, &hash32).is_err());
+
+    // invalid addresses
+    let invalid = [
+        "bitcoincash:qr95sy3j9xwd2ap32xkykttr4cvcu7as4y0qverfuz",
+        "bitcoincash:qr95sy3j9xwd2ap32xkykttr4cvcu7as4Y0qverfuy",
+        "bitcash:qr95sy3j9xwd2ap32xkykttr4cvcu7as4y0qverfuy",
+        "bitcoincash:",
+        "1KXrWXciRDZUpQwQmuM1DbwsKDLYAYsVLS",
+    ];
+    for address in invalid.iter() {
+        assert!(address.parse::<Address>().is_err());
+    }
+    assert!(Address::from_script(Network::Mainnet, &[0x6a]).is_err());
+}
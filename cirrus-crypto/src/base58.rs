@@ -0,0 +1,95 @@
+use crate::errors::{ErrorKind, Result};
+use crate::hash::double_sha256;
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+pub fn to_base58(data: &[u8]) -> String {
+    let num_leading_zeros = data.iter().take_while(|byte| **byte == 0).count();
+    // little endian base 58 digits
+    let mut digits: Vec<u8> = Vec::with_capacity(data.len() * 138 / 100 + 1);
+    for byte in &data[num_leading_zeros..] {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let mut encoded = String::with_capacity(num_leading_zeros + digits.len());
+    encoded.push_str(&"1".repeat(num_leading_zeros));
+    encoded.extend(
+        digits
+            .iter()
+            .rev()
+            .map(|digit| ALPHABET[*digit as usize] as char),
+    );
+    encoded
+}
+
+pub fn from_base58(encoded: &str) -> Result<Vec<u8>> {
+    let num_leading_ones = encoded.bytes().take_while(|c| *c == b'1').count();
+    // little endian base 256 digits
+    let mut bytes: Vec<u8> = Vec::with_capacity(encoded.len());
+    for c in encoded.bytes().skip(num_leading_ones) {
+        let mut carry = match ALPHABET.iter().position(|a| *a == c) {
+            Some(value) => value as u32,
+            None => return Err(ErrorKind::InvalidBase58.into()),
+        };
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let mut decoded = vec![0; num_leading_ones];
+    decoded.extend(bytes.iter().rev());
+    Ok(decoded)
+}
+
+/// Appends the first 4 bytes of double_sha256(payload) and encodes in base 58.
+pub fn to_base58check(payload: &[u8]) -> String {
+    let mut data = payload.to_vec();
+    data.extend_from_slice(&double_sha256(payload)[..4]);
+    to_base58(&data)
+}
+
+pub fn from_base58check(encoded: &str) -> Result<Vec<u8>> {
+    let mut data = from_base58(encoded)?;
+    if data.len() < 4 {
+        return Err(ErrorKind::InvalidChecksum.into());
+    }
+    let checksum = data.split_off(data.len() - 4);
+    if checksum[..] != double_sha256(&data)[..4] {
+        return Err(ErrorKind::InvalidChecksum.into());
+    }
+    Ok(data)
+}
+
+#[test]
+fn test_base58() {
+    use hex_literal::hex;
+    assert_eq!(to_base58(b""), "");
+    assert_eq!(to_base58(&hex!("00000102")), "115T");
+    assert_eq!(from_base58("115T").unwrap(), hex!("00000102").to_vec());
+    assert_eq!(to_base58(b"hello world"), "StV1DL6CwTryKyV");
+    assert_eq!(
+        from_base58("StV1DL6CwTryKyV").unwrap(),
+        b"hello world".to_vec()
+    );
+    assert!(from_base58("0OIl").is_err());
+    let encoded = to_base58check(&hex!("00f54a5851e9372b87810a8e60cdd2e7cfd80b6e31"));
+    assert_eq!(encoded, "1PMycacnJaSqwwJqjawXBErnLsZ7RkXUAs");
+    assert_eq!(
+        from_base58check(&encoded).unwrap(),
+        hex!("00f54a5851e9372b87810a8e60cdd2e7cfd80b6e31").to_vec(),
+    );
+    assert!(from_base58check("1PMycacnJaSqwwJqjawXBErnLsZ7RkXUAt").is_err());
+}
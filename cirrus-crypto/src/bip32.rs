@@ -0,0 +1,424 @@
+use crate::base58::{from_base58check, to_base58check};
+use crate::errors::{Error, ErrorKind, Result};
+use crate::hash::{hash160, hmac_sha512};
+use crate::keys::{PrivateKey, PublicKey};
+use crate::network::Network;
+use byteorder::{BigEndian, ByteOrder};
+use std::fmt;
+use std::str::FromStr;
+
+pub const HARDENED_BIT: u32 = 1 << 31;
+
+const XPRV_VERSION: u32 = 0x0488_ade4;
+const XPUB_VERSION: u32 = 0x0488_b21e;
+const TPRV_VERSION: u32 = 0x0435_8394;
+const TPUB_VERSION: u32 = 0x0435_87cf;
+
+const SERIALIZED_LEN: usize = 78;
+
+/// A list of child numbers, hardened ones have HARDENED_BIT set.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DerivationPath(pub Vec<u32>);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExtendedPrivateKey {
+    pub network: Network,
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub private_key: PrivateKey,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExtendedPublicKey {
+    pub network: Network,
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub public_key: PublicKey,
+}
+
+impl DerivationPath {
+    pub fn child(&self, child_number: u32) -> DerivationPath {
+        let mut path = self.0.clone();
+        path.push(child_number);
+        DerivationPath(path)
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    /// Parses paths like m/44'/245'/0'/0/5; h can be used instead of '.
+    fn from_str(path: &str) -> Result<DerivationPath> {
+        let invalid = || Error::from(ErrorKind::InvalidDerivationPath(path.to_string()));
+        let mut parts = path.split('/');
+        if parts.next() != Some("m") {
+            return Err(invalid());
+        }
+        let mut child_numbers = Vec::new();
+        for part in parts {
+            let (index, hardened_bit) = if part.ends_with('\'') || part.ends_with('h') {
+                (&part[..part.len() - 1], HARDENED_BIT)
+            } else {
+                (part, 0)
+            };
+            let index: u32 = index.parse().map_err(|_| invalid())?;
+            if index >= HARDENED_BIT {
+                return Err(invalid());
+            }
+            child_numbers.push(index | hardened_bit);
+        }
+        Ok(DerivationPath(child_numbers))
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt<'a>(&self, f: &mut fmt::Formatter<'a>) -> fmt::Result {
+        write!(f, "m")?;
+        for child_number in self.0.iter() {
+            if child_number & HARDENED_BIT != 0 {
+                write!(f, "/{}'", child_number & !HARDENED_BIT)?;
+            } else {
+                write!(f, "/{}", child_number)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn serialize(
+    version: u32,
+    depth: u8,
+    parent_fingerprint: &[u8; 4],
+    child_number: u32,
+    chain_code: &[u8; 32],
+    key: &[u8; 33],
+) -> String {
+    let mut data = [0; SERIALIZED_LEN];
+    BigEndian::write_u32(&mut data[0..4], version);
+    data[4] = depth;
+    data[5..9].copy_from_slice(parent_fingerprint);
+    BigEndian::write_u32(&mut data[9..13], child_number);
+    data[13..45].copy_from_slice(chain_code);
+    data[45..78].copy_from_slice(key);
+    to_base58check(&data)
+}
+
+struct Deserialized {
+    version: u32,
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+    key: [u8; 33],
+}
+
+fn deserialize(encoded: &str) -> Result<Deserialized> {
+    let data = from_base58check(encoded)?;
+    if data.len() != SERIALIZED_LEN {
+        return Err(ErrorKind::InvalidExtendedKey.into());
+    }
+    let mut parent_fingerprint = [0; 4];
+    parent_fingerprint.copy_from_slice(&data[5..9]);
+    let mut chain_code = [0; 32];
+    chain_code.copy_from_slice(&data[13..45]);
+    let mut key = [0; 33];
+    key.copy_from_slice(&data[45..78]);
+    Ok(Deserialized {
+        version: BigEndian::read_u32(&data[0..4]),
+        depth: data[4],
+        parent_fingerprint,
+        child_number: BigEndian::read_u32(&data[9..13]),
+        chain_code,
+        key,
+    })
+}
+
+/// Splits HMAC-SHA512(chain_code, data || child_number) into tweak and chain code.
+fn derive_tweak(chain_code: &[u8; 32], data: &[u8], child_number: u32) -> ([u8; 32], [u8; 32]) {
+    let mut message = data.to_vec();
+    message.extend_from_slice(&child_number.to_be_bytes());
+    let hash = hmac_sha512(chain_code, &message);
+    let mut tweak = [0; 32];
+    let mut child_chain_code = [0; 32];
+    tweak.copy_from_slice(&hash[..32]);
+    child_chain_code.copy_from_slice(&hash[32..]);
+    (tweak, child_chain_code)
+}
+
+impl ExtendedPrivateKey {
+    /// Derives the master key from a seed, e.g. from BIP39.
+    pub fn from_seed(seed: &[u8], network: Network) -> Result<ExtendedPrivateKey> {
+        let hash = hmac_sha512(b"Bitcoin seed", seed);
+        let mut chain_code = [0; 32];
+        chain_code.copy_from_slice(&hash[32..]);
+        Ok(ExtendedPrivateKey {
+            network,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+            chain_code,
+            private_key: PrivateKey::from_slice(&hash[..32])?,
+        })
+    }
+
+    pub fn derive_child(&self, child_number: u32) -> Result<ExtendedPrivateKey> {
+        let data = if child_number & HARDENED_BIT != 0 {
+            let mut data = vec![0];
+            data.extend_from_slice(&self.private_key.to_bytes());
+            data
+        } else {
+            self.private_key
+                .public_key()
+                .serialize_compressed()
+                .to_vec()
+        };
+        let (tweak, chain_code) = derive_tweak(&self.chain_code, &data, child_number);
+        Ok(ExtendedPrivateKey {
+            network: self.network,
+            depth: self
+                .depth
+                .checked_add(1)
+                .ok_or(ErrorKind::InvalidExtendedKey)?,
+            parent_fingerprint: self.fingerprint(),
+            child_number,
+            chain_code,
+            private_key: self.private_key.tweak_add(&tweak)?,
+        })
+    }
+
+    /// Derives all children in `path`, relative to this key.
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<ExtendedPrivateKey> {
+        let mut key = self.clone();
+        for child_number in path.0.iter() {
+            key = key.derive_child(*child_number)?;
+        }
+        Ok(key)
+    }
+
+    pub fn extended_public_key(&self) -> ExtendedPublicKey {
+        ExtendedPublicKey {
+            network: self.network,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            public_key: self.private_key.public_key(),
+        }
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        self.extended_public_key().fingerprint()
+    }
+}
+
+impl ExtendedPublicKey {
+    /// Fails for hardened child numbers, which require the private key.
+    pub fn derive_child(&self, child_number: u32) -> Result<ExtendedPublicKey> {
+        if child_number & HARDENED_BIT != 0 {
+            return Err(ErrorKind::HardenedDerivationFromPublicKey.into());
+        }
+        let data = self.public_key.serialize_compressed();
+        let (tweak, chain_code) = derive_tweak(&self.chain_code, &data, child_number);
+        Ok(ExtendedPublicKey {
+            network: self.network,
+            depth: self
+                .depth
+                .checked_add(1)
+                .ok_or(ErrorKind::InvalidExtendedKey)?,
+            parent_fingerprint: self.fingerprint(),
+            child_number,
+            chain_code,
+            public_key: self.public_key.tweak_add(&tweak)?,
+        })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<ExtendedPublicKey> {
+        let mut key = self.clone();
+        for child_number in path.0.iter() {
+            key = key.derive_child(*child_number)?;
+        }
+        Ok(key)
+    }
+
+    /// hash160 of the compressed public key.
+    pub fn identifier(&self) -> [u8; 20] {
+        hash160(&self.public_key.serialize_compressed())
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let mut fingerprint = [0; 4];
+        fingerprint.copy_from_slice(&self.identifier()[..4]);
+        fingerprint
+    }
+}
+
+impl fmt::Display for ExtendedPrivateKey {
+    fn fmt<'a>(&self, f: &mut fmt::Formatter<'a>) -> fmt::Result {
+        let version = match self.network {
+            Network::Mainnet => XPRV_VERSION,
+            Network::Testnet | Network::Regtest => TPRV_VERSION,
+        };
+        let mut key = [0; 33];
+        key[1..].copy_from_slice(&self.private_key.to_bytes());
+        let encoded = serialize(
+            version,
+            self.depth,
+            &self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            &key,
+        );
+        write!(f, "{}", encoded)
+    }
+}
+
+impl fmt::Display for ExtendedPublicKey {
+    fn fmt<'a>(&self, f: &mut fmt::Formatter<'a>) -> fmt::Result {
+        let version = match self.network {
+            Network::Mainnet => XPUB_VERSION,
+            Network::Testnet | Network::Regtest => TPUB_VERSION,
+        };
+        let encoded = serialize(
+            version,
+            self.depth,
+            &self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            &self.public_key.serialize_compressed(),
+        );
+        write!(f, "{}", encoded)
+    }
+}
+
+impl FromStr for ExtendedPrivateKey {
+    type Err = Error;
+
+    /// Parses xprv and tprv keys; tprv keys are parsed as testnet keys.
+    fn from_str(encoded: &str) -> Result<ExtendedPrivateKey> {
+        let data = deserialize(encoded)?;
+        let network = match data.version {
+            XPRV_VERSION => Network::Mainnet,
+            TPRV_VERSION => Network::Testnet,
+            _ => return Err(ErrorKind::InvalidExtendedKey.into()),
+        };
+        if data.key[0] != 0 {
+            return Err(ErrorKind::InvalidExtendedKey.into());
+        }
+        Ok(ExtendedPrivateKey {
+            network,
+            depth: data.depth,
+            parent_fingerprint: data.parent_fingerprint,
+            child_number: data.child_number,
+            chain_code: data.chain_code,
+            private_key: PrivateKey::from_slice(&data.key[1..])?,
+        })
+    }
+}
+
+impl FromStr for ExtendedPublicKey {
+    type Err = Error;
+
+    /// Parses xpub and tpub keys; tpub keys are parsed as testnet keys.
+    fn from_str(encoded: &str) -> Result<ExtendedPublicKey> {
+        let data = deserialize(encoded)?;
+        let network = match data.version {
+            XPUB_VERSION => Network::Mainnet,
+            TPUB_VERSION => Network::Testnet,
+            _ => return Err(ErrorKind::InvalidExtendedKey.into()),
+        };
+        Ok(ExtendedPublicKey {
+            network,
+            depth: data.depth,
+            parent_fingerprint: data.parent_fingerprint,
+            child_number: data.child_number,
+            chain_code: data.chain_code,
+            public_key: PublicKey::from_slice(&data.key)?,
+        })
+    }
+}
+
+#[test]
+fn test_bip32_vectors() {
+    use hex_literal::hex;
+    // (path, xpub, xprv) for each seed of BIP32 test vectors 1, 2 and 3
+    type Chain<'a> = &'a [(&'a str, &'a str, &'a str)];
+    let vectors: &[(&[u8], Chain)] = &[
+        (&hex!("000102030405060708090a0b0c0d0e0f"), &[
+            ("m", "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8", "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi"),
+            ("m/0'", "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw", "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7"),
+            ("m/0'/1", "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ", "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs"),
+            ("m/0'/1/2'", "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5", "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM"),
+            ("m/0'/1/2'/2", "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV", "xprvA2JDeKCSNNZky6uBCviVfJSKyQ1mDYahRjijr5idH2WwLsEd4Hsb2Tyh8RfQMuPh7f7RtyzTtdrbdqqsunu5Mm3wDvUAKRHSC34sJ7in334"),
+            ("m/0'/1/2'/2/1000000000", "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy", "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76"),
+        ]),
+        (&hex!("fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542"), &[
+            ("m", "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB", "xprv9s21ZrQH143K31xYSDQpPDxsXRTUcvj2iNHm5NUtrGiGG5e2DtALGdso3pGz6ssrdK4PFmM8NSpSBHNqPqm55Qn3LqFtT2emdEXVYsCzC2U"),
+            ("m/0", "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH", "xprv9vHkqa6EV4sPZHYqZznhT2NPtPCjKuDKGY38FBWLvgaDx45zo9WQRUT3dKYnjwih2yJD9mkrocEZXo1ex8G81dwSM1fwqWpWkeS3v86pgKt"),
+            ("m/0/2147483647'", "xpub6ASAVgeehLbnwdqV6UKMHVzgqAG8Gr6riv3Fxxpj8ksbH9ebxaEyBLZ85ySDhKiLDBrQSARLq1uNRts8RuJiHjaDMBU4Zn9h8LZNnBC5y4a", "xprv9wSp6B7kry3Vj9m1zSnLvN3xH8RdsPP1Mh7fAaR7aRLcQMKTR2vidYEeEg2mUCTAwCd6vnxVrcjfy2kRgVsFawNzmjuHc2YmYRmagcEPdU9"),
+            ("m/0/2147483647'/1", "xpub6DF8uhdarytz3FWdA8TvFSvvAh8dP3283MY7p2V4SeE2wyWmG5mg5EwVvmdMVCQcoNJxGoWaU9DCWh89LojfZ537wTfunKau47EL2dhHKon", "xprv9zFnWC6h2cLgpmSA46vutJzBcfJ8yaJGg8cX1e5StJh45BBciYTRXSd25UEPVuesF9yog62tGAQtHjXajPPdbRCHuWS6T8XA2ECKADdw4Ef"),
+            ("m/0/2147483647'/1/2147483646'", "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL", "xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc"),
+            ("m/0/2147483647'/1/2147483646'/2", "xpub6FnCn6nSzZAw5Tw7cgR9bi15UV96gLZhjDstkXXxvCLsUXBGXPdSnLFbdpq8p9HmGsApME5hQTZ3emM2rnY5agb9rXpVGyy3bdW6EEgAtqt", "xprvA2nrNbFZABcdryreWet9Ea4LvTJcGsqrMzxHx98MMrotbir7yrKCEXw7nadnHM8Dq38EGfSh6dqA9QWTyefMLEcBYJUuekgW4BYPJcr9E7j"),
+        ]),
+        (&hex!("4b381541583be4423346c643850da4b320e46a87ae3d2a4e6da11eba819cd4acba45d239319ac14f863b8d5ab5a0d0c64d2e8a1e7d1457df2e5a3c51c73235be"), &[
+            ("m", "xpub661MyMwAqRbcEZVB4dScxMAdx6d4nFc9nvyvH3v4gJL378CSRZiYmhRoP7mBy6gSPSCYk6SzXPTf3ND1cZAceL7SfJ1Z3GC8vBgp2epUt13", "xprv9s21ZrQH143K25QhxbucbDDuQ4naNntJRi4KUfWT7xo4EKsHt2QJDu7KXp1A3u7Bi1j8ph3EGsZ9Xvz9dGuVrtHHs7pXeTzjuxBrCmmhgC6"),
+            ("m/0'", "xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y", "xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L"),
+        ]),
+    ];
+    for (seed, chain) in vectors {
+        let master = ExtendedPrivateKey::from_seed(seed, Network::Mainnet).unwrap();
+        for (path, xpub, xprv) in chain.iter() {
+            let path: DerivationPath = path.parse().unwrap();
+            let key = master.derive_path(&path).unwrap();
+            assert_eq!(key.to_string(), *xprv);
+            assert_eq!(key.extended_public_key().to_string(), *xpub);
+            assert_eq!(xprv.parse::<ExtendedPrivateKey>().unwrap(), key);
+            assert_eq!(
+                xpub.parse::<ExtendedPublicKey>().unwrap(),
+                key.extended_public_key()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_bip32_derivation() {
+    use hex_literal::hex;
+    let seed = hex!("000102030405060708090a0b0c0d0e0f");
+    let master = ExtendedPrivateKey::from_seed(&seed, Network::Testnet).unwrap();
+    let path: DerivationPath = "m/44'/245'/0'/0/5".parse().unwrap();
+    assert_eq!(path.to_string(), "m/44'/245'/0'/0/5");
+    assert_eq!("m/44h/245h/0h/0/5".parse::<DerivationPath>().unwrap(), path);
+    let key = master.derive_path(&path).unwrap();
+    assert_eq!(
+        key.extended_public_key().to_string(),
+        "tpubDHJ4msgko5dTgShFFXomBABMM3kEwq6H66VJo8s7T2KM69gUx7bgRKnNGs2RQkk9t1SauFJgLUoNEUsokgEZnMfFB1T4tGr9HtfTj1Cqfwi",
+    );
+    assert_eq!(
+        key.private_key.public_key().serialize_compressed()[..],
+        hex!("038e4802e64d44a3c115f66df915ca7b3acdfb9a86f234f4310e36aeb4b4c04cc6")[..],
+    );
+    // non-hardened children can be derived from the account xpub
+    let account_path: DerivationPath = "m/44'/245'/0'".parse().unwrap();
+    let account = master.derive_path(&account_path).unwrap();
+    let public_child = account
+        .extended_public_key()
+        .derive_path(&DerivationPath(vec![0, 5]))
+        .unwrap();
+    assert_eq!(public_child, key.extended_public_key());
+    assert!(account
+        .extended_public_key()
+        .derive_child(HARDENED_BIT)
+        .is_err());
+
+    for invalid in &["", "44'/0", "m/", "m/x", "m/2147483648", "m/1''"] {
+        assert!(invalid.parse::<DerivationPath>().is_err());
+    }
+    assert_eq!(
+        "m".parse::<DerivationPath>().unwrap(),
+        DerivationPath(vec![])
+    );
+}
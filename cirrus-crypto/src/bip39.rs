@@ -0,0 +1,238 @@
+use crate::errors::{Error, ErrorKind, Result};
+use crate::hash::sha256;
+use hmac::Hmac;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha512;
+use std::fmt;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+
+const WORDLIST_ENGLISH: &str = include_str!("wordlists/english.txt");
+
+const PBKDF2_ROUNDS: usize = 2048;
+
+const WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Language {
+    English,
+}
+
+/// A BIP39 mnemonic phrase of 12, 15, 18, 21 or 24 words.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mnemonic {
+    language: Language,
+    words: Vec<&'static str>,
+    entropy: Vec<u8>,
+}
+
+impl Language {
+    pub fn wordlist(self) -> Vec<&'static str> {
+        match self {
+            Language::English => WORDLIST_ENGLISH.lines().collect(),
+        }
+    }
+}
+
+fn checksum_bits(entropy: &[u8]) -> usize {
+    entropy.len() * 8 / 32
+}
+
+/// Reads the `bit_index`-th bit of `data`, most significant bit first.
+fn bit(data: &[u8], bit_index: usize) -> bool {
+    data[bit_index / 8] & (0x80 >> (bit_index % 8)) != 0
+}
+
+impl Mnemonic {
+    /// Generates a new mnemonic with `word_count` words from OS randomness.
+    pub fn generate(word_count: usize, language: Language) -> Result<Mnemonic> {
+        if !WORD_COUNTS.contains(&word_count) {
+            return Err(ErrorKind::InvalidMnemonicWordCount(word_count).into());
+        }
+        let mut entropy = vec![0; word_count / 3 * 4];
+        OsRng.fill_bytes(&mut entropy);
+        Mnemonic::from_entropy(&entropy, language)
+    }
+
+    /// Fails if `entropy` isn't 16, 20, 24, 28 or 32 bytes long.
+    pub fn from_entropy(entropy: &[u8], language: Language) -> Result<Mnemonic> {
+        if !WORD_COUNTS
+            .iter()
+            .any(|count| count / 3 * 4 == entropy.len())
+        {
+            return Err(ErrorKind::InvalidEntropyLength(entropy.len()).into());
+        }
+        let mut data = entropy.to_vec();
+        data.extend_from_slice(&sha256(entropy));
+        let wordlist = language.wordlist();
+        let num_bits = entropy.len() * 8 + checksum_bits(entropy);
+        let words = (0..num_bits / 11)
+            .map(|word_idx| {
+                let index = (0..11).fold(0, |index, bit_idx| {
+                    (index << 1) | bit(&data, word_idx * 11 + bit_idx) as usize
+                });
+                wordlist[index]
+            })
+            .collect();
+        Ok(Mnemonic {
+            language,
+            words,
+            entropy: entropy.to_vec(),
+        })
+    }
+
+    /// Parses a phrase of whitespace separated words and validates its checksum.
+    pub fn from_phrase(phrase: &str, language: Language) -> Result<Mnemonic> {
+        let phrase = phrase.nfkd().collect::<String>();
+        let wordlist = language.wordlist();
+        let indices = phrase
+            .split_whitespace()
+            .map(|word| {
+                wordlist
+                    .iter()
+                    .position(|candidate| *candidate == word)
+                    .ok_or_else(|| Error::from(ErrorKind::InvalidMnemonicWord(word.to_string())))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if !WORD_COUNTS.contains(&indices.len()) {
+            return Err(ErrorKind::InvalidMnemonicWordCount(indices.len()).into());
+        }
+        let entropy_len = indices.len() / 3 * 4;
+        // the checksum has at most 8 bits
+        let mut data = vec![0u8; entropy_len + 1];
+        for (word_idx, index) in indices.iter().enumerate() {
+            for bit_idx in 0..11 {
+                if index & (1 << (10 - bit_idx)) != 0 {
+                    let bit_index = word_idx * 11 + bit_idx;
+                    data[bit_index / 8] |= 0x80 >> (bit_index % 8);
+                }
+            }
+        }
+        let mnemonic = Mnemonic::from_entropy(&data[..entropy_len], language)?;
+        let checksum = checksum_bits(&mnemonic.entropy);
+        let hash = sha256(&mnemonic.entropy);
+        let expected = (0..checksum).all(|i| bit(&hash, i) == bit(&data, entropy_len * 8 + i));
+        if !expected {
+            return Err(ErrorKind::InvalidChecksum.into());
+        }
+        Ok(mnemonic)
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    pub fn words(&self) -> &[&'static str] {
+        &self.words
+    }
+
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    pub fn phrase(&self) -> String {
+        self.words.join(" ")
+    }
+
+    /// The 64 byte seed for `ExtendedPrivateKey::from_seed`, derived with
+    /// PBKDF2-HMAC-SHA512 and "mnemonic" + `passphrase` as salt.
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        let salt = format!("mnemonic{}", passphrase).nfkd().collect::<String>();
+        let mut seed = [0; 64];
+        pbkdf2::pbkdf2::<Hmac<Sha512>>(
+            self.phrase().as_bytes(),
+            salt.as_bytes(),
+            PBKDF2_ROUNDS,
+            &mut seed,
+        );
+        seed
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt<'a>(&self, f: &mut fmt::Formatter<'a>) -> fmt::Result {
+        write!(f, "{}", self.phrase())
+    }
+}
+
+impl FromStr for Mnemonic {
+    type Err = Error;
+
+    /// Parses an English phrase.
+    fn from_str(phrase: &str) -> Result<Mnemonic> {
+        Mnemonic::from_phrase(phrase, Language::English)
+    }
+}
+
+#[test]
+fn test_bip39() {
+    use crate::bip32::ExtendedPrivateKey;
+    use crate::network::Network;
+    use hex_literal::hex;
+    // (entropy, phrase, seed with passphrase "TREZOR") from the Trezor reference vectors
+    let vectors: &[(&[u8], &str, &[u8])] = &[
+        (
+            &hex!("00000000000000000000000000000000"),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            &hex!("c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"),
+        ),
+        (
+            &hex!("7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f"),
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            &hex!("2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607"),
+        ),
+        (
+            &hex!("80808080808080808080808080808080"),
+            "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+            &hex!("d71de856f81a8acc65e6fc851a38d4d7ec216fd0796d0a6827a3ad6ed5511a30fa280f12eb2e47ed2ac03b5c462a0358d18d69fe4f985ec81778c1b370b652a8"),
+        ),
+        (
+            &hex!("ffffffffffffffffffffffffffffffff"),
+            "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+            &hex!("ac27495480225222079d7be181583751e86f571027b0497b5b5d11218e0a8a13332572917f0f8e5a589620c6f15b11c61dee327651a14c34e18231052e48c069"),
+        ),
+        (
+            &hex!("000000000000000000000000000000000000000000000000"),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon agent",
+            &hex!("035895f2f481b1b0f01fcf8c289c794660b289981a78f8106447707fdd9666ca06da5a9a565181599b79f53b844d8a71dd9f439c52a3d7b3e8a79c906ac845fa"),
+        ),
+        (
+            &hex!("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"),
+            "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+            &hex!("dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad"),
+        ),
+        (
+            &hex!("9e885d952ad362caeb4efe34a8e91bd2"),
+            "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+            &hex!("274ddc525802f7c828d8ef7ddbcdc5304e87ac3535913611fbbfa986d0c9e5476c91689f9c8a54fd55bd38606aa6a8595ad213d4c9c9f9aca3fb217069a41028"),
+        ),
+        (
+            &hex!("68a79eaca2324873eacc50cb9c6eca8cc68ea5d936f98787c60c7ebc74e6ce7c"),
+            "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+            &hex!("64c87cde7e12ecf6704ab95bb1408bef047c22db4cc7491c4271d170a1b213d20b385bc1588d9c7b38f1b39d415665b8a9030c9ec653d75e65f847d8fc1fc440"),
+        ),
+    ];
+    for (entropy, phrase, seed) in vectors {
+        let mnemonic = Mnemonic::from_entropy(entropy, Language::English).unwrap();
+        assert_eq!(mnemonic.phrase(), *phrase);
+        assert_eq!(phrase.parse::<Mnemonic>().unwrap(), mnemonic);
+        assert_eq!(mnemonic.to_seed("TREZOR")[..], seed[..]);
+    }
+
+    let mnemonic = Mnemonic::generate(24, Language::English).unwrap();
+    assert_eq!(mnemonic.entropy().len(), 32);
+    assert_eq!(mnemonic.to_string().parse::<Mnemonic>().unwrap(), mnemonic);
+    let seed = mnemonic.to_seed("");
+    assert!(ExtendedPrivateKey::from_seed(&seed, Network::Mainnet).is_ok());
+
+    // last word changed from "about", which breaks the checksum
+    let bad_checksum = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon";
+    assert!(bad_checksum.parse::<Mnemonic>().is_err());
+    assert!("abandon abandon abandon about".parse::<Mnemonic>().is_err());
+    assert!("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon bitcoin"
+        .parse::<Mnemonic>()
+        .is_err());
+    assert!(Mnemonic::from_entropy(&[0; 15], Language::English).is_err());
+    assert!(Mnemonic::generate(13, Language::English).is_err());
+}
@@ -0,0 +1,109 @@
+use crate::errors::{Error, ErrorKind, Result};
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+const CHECKSUM_LEN: usize = 8;
+
+/// Hash sizes in bytes, indexed by the size bits of the version byte.
+const HASH_SIZES: [usize; 8] = [20, 24, 28, 32, 40, 48, 56, 64];
+
+fn polymod(values: impl Iterator<Item = u8>) -> u64 {
+    const GENERATORS: [u64; 5] = [
+        0x98_f2bc_8e61,
+        0x79_b76d_99e2,
+        0xf3_3e5f_b3c4,
+        0xae_2eab_e2a8,
+        0x1e_4f43_e470,
+    ];
+    let mut checksum = 1u64;
+    for value in values {
+        let top = checksum >> 35;
+        checksum = ((checksum & 0x07_ffff_ffff) << 5) ^ u64::from(value);
+        for (i, generator) in GENERATORS.iter().enumerate() {
+            if (top >> i) & 1 != 0 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum ^ 1
+}
+
+fn prefix_values(prefix: &str) -> impl Iterator<Item = u8> + '_ {
+    prefix.bytes().map(|b| b & 0x1f).chain(std::iter::once(0))
+}
+
+/// Regroups `data` from `from` bit to `to` bit words. Without padding, the
+/// leftover bits have to be zero.
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut result = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    let max_value = (1 << to) - 1;
+    for value in data {
+        acc = (acc << from) | u32::from(*value);
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((acc >> bits) & max_value) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            result.push(((acc << (to - bits)) & max_value) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max_value != 0 {
+        return None;
+    }
+    Some(result)
+}
+
+/// Encodes `hash` with the given version byte as `prefix:payload`.
+pub(crate) fn encode(prefix: &str, version: u8, hash: &[u8]) -> String {
+    let mut payload = vec![version];
+    payload.extend_from_slice(hash);
+    let mut values = convert_bits(&payload, 8, 5, true).expect("Padding never fails");
+    let checksum = polymod(
+        prefix_values(prefix)
+            .chain(values.iter().cloned())
+            .chain([0; CHECKSUM_LEN].iter().cloned()),
+    );
+    values.extend((0..CHECKSUM_LEN).map(|i| ((checksum >> (5 * (7 - i))) & 0x1f) as u8));
+    let mut encoded = String::with_capacity(prefix.len() + 1 + values.len());
+    encoded.push_str(prefix);
+    encoded.push(':');
+    encoded.extend(values.iter().map(|value| CHARSET[*value as usize] as char));
+    encoded
+}
+
+/// Decodes `prefix:payload` into the lowercase prefix, version byte and hash.
+pub(crate) fn decode(address: &str) -> Result<(String, u8, Vec<u8>)> {
+    let invalid = || Error::from(ErrorKind::InvalidAddress(address.to_string()));
+    let has_lower = address.bytes().any(|b| b.is_ascii_lowercase());
+    let has_upper = address.bytes().any(|b| b.is_ascii_uppercase());
+    if has_lower && has_upper {
+        return Err(invalid());
+    }
+    let lowercase = address.to_ascii_lowercase();
+    let mut parts = lowercase.splitn(2, ':');
+    let prefix = parts.next().ok_or_else(invalid)?;
+    let payload = parts.next().ok_or_else(invalid)?;
+    if prefix.is_empty() || payload.len() <= CHECKSUM_LEN {
+        return Err(invalid());
+    }
+    let values = payload
+        .bytes()
+        .map(|c| CHARSET.iter().position(|x| *x == c).map(|v| v as u8))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)?;
+    if polymod(prefix_values(prefix).chain(values.iter().cloned())) != 0 {
+        return Err(ErrorKind::InvalidChecksum.into());
+    }
+    let data = &values[..values.len() - CHECKSUM_LEN];
+    let payload = convert_bits(data, 5, 8, false).ok_or_else(invalid)?;
+    let version = payload[0];
+    let hash = payload[1..].to_vec();
+    if version & 0x80 != 0 || HASH_SIZES[(version & 0x07) as usize] != hash.len() {
+        return Err(invalid());
+    }
+    Ok((prefix.to_string(), version, hash))
+}
@@ -0,0 +1,143 @@
+use crate::errors::{ErrorKind, Result};
+use crate::rfc6979::NonceGenerator;
+use secp256k1::curve::{Scalar, ECMULT_GEN_CONTEXT};
+use secp256k1::{SecretKey, Signature};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EcdsaSignature {
+    sig: Signature,
+}
+
+impl EcdsaSignature {
+    /// Parses a strictly DER encoded signature, without sighash type.
+    pub fn from_der(der: &[u8]) -> Result<EcdsaSignature> {
+        let sig = Signature::parse_der(der).map_err(|_| ErrorKind::InvalidSignature)?;
+        Ok(EcdsaSignature { sig })
+    }
+
+    /// Parses a DER signature the way the original client's OpenSSL did,
+    /// accepting e.g. superfluous padding. Only for consensus validation.
+    pub fn from_der_lax(der: &[u8]) -> Result<EcdsaSignature> {
+        let sig = Signature::parse_der_lax(der).map_err(|_| ErrorKind::InvalidSignature)?;
+        Ok(EcdsaSignature { sig })
+    }
+
+    /// Parses the 64 byte concatenation of r and s, which have to be in
+    /// the range 1 to n - 1.
+    pub fn from_compact(compact: &[u8; 64]) -> Result<EcdsaSignature> {
+        let mut r = Scalar::default();
+        let mut s = Scalar::default();
+        let mut r_bytes = [0; 32];
+        let mut s_bytes = [0; 32];
+        r_bytes.copy_from_slice(&compact[..32]);
+        s_bytes.copy_from_slice(&compact[32..]);
+        let r_overflow = bool::from(r.set_b32(&r_bytes));
+        let s_overflow = bool::from(s.set_b32(&s_bytes));
+        if r_overflow || s_overflow || r.is_zero() || s.is_zero() {
+            return Err(ErrorKind::InvalidSignature.into());
+        }
+        Ok(EcdsaSignature {
+            sig: Signature { r, s },
+        })
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        self.sig.serialize_der().as_ref().to_vec()
+    }
+
+    pub fn to_compact(&self) -> [u8; 64] {
+        self.sig.serialize()
+    }
+
+    pub fn is_low_s(&self) -> bool {
+        !self.sig.s.is_high()
+    }
+
+    /// Replaces S with n - S if S is above n / 2, which is equally valid.
+    pub fn normalize_s(&mut self) {
+        self.sig.normalize_s();
+    }
+
+    pub(crate) fn inner(&self) -> &Signature {
+        &self.sig
+    }
+}
+
+/// Signs with an RFC6979 nonce; the signature always has a low S.
+pub(crate) fn sign_ecdsa(secret_key: &SecretKey, msg_hash: &[u8; 32]) -> EcdsaSignature {
+    sign_ecdsa_recoverable(secret_key, msg_hash).0
+}
+
+/// Like `sign_ecdsa`, but also returns the recovery id of the signature.
+pub(crate) fn sign_ecdsa_recoverable(
+    secret_key: &SecretKey,
+    msg_hash: &[u8; 32],
+) -> (EcdsaSignature, u8) {
+    let secret_scalar: Scalar = secret_key.clone().into();
+    let mut msg_scalar = Scalar::default();
+    let _ = msg_scalar.set_b32(msg_hash);
+    let mut nonces = NonceGenerator::new(&secret_key.serialize(), msg_hash, &[]);
+    loop {
+        let nonce = nonces.next_nonce();
+        if let Ok((r, s, recovery_id)) =
+            ECMULT_GEN_CONTEXT.sign_raw(&secret_scalar, &msg_scalar, &nonce)
+        {
+            let sig = EcdsaSignature {
+                sig: Signature { r, s },
+            };
+            return (sig, recovery_id);
+        }
+    }
+}
+
+#[test]
+fn test_ecdsa() {
+    use crate::PrivateKey;
+    use hex_literal::hex;
+    // (private key, sha256 of message, signature); the first two sign
+    // "Satoshi Nakamoto" with the keys 1 and n - 1.
+    let vectors = [
+        (
+            hex!("0000000000000000000000000000000000000000000000000000000000000001"),
+            hex!("a0dc65ffca799873cbea0ac274015b9526505daaaed385155425f7337704883e"),
+            &hex!("3045022100934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d802202442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e5")[..],
+        ),
+        (
+            hex!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140"),
+            hex!("a0dc65ffca799873cbea0ac274015b9526505daaaed385155425f7337704883e"),
+            &hex!("3045022100fd567d121db66e382991534ada77a6bd3106f0a1098c231e47993447cd6af2d002206b39cd0eb1bc8603e159ef5c20a5c8ad685a45b06ce9bebed3f153d10d93bed5")[..],
+        ),
+        (
+            hex!("b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef"),
+            hex!("8a9851255d671c4e0ac3ad525ad0ff595cb31a1ad85327a77df3d15129b0a245"),
+            &hex!("3045022100ca44d44f4a40f521a7bf3bc47ce36aa054cd5c4701764830c5d75c0d818b51b302204210fcbd9718f08ef95f11deb26cc9e5ee5ae19dbb35dbec97b444bed0c95da3")[..],
+        ),
+    ];
+    let other_key = PrivateKey::from_slice(&[1; 32]).unwrap().public_key();
+    for (secret, msg_hash, expected_der) in vectors.iter() {
+        let private_key = PrivateKey::from_slice(secret).unwrap();
+        let sig = private_key.sign_ecdsa(msg_hash);
+        assert_eq!(&sig.to_der()[..], *expected_der);
+        assert!(sig.is_low_s());
+        let public_key = private_key.public_key();
+        assert!(public_key.verify_ecdsa(msg_hash, &sig));
+        assert!(public_key.uncompressed().verify(msg_hash, &sig.to_der()));
+        assert!(!public_key.verify_ecdsa(&[0; 32], &sig));
+        assert!(!other_key.verify_ecdsa(msg_hash, &sig));
+        let mut high_s = EcdsaSignature {
+            sig: Signature {
+                r: sig.sig.r.clone(),
+                s: -sig.sig.s.clone(),
+            },
+        };
+        assert!(!high_s.is_low_s());
+        assert!(public_key.verify_ecdsa(msg_hash, &high_s));
+        high_s.normalize_s();
+        assert_eq!(high_s, sig);
+        assert_eq!(EcdsaSignature::from_der(&sig.to_der()).unwrap(), sig);
+        assert_eq!(
+            EcdsaSignature::from_compact(&sig.to_compact()).unwrap(),
+            sig
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use error_chain::error_chain;
+
+error_chain! {
+    errors {
+        InvalidPrivateKey {}
+        InvalidPublicKey {}
+        InvalidSignature {}
+        InvalidBase58 {}
+        InvalidChecksum {}
+        InvalidExtendedKey {}
+        InvalidDerivationPath(path: String) {
+            description("Invalid derivation path")
+            display("Invalid derivation path: {}", path)
+        }
+        HardenedDerivationFromPublicKey {}
+        InvalidEntropyLength(len: usize) {
+            description("Invalid entropy length")
+            display("Invalid entropy length: {} bytes", len)
+        }
+        InvalidMnemonicWord(word: String) {
+            description("Invalid mnemonic word")
+            display("Invalid mnemonic word: {}", word)
+        }
+        InvalidMnemonicWordCount(count: usize) {
+            description("Invalid mnemonic word count")
+            display("Invalid mnemonic word count: {}", count)
+        }
+        InvalidAddress(address: String) {
+            description("Invalid address")
+            display("Invalid address: {}", address)
+        }
+        InvalidAddressHash(len: usize) {
+            description("Invalid address hash length")
+            display("Invalid address hash length: {} bytes", len)
+        }
+        NoLegacyEncoding {}
+        NonStandardScript {}
+    }
+}
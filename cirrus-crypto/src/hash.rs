@@ -0,0 +1,28 @@
+use hmac::{Hmac, Mac};
+use ripemd160::Ripemd160;
+use sha2::{Digest, Sha256, Sha512};
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&Sha256::digest(data));
+    hash
+}
+
+pub fn double_sha256(data: &[u8]) -> [u8; 32] {
+    sha256(&sha256(data))
+}
+
+/// ripemd160(sha256(data)), as used for addresses.
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    let mut hash = [0; 20];
+    hash.copy_from_slice(&Ripemd160::digest(&sha256(data)));
+    hash
+}
+
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut mac = Hmac::<Sha512>::new_varkey(key).expect("HMAC accepts any key size");
+    mac.input(data);
+    let mut hash = [0; 64];
+    hash.copy_from_slice(&mac.result().code());
+    hash
+}
@@ -0,0 +1,191 @@
+use crate::ecdsa::{sign_ecdsa, sign_ecdsa_recoverable, EcdsaSignature};
+use crate::errors::{ErrorKind, Result};
+use crate::schnorr::{sign_schnorr, verify_schnorr, SchnorrSignature};
+use secp256k1::{Message, RecoveryId, SecretKey};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PrivateKey {
+    key: SecretKey,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PublicKey {
+    key: secp256k1::PublicKey,
+    is_compressed: bool,
+}
+
+impl PrivateKey {
+    /// Fails if `bytes` isn't a 32 byte number in [1, n-1].
+    pub fn from_slice(bytes: &[u8]) -> Result<PrivateKey> {
+        let key = SecretKey::parse_slice(bytes).map_err(|_| ErrorKind::InvalidPrivateKey)?;
+        Ok(PrivateKey { key })
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.key.serialize()
+    }
+
+    /// The compressed public key of this key.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            key: secp256k1::PublicKey::from_secret_key(&self.key),
+            is_compressed: true,
+        }
+    }
+
+    pub fn sign_ecdsa(&self, msg_hash: &[u8; 32]) -> EcdsaSignature {
+        sign_ecdsa(&self.key, msg_hash)
+    }
+
+    /// Signs `msg_hash` and returns the signature with its recovery id.
+    pub fn sign_ecdsa_recoverable(&self, msg_hash: &[u8; 32]) -> (EcdsaSignature, u8) {
+        sign_ecdsa_recoverable(&self.key, msg_hash)
+    }
+
+    pub fn sign_schnorr(&self, msg_hash: &[u8; 32]) -> SchnorrSignature {
+        sign_schnorr(&self.key, &self.public_key(), msg_hash)
+    }
+
+    /// Adds `tweak` to the key modulo n, as used by BIP32.
+    pub(crate) fn tweak_add(&self, tweak: &[u8; 32]) -> Result<PrivateKey> {
+        let tweak = SecretKey::parse(tweak).map_err(|_| ErrorKind::InvalidPrivateKey)?;
+        let mut key = self.key.clone();
+        key.tweak_add_assign(&tweak)
+            .map_err(|_| ErrorKind::InvalidPrivateKey)?;
+        Ok(PrivateKey { key })
+    }
+}
+
+impl PublicKey {
+    /// Parses a 33 byte compressed or 65 byte uncompressed public key.
+    pub fn from_slice(bytes: &[u8]) -> Result<PublicKey> {
+        let is_compressed = match (bytes.first(), bytes.len()) {
+            (Some(0x02), 33) | (Some(0x03), 33) => true,
+            (Some(0x04), 65) => false,
+            _ => return Err(ErrorKind::InvalidPublicKey.into()),
+        };
+        let key = secp256k1::PublicKey::parse_slice(bytes, None)
+            .map_err(|_| ErrorKind::InvalidPublicKey)?;
+        Ok(PublicKey { key, is_compressed })
+    }
+
+    /// Recovers the (compressed) key that made `sig` over `msg_hash`.
+    pub fn recover_ecdsa(
+        msg_hash: &[u8; 32],
+        sig: &EcdsaSignature,
+        recovery_id: u8,
+    ) -> Result<PublicKey> {
+        let recovery_id =
+            RecoveryId::parse(recovery_id).map_err(|_| ErrorKind::InvalidSignature)?;
+        let key = secp256k1::recover(&Message::parse(msg_hash), sig.inner(), &recovery_id)
+            .map_err(|_| ErrorKind::InvalidSignature)?;
+        Ok(PublicKey {
+            key,
+            is_compressed: true,
+        })
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.is_compressed
+    }
+
+    pub fn compressed(&self) -> PublicKey {
+        PublicKey {
+            key: self.key.clone(),
+            is_compressed: true,
+        }
+    }
+
+    pub fn uncompressed(&self) -> PublicKey {
+        PublicKey {
+            key: self.key.clone(),
+            is_compressed: false,
+        }
+    }
+
+    /// Serializes the key in the format it was created with.
+    pub fn serialize(&self) -> Vec<u8> {
+        if self.is_compressed {
+            self.serialize_compressed().to_vec()
+        } else {
+            self.serialize_uncompressed().to_vec()
+        }
+    }
+
+    pub fn serialize_compressed(&self) -> [u8; 33] {
+        self.key.serialize_compressed()
+    }
+
+    pub fn serialize_uncompressed(&self) -> [u8; 65] {
+        self.key.serialize()
+    }
+
+    /// Verifies an ECDSA signature. High S values are accepted, as
+    /// required by consensus; policy has to check them separately.
+    pub fn verify_ecdsa(&self, msg_hash: &[u8; 32], sig: &EcdsaSignature) -> bool {
+        let mut sig = sig.inner().clone();
+        sig.normalize_s();
+        secp256k1::verify(&Message::parse(msg_hash), &sig, &self.key)
+    }
+
+    pub fn verify_schnorr(&self, msg_hash: &[u8; 32], sig: &SchnorrSignature) -> bool {
+        verify_schnorr(self, msg_hash, sig)
+    }
+
+    /// Verifies a 64 byte Schnorr signature or a (lax) DER encoded ECDSA
+    /// signature, the same way OP_CHECKSIG and OP_CHECKDATASIG do.
+    pub fn verify(&self, msg_hash: &[u8; 32], sig: &[u8]) -> bool {
+        if sig.len() == 64 {
+            let mut sig_bytes = [0; 64];
+            sig_bytes.copy_from_slice(sig);
+            return self.verify_schnorr(msg_hash, &SchnorrSignature::from_bytes(sig_bytes));
+        }
+        match EcdsaSignature::from_der_lax(sig) {
+            Ok(sig) => self.verify_ecdsa(msg_hash, &sig),
+            Err(_) => false,
+        }
+    }
+
+    /// Adds `tweak` * G to the key, as used by BIP32.
+    pub(crate) fn tweak_add(&self, tweak: &[u8; 32]) -> Result<PublicKey> {
+        let tweak = SecretKey::parse(tweak).map_err(|_| ErrorKind::InvalidPublicKey)?;
+        let mut key = self.key.clone();
+        key.tweak_add_assign(&tweak)
+            .map_err(|_| ErrorKind::InvalidPublicKey)?;
+        Ok(PublicKey {
+            key,
+            is_compressed: self.is_compressed,
+        })
+    }
+
+    pub(crate) fn inner(&self) -> &secp256k1::PublicKey {
+        &self.key
+    }
+}
+
+#[test]
+fn test_keys() {
+    use hex_literal::hex;
+    let private_key = PrivateKey::from_slice(&hex!(
+        "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef"
+    ))
+    .unwrap();
+    let public_key = private_key.public_key();
+    assert_eq!(
+        public_key.serialize(),
+        hex!("02dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659").to_vec(),
+    );
+    let uncompressed = public_key.uncompressed().serialize();
+    assert_eq!(uncompressed.len(), 65);
+    assert_eq!(
+        PublicKey::from_slice(&uncompressed).unwrap(),
+        public_key.uncompressed()
+    );
+    assert!(PrivateKey::from_slice(&[0; 32]).is_err());
+    assert!(PrivateKey::from_slice(&[0xff; 32]).is_err());
+    assert!(PublicKey::from_slice(&uncompressed[..33]).is_err());
+    // x = 0 is not on the curve
+    let mut not_on_curve = [0; 33];
+    not_on_curve[0] = 0x02;
+    assert!(PublicKey::from_slice(&not_on_curve).is_err());
+}
@@ -1,7 +1,26 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
-}
+mod address;
+mod base58;
+mod bip32;
+mod bip39;
+mod cashaddr;
+mod ecdsa;
+pub mod errors;
+mod hash;
+mod keys;
+mod message;
+mod multiset;
+mod network;
+mod rfc6979;
+mod schnorr;
+
+pub use address::*;
+pub use base58::*;
+pub use bip32::*;
+pub use bip39::*;
+pub use ecdsa::*;
+pub use hash::*;
+pub use keys::*;
+pub use message::*;
+pub use multiset::*;
+pub use network::*;
+pub use schnorr::*;
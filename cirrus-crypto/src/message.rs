@@ -0,0 +1,213 @@
+use crate::address::{Address, AddressType};
+use crate::ecdsa::EcdsaSignature;
+use crate::errors::{ErrorKind, Result};
+use crate::hash::double_sha256;
+use crate::keys::{PrivateKey, PublicKey};
+
+const MESSAGE_MAGIC: &[u8] = b"Bitcoin Signed Message:\n";
+
+/// Header byte of a compact signature: 27 + recovery id, plus 4 if the
+/// signing key is compressed.
+const COMPACT_HEADER_BASE: u8 = 27;
+const COMPACT_HEADER_COMPRESSED: u8 = 4;
+
+/// A 65 byte compact ECDSA signature from which the public key can be
+/// recovered, as produced by signmessage.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecoverableSignature {
+    sig: EcdsaSignature,
+    recovery_id: u8,
+    is_compressed: bool,
+}
+
+fn write_compact_size(data: &mut Vec<u8>, size: usize) {
+    match size {
+        0..=0xfc => data.push(size as u8),
+        0xfd..=0xffff => {
+            data.push(0xfd);
+            data.extend_from_slice(&(size as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            data.push(0xfe);
+            data.extend_from_slice(&(size as u32).to_le_bytes());
+        }
+        _ => {
+            data.push(0xff);
+            data.extend_from_slice(&(size as u64).to_le_bytes());
+        }
+    }
+}
+
+/// The hash signed by signmessage: the magic prefix and the message, both
+/// prefixed with their length, hashed with double SHA256.
+pub fn signed_message_hash(message: &[u8]) -> [u8; 32] {
+    let mut data = Vec::with_capacity(MESSAGE_MAGIC.len() + message.len() + 10);
+    write_compact_size(&mut data, MESSAGE_MAGIC.len());
+    data.extend_from_slice(MESSAGE_MAGIC);
+    write_compact_size(&mut data, message.len());
+    data.extend_from_slice(message);
+    double_sha256(&data)
+}
+
+impl RecoverableSignature {
+    pub fn from_compact(compact: &[u8]) -> Result<RecoverableSignature> {
+        if compact.len() != 65 {
+            return Err(ErrorKind::InvalidSignature.into());
+        }
+        let header = compact[0]
+            .checked_sub(COMPACT_HEADER_BASE)
+            .filter(|header| *header < 8)
+            .ok_or(ErrorKind::InvalidSignature)?;
+        let mut sig = [0; 64];
+        sig.copy_from_slice(&compact[1..]);
+        Ok(RecoverableSignature {
+            sig: EcdsaSignature::from_compact(&sig)?,
+            recovery_id: header & 3,
+            is_compressed: header & COMPACT_HEADER_COMPRESSED != 0,
+        })
+    }
+
+    pub fn to_compact(&self) -> [u8; 65] {
+        let mut compact = [0; 65];
+        compact[0] = COMPACT_HEADER_BASE + self.recovery_id;
+        if self.is_compressed {
+            compact[0] += COMPACT_HEADER_COMPRESSED;
+        }
+        compact[1..].copy_from_slice(&self.sig.to_compact());
+        compact
+    }
+
+    /// Parses the base64 encoding used by wallets.
+    pub fn from_base64(encoded: &str) -> Result<RecoverableSignature> {
+        let compact = base64::decode(encoded).map_err(|_| ErrorKind::InvalidSignature)?;
+        RecoverableSignature::from_compact(&compact)
+    }
+
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.to_compact()[..])
+    }
+
+    pub fn signature(&self) -> &EcdsaSignature {
+        &self.sig
+    }
+
+    pub fn recovery_id(&self) -> u8 {
+        self.recovery_id
+    }
+
+    /// Recovers the signing key, compressed or not as flagged in the header.
+    pub fn recover(&self, msg_hash: &[u8; 32]) -> Result<PublicKey> {
+        let public_key = PublicKey::recover_ecdsa(msg_hash, &self.sig, self.recovery_id)?;
+        if self.is_compressed {
+            Ok(public_key)
+        } else {
+            Ok(public_key.uncompressed())
+        }
+    }
+}
+
+/// Signs `message` like signmessage; keys are always flagged as compressed.
+pub fn sign_message(private_key: &PrivateKey, message: &[u8]) -> RecoverableSignature {
+    let (sig, recovery_id) = private_key.sign_ecdsa_recoverable(&signed_message_hash(message));
+    RecoverableSignature {
+        sig,
+        recovery_id,
+        is_compressed: true,
+    }
+}
+
+/// Recovers the public key that signed `message`.
+pub fn recover_message_signer(message: &[u8], sig: &RecoverableSignature) -> Result<PublicKey> {
+    sig.recover(&signed_message_hash(message))
+}
+
+/// Checks that `sig` over `message` was made by the key of the P2PKH `address`.
+/// The address may use any prefix of its network, e.g. simpleledger.
+pub fn verify_message(address: &Address, message: &[u8], sig: &RecoverableSignature) -> bool {
+    if address.addr_type() != AddressType::P2PKH {
+        return false;
+    }
+    match recover_message_signer(message, sig) {
+        Ok(public_key) => Address::p2pkh(address.network(), &public_key).hash() == address.hash(),
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn test_sign_message() {
+    use hex_literal::hex;
+    let private_key = PrivateKey::from_slice(&hex!(
+        "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef"
+    ))
+    .unwrap();
+    let message = b"This is an example of a signed message.";
+    assert_eq!(
+        signed_message_hash(message),
+        hex!("d0e5595ac689a1df9f0b13443e0efd876eeb762d50a05f7179b1506bfccfeec5"),
+    );
+    let sig = sign_message(&private_key, message);
+    assert_eq!(
+        sig.to_base64(),
+        "HxWAjUbwJmnMmoQwgb6pW/6Rt+MUZ2cl7S9+hPjYwafzfqEfEKffd0R7QLHiYbzGRdkpRCpxZGZ8s1nkuxePnyw=",
+    );
+    assert_eq!(
+        RecoverableSignature::from_base64(&sig.to_base64()).unwrap(),
+        sig
+    );
+    assert_eq!(
+        recover_message_signer(message, &sig).unwrap(),
+        private_key.public_key()
+    );
+
+    let address: Address = "bitcoincash:qppmqfnaajtr7dznljfrjtswcafa449mssq85kchrj"
+        .parse()
+        .unwrap();
+    let slp_address: Address = "simpleledger:qppmqfnaajtr7dznljfrjtswcafa449mssvulddhav"
+        .parse()
+        .unwrap();
+    assert!(verify_message(&address, message, &sig));
+    assert!(verify_message(&slp_address, message, &sig));
+    assert!(!verify_message(&address, b"This is another message.", &sig));
+
+    let challenge = b"slpdex challenge 1234";
+    let challenge_sig = RecoverableSignature::from_base64(
+        "IISLHSmhKitgl3gAKfQ4NTIojTQn+wOoA/znjF7pPysYTibfezEm1A22xt1olxcmjY/Uu1LlzG9iR+fVgNp6VXU=",
+    )
+    .unwrap();
+    assert_eq!(sign_message(&private_key, challenge), challenge_sig);
+    assert!(verify_message(&address, challenge, &challenge_sig));
+
+    // same signature, flagged as made by the uncompressed key
+    let uncompressed_sig = RecoverableSignature::from_base64(
+        "GxWAjUbwJmnMmoQwgb6pW/6Rt+MUZ2cl7S9+hPjYwafzfqEfEKffd0R7QLHiYbzGRdkpRCpxZGZ8s1nkuxePnyw=",
+    )
+    .unwrap();
+    let uncompressed_address: Address = "bitcoincash:qrkf52vtkjzvvthdx8udelfkdaxn8unswyv04nh6dh"
+        .parse()
+        .unwrap();
+    assert!(verify_message(
+        &uncompressed_address,
+        message,
+        &uncompressed_sig
+    ));
+    assert!(!verify_message(&address, message, &uncompressed_sig));
+
+    assert!(RecoverableSignature::from_compact(&[0; 65]).is_err());
+    // r or s out of range, i.e. zero or at least the curve order n
+    let order = hex!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141");
+    let compact = sig.to_compact();
+    for (range, value) in &[
+        (1..33, [0; 32]),
+        (33..65, [0; 32]),
+        (1..33, order),
+        (33..65, order),
+    ] {
+        let mut invalid = compact;
+        invalid[range.clone()].copy_from_slice(value);
+        assert!(RecoverableSignature::from_compact(&invalid).is_err());
+    }
+    let mut invalid = compact;
+    invalid[33..65].copy_from_slice(&[0xff; 32]);
+    assert!(RecoverableSignature::from_compact(&invalid).is_err());
+    assert!(RecoverableSignature::from_base64("not base64").is_err());
+}
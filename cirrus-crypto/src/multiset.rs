@@ -0,0 +1,174 @@
+use crate::hash::sha256;
+use secp256k1::curve::{Affine, Field, Jacobian};
+
+/// Elliptic curve multiset hash (ECMH) over secp256k1, as used for UTXO
+/// set commitments. Elements are mapped to curve points which are summed
+/// up, so the hash doesn't depend on the order of insertion.
+#[derive(Clone, Debug)]
+pub struct EcMultiset {
+    sum: Jacobian,
+}
+
+/// Maps `data` to a curve point with try-and-increment: the first
+/// sha256(counter || sha256(data)) that is a valid x coordinate, with even y.
+fn data_to_point(data: &[u8]) -> Affine {
+    let mut buffer = [0; 8 + 32];
+    buffer[8..].copy_from_slice(&sha256(data));
+    for counter in 0u64.. {
+        buffer[..8].copy_from_slice(&counter.to_le_bytes());
+        let mut x = Field::default();
+        if !x.set_b32(&sha256(&buffer)) {
+            continue;
+        }
+        let mut point = Affine::default();
+        if point.set_xo_var(&x, false) {
+            return point;
+        }
+    }
+    unreachable!()
+}
+
+impl EcMultiset {
+    pub fn new() -> Self {
+        let mut sum = Jacobian::default();
+        sum.set_infinity();
+        EcMultiset { sum }
+    }
+
+    /// Restores a multiset from `to_bytes`. None if the bytes aren't a
+    /// point on the curve.
+    pub fn from_bytes(bytes: &[u8; 64]) -> Option<Self> {
+        let mut multiset = EcMultiset::new();
+        if bytes[..] == [0; 64][..] {
+            return Some(multiset);
+        }
+        let mut coordinate = [0; 32];
+        let mut x = Field::default();
+        coordinate.copy_from_slice(&bytes[..32]);
+        if !x.set_b32(&coordinate) {
+            return None;
+        }
+        let mut y = Field::default();
+        coordinate.copy_from_slice(&bytes[32..]);
+        if !y.set_b32(&coordinate) {
+            return None;
+        }
+        let mut point = Affine::default();
+        point.set_xy(&x, &y);
+        if !point.is_valid_var() {
+            return None;
+        }
+        multiset.sum.set_ge(&point);
+        Some(multiset)
+    }
+
+    pub fn insert(&mut self, data: &[u8]) {
+        self.sum = self.sum.add_ge_var(&data_to_point(data), None);
+    }
+
+    /// Removes `data`, which is only meaningful if it has been inserted.
+    pub fn remove(&mut self, data: &[u8]) {
+        self.sum = self.sum.add_ge_var(&data_to_point(data).neg(), None);
+    }
+
+    /// Inserts all elements of `other`.
+    pub fn combine(&mut self, other: &EcMultiset) {
+        self.sum = self.sum.add_var(&other.sum, None);
+    }
+
+    /// Removes all elements of `other`.
+    pub fn subtract(&mut self, other: &EcMultiset) {
+        self.sum = self.sum.add_var(&other.sum.neg(), None);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sum.is_infinity()
+    }
+
+    /// The x and y coordinates of the sum, all zeros if empty.
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut coordinates = [0; 64];
+        if self.sum.is_infinity() {
+            return coordinates;
+        }
+        let mut point = Affine::default();
+        point.set_gej_var(&self.sum);
+        point.x.normalize_var();
+        point.y.normalize_var();
+        coordinates[..32].copy_from_slice(&point.x.b32());
+        coordinates[32..].copy_from_slice(&point.y.b32());
+        coordinates
+    }
+
+    /// sha256 of the x and y coordinates of the sum, all zeros if empty.
+    pub fn hash(&self) -> [u8; 32] {
+        if self.sum.is_infinity() {
+            return [0; 32];
+        }
+        sha256(&self.to_bytes())
+    }
+}
+
+impl Default for EcMultiset {
+    fn default() -> Self {
+        EcMultiset::new()
+    }
+}
+
+#[test]
+fn test_ec_multiset() {
+    use hex_literal::hex;
+    // Test vectors from the ECMH specification
+    let d1 = hex!("982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e00000000010000000100f2052a0100000043410496b538e853519c726a2c91e61ec11600ae1390813a627c66fb8be7947be63c52da7589379515d4e0a604f8141781e62294721166bf621e73a82cbf2342c858eeac");
+    let d2 = hex!("d5fdcc541e25de1c7a5addedf24858b8bb665c9f36ef744ee42c316022c90f9b00000000020000000100f2052a010000004341047211a824f55b505228e4c3d5194c1fcfaa15a456abdf37f9b9d97a4040afc073dee6c89064984f03385237d92167c13e236446b417ab79a0fcae412ae3316b77ac");
+    let d3 = hex!("44f672226090d85db9a9f2fbfe5f0f9609b387af7be5b7fbb7a1767c831c9e9900000000030000000100f2052a0100000043410494b9d3e76c5b1629ecf97fff95d7a4bbdac87cc26099ada28066c6ff1eb9191223cd897194a08d0c2726c5747f1db49e8cf90e75dc3e3550ae9b30086f3cd5aaac");
+    let hash = |elements: &[&[u8]]| {
+        let mut multiset = EcMultiset::new();
+        for element in elements {
+            multiset.insert(element);
+        }
+        multiset.hash()
+    };
+    assert_eq!(hash(&[]), [0; 32]);
+    assert_eq!(
+        hash(&[&d1]),
+        hex!("f883195933a687170c34fa1adec66fe2861889279fb12c03a3fb0ca68ad87893")
+    );
+    assert_eq!(
+        hash(&[&d2]),
+        hex!("ef85d123a15da95d8aff92623ad1e1c9fcda3baa801bd40bc567a83a6fdcf3e2")
+    );
+    assert_eq!(
+        hash(&[&d3]),
+        hex!("cfadf40fc017faff5e04ccc0a2fae0fd616e4226dd7c03b1334a7a610468edff")
+    );
+    assert_eq!(
+        hash(&[&d1, &d2]),
+        hex!("fabafd38d07370982a34547daf5b57b8a4398696d6fd2294788abda07b1faaaf")
+    );
+    assert_eq!(
+        hash(&[&d1, &d2, &d3]),
+        hex!("1cbccda23d7ce8c5a8b008008e1738e6bf9cffb1d5b86a92a4e62b5394a636e2")
+    );
+    assert_eq!(hash(&[&d3, &d1, &d2]), hash(&[&d1, &d2, &d3]));
+
+    let mut multiset = EcMultiset::new();
+    multiset.insert(&d1);
+    multiset.insert(&d2);
+    multiset.insert(&d3);
+    multiset.remove(&d2);
+    assert_eq!(multiset.hash(), hash(&[&d1, &d3]));
+    let restored = EcMultiset::from_bytes(&multiset.to_bytes()).unwrap();
+    assert_eq!(restored.hash(), multiset.hash());
+    let mut other = EcMultiset::new();
+    other.insert(&d2);
+    multiset.combine(&other);
+    assert_eq!(multiset.hash(), hash(&[&d1, &d2, &d3]));
+    multiset.subtract(&other);
+    multiset.remove(&d1);
+    multiset.remove(&d3);
+    assert!(multiset.is_empty());
+    assert_eq!(multiset.hash(), [0; 32]);
+    assert!(EcMultiset::from_bytes(&[0; 64]).unwrap().is_empty());
+    assert!(EcMultiset::from_bytes(&[1; 64]).is_none());
+}
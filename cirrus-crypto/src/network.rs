@@ -0,0 +1,6 @@
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
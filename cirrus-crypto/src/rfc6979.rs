@@ -0,0 +1,55 @@
+use hmac::{Hmac, Mac};
+use secp256k1::curve::Scalar;
+use sha2::Sha256;
+
+/// Deterministic nonces as specified in RFC6979, using HMAC-SHA256 as
+/// HMAC_DRBG. `extra` is appended to key and message, like libsecp256k1 does
+/// with its `algo16` parameter, so different schemes use different nonces.
+pub(crate) struct NonceGenerator {
+    k: [u8; 32],
+    v: [u8; 32],
+    is_first: bool,
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC accepts any key size");
+    for part in parts {
+        mac.input(part);
+    }
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&mac.result().code());
+    hash
+}
+
+impl NonceGenerator {
+    pub fn new(secret_key: &[u8; 32], msg_hash: &[u8; 32], extra: &[u8]) -> Self {
+        let mut k = [0x00; 32];
+        let mut v = [0x01; 32];
+        k = hmac_sha256(&k, &[&v, &[0x00], secret_key, msg_hash, extra]);
+        v = hmac_sha256(&k, &[&v]);
+        k = hmac_sha256(&k, &[&v, &[0x01], secret_key, msg_hash, extra]);
+        v = hmac_sha256(&k, &[&v]);
+        NonceGenerator {
+            k,
+            v,
+            is_first: true,
+        }
+    }
+
+    /// Generates the next candidate nonce in [1, n-1].
+    pub fn next_nonce(&mut self) -> Scalar {
+        loop {
+            if !self.is_first {
+                self.k = hmac_sha256(&self.k, &[&self.v, &[0x00]]);
+                self.v = hmac_sha256(&self.k, &[&self.v]);
+            }
+            self.is_first = false;
+            self.v = hmac_sha256(&self.k, &[&self.v]);
+            let mut nonce = Scalar::default();
+            let overflow = bool::from(nonce.set_b32(&self.v));
+            if !overflow && !nonce.is_zero() {
+                return nonce;
+            }
+        }
+    }
+}
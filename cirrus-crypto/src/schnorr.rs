@@ -0,0 +1,198 @@
+use crate::errors::{ErrorKind, Result};
+use crate::hash::sha256;
+use crate::keys::PublicKey;
+use crate::rfc6979::NonceGenerator;
+use secp256k1::curve::{Affine, Field, Jacobian, Scalar, ECMULT_CONTEXT, ECMULT_GEN_CONTEXT};
+use secp256k1::SecretKey;
+
+/// Distinguishes Schnorr nonces from ECDSA nonces of the same key and message.
+const SCHNORR_NONCE_TAG: &[u8; 16] = b"Schnorr+SHA256  ";
+
+/// A Schnorr signature as specified for Bitcoin Cash in 2019-05-15,
+/// consisting of the x coordinate of R and s.
+#[derive(Clone, Copy)]
+pub struct SchnorrSignature([u8; 64]);
+
+impl SchnorrSignature {
+    pub fn from_bytes(bytes: [u8; 64]) -> SchnorrSignature {
+        SchnorrSignature(bytes)
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<SchnorrSignature> {
+        if bytes.len() != 64 {
+            return Err(ErrorKind::InvalidSignature.into());
+        }
+        let mut sig = [0; 64];
+        sig.copy_from_slice(bytes);
+        Ok(SchnorrSignature(sig))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 64] {
+        self.0
+    }
+}
+
+impl PartialEq for SchnorrSignature {
+    fn eq(&self, other: &SchnorrSignature) -> bool {
+        self.0[..] == other.0[..]
+    }
+}
+
+impl Eq for SchnorrSignature {}
+
+impl std::fmt::Debug for SchnorrSignature {
+    fn fmt<'a>(&self, f: &mut std::fmt::Formatter<'a>) -> std::fmt::Result {
+        write!(f, "SchnorrSignature({:?})", &self.0[..])
+    }
+}
+
+/// e = sha256(R.x || compressed(P) || m) mod n
+fn challenge(r_x: &[u8], public_key: &PublicKey, msg_hash: &[u8; 32]) -> Scalar {
+    let mut preimage = Vec::with_capacity(32 + 33 + 32);
+    preimage.extend_from_slice(r_x);
+    preimage.extend_from_slice(&public_key.serialize_compressed());
+    preimage.extend_from_slice(msg_hash);
+    let mut e = Scalar::default();
+    let _ = e.set_b32(&sha256(&preimage));
+    e
+}
+
+pub(crate) fn sign_schnorr(
+    secret_key: &SecretKey,
+    public_key: &PublicKey,
+    msg_hash: &[u8; 32],
+) -> SchnorrSignature {
+    let secret_scalar: Scalar = secret_key.clone().into();
+    let mut nonces = NonceGenerator::new(&secret_key.serialize(), msg_hash, SCHNORR_NONCE_TAG);
+    let mut k = nonces.next_nonce();
+    let mut r_jacobian = Jacobian::default();
+    ECMULT_GEN_CONTEXT.ecmult_gen(&mut r_jacobian, &k);
+    let mut r = Affine::from_gej(&r_jacobian);
+    r.x.normalize();
+    r.y.normalize();
+    // R must have a quadratic residue as y, otherwise -R (from -k) does.
+    if !r.y.is_quad_var() {
+        k = -k;
+    }
+    let r_x = r.x.b32();
+    let e = challenge(&r_x, public_key, msg_hash);
+    let s = &k + &(&e * &secret_scalar);
+    let mut sig = [0; 64];
+    sig[..32].copy_from_slice(&r_x);
+    sig[32..].copy_from_slice(&s.b32());
+    SchnorrSignature(sig)
+}
+
+pub(crate) fn verify_schnorr(
+    public_key: &PublicKey,
+    msg_hash: &[u8; 32],
+    sig: &SchnorrSignature,
+) -> bool {
+    let mut r_x = Field::default();
+    let mut r_x_bytes = [0; 32];
+    r_x_bytes.copy_from_slice(&sig.0[..32]);
+    if !r_x.set_b32(&r_x_bytes) {
+        return false;
+    }
+    let mut s_bytes = [0; 32];
+    s_bytes.copy_from_slice(&sig.0[32..]);
+    let mut s = Scalar::default();
+    if bool::from(s.set_b32(&s_bytes)) {
+        return false;
+    }
+    let e = challenge(&r_x_bytes, public_key, msg_hash);
+    // R = s*G - e*P
+    let public_point: Affine = public_key.inner().clone().into();
+    let mut r = Jacobian::default();
+    ECMULT_CONTEXT.ecmult(&mut r, &Jacobian::from_ge(&public_point), &-e, &s);
+    !r.is_infinity() && r.has_quad_y_var() && r.eq_x_var(&r_x)
+}
+
+#[test]
+fn test_schnorr() {
+    use crate::PrivateKey;
+    use hex_literal::hex;
+    // (public key, message, signature, is valid); the valid ones are from the
+    // specification, the invalid ones are derived: negated s, r = p and s = n.
+    let verify_vectors = [
+        (
+            hex!("0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"),
+            hex!("0000000000000000000000000000000000000000000000000000000000000000"),
+            hex!("787A848E71043D280C50470E8E1532B2DD5D20EE912A45DBDD2BD1DFBF187EF67031A98831859DC34DFFEEDDA86831842CCD0079E1F92AF177F7F22CC1DCED05"),
+            true,
+        ),
+        (
+            hex!("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            hex!("243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89"),
+            hex!("2A298DACAE57395A15D0795DDBFD1DCB564DA82B0F269BC70A74F8220429BA1D1E51A22CCEC35599B8F266912281F8365FFC2D035A230434A1A64DC59F7013FD"),
+            true,
+        ),
+        (
+            hex!("03FAC2114C2FBB091527EB7C64ECB11F8021CB45E8E7809D3C0938E4B8C0E5F84B"),
+            hex!("5E2D58D8B3BCDF1ABADEC7829054F90DDA9805AAB56C77333024B9D0A508B75C"),
+            hex!("00DA9B08172A9B6F0466A2DEFD817F2D7AB437E0D253CB5395A963866B3574BE00880371D01766935B92D2AB4CD5C8A2A5837EC57FED7660773A05F0DE142380"),
+            true,
+        ),
+        (
+            hex!("03DEFDEA4CDB677750A420FEE807EACF21EB9898AE79B9768766E4FAA04A2D4A34"),
+            hex!("4DF3C3F68FCC83B27E9D42C90431A72499F17875C81A599B566C9889B9696703"),
+            hex!("00000000000000000000003B78CE563F89A0ED9414F5AA28AD0D96D6795F9C6302A8DC32E64E86A333F20EF56EAC9BA30B7246D6D25E22ADB8C6BE1AEB08D49D"),
+            true,
+        ),
+        (
+            hex!("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            hex!("243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89"),
+            hex!("2A298DACAE57395A15D0795DDBFD1DCB564DA82B0F269BC70A74F8220429BA1DE1AE5DD3313CAA66470D996EDD7E07C85AB2AFE355259C071E2C10C730C62D44"),
+            false,
+        ),
+        (
+            hex!("0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"),
+            hex!("0000000000000000000000000000000000000000000000000000000000000000"),
+            hex!("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F7031A98831859DC34DFFEEDDA86831842CCD0079E1F92AF177F7F22CC1DCED05"),
+            false,
+        ),
+        (
+            hex!("0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798"),
+            hex!("0000000000000000000000000000000000000000000000000000000000000000"),
+            hex!("787A848E71043D280C50470E8E1532B2DD5D20EE912A45DBDD2BD1DFBF187EF6FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141"),
+            false,
+        ),
+    ];
+    for (public_key, msg_hash, sig, is_valid) in verify_vectors.iter() {
+        let public_key = PublicKey::from_slice(public_key).unwrap();
+        let sig = SchnorrSignature::from_bytes(*sig);
+        assert_eq!(public_key.verify_schnorr(msg_hash, &sig), *is_valid);
+        assert_eq!(public_key.verify(msg_hash, &sig.to_bytes()), *is_valid);
+    }
+
+    // (private key, sha256 of message, signature) with RFC6979 nonces
+    let sign_vectors = [
+        (
+            hex!("0000000000000000000000000000000000000000000000000000000000000001"),
+            hex!("a0dc65ffca799873cbea0ac274015b9526505daaaed385155425f7337704883e"),
+            hex!("2f78a0720cf85bef9a24aef691fce02002c59381133ee543055d24222e2797cc78531f684122d541bbb4afe536e0b19236ad83e2e97a6277626aa5e0d1428f64"),
+        ),
+        (
+            hex!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140"),
+            hex!("a0dc65ffca799873cbea0ac274015b9526505daaaed385155425f7337704883e"),
+            hex!("bebecd554a4c22ad202d074db855af4c974a55a5b9ef5ecf009eff232c806c63b9b37ef1d984feb2aa500777562d66f2ba468fab0491202ede12e76aa69d194a"),
+        ),
+        (
+            hex!("b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef"),
+            hex!("8a9851255d671c4e0ac3ad525ad0ff595cb31a1ad85327a77df3d15129b0a245"),
+            hex!("3cc5ea1a1a404f161fefb886f73e43553db6372403c0591b31d05ed57f4e5b5f500f8d92b3fc56a112a6835232f7efff0b211082ebba288f2de47e669e4f6296"),
+        ),
+        (
+            hex!("c90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b14e5c7"),
+            hex!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            hex!("02e8250f34954b2b0a79c2b585ef761472bbeed99ce946a12178762158690765e4d1e9078d7437ffa590e05900f1ab2ccf640be5b6e80f91385b93e74707becd"),
+        ),
+    ];
+    for (secret, msg_hash, expected_sig) in sign_vectors.iter() {
+        let private_key = PrivateKey::from_slice(secret).unwrap();
+        let sig = private_key.sign_schnorr(msg_hash);
+        assert_eq!(&sig.to_bytes()[..], &expected_sig[..]);
+        assert!(private_key.public_key().verify_schnorr(msg_hash, &sig));
+        assert!(!private_key.public_key().verify_schnorr(&[0; 32], &sig));
+    }
+}
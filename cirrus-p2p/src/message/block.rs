@@ -0,0 +1,68 @@
+use crate::message::Message;
+use cirrus_consensus::{Block, BlockHeader, PartialMerkleTree};
+use cirrus_peer::{
+    errors::{message::ErrorKind::IoError, Result, ResultExt},
+    MessagePacket,
+};
+use std::io;
+
+#[derive(Clone, Debug)]
+pub struct BlockMessage {
+    pub block: Block,
+}
+
+impl Message for BlockMessage {
+    fn command() -> &'static [u8] {
+        b"block"
+    }
+
+    fn packet(&self) -> MessagePacket {
+        let mut payload = Vec::new();
+        self.block.write_to_stream(&mut payload).unwrap();
+        MessagePacket::from_payload(Self::command(), payload)
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self> {
+        Ok(BlockMessage {
+            block: Block::from_stream(&mut io::Cursor::new(payload)).chain_err(|| IoError)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MerkleBlockMessage {
+    pub header: BlockHeader,
+    pub tree: PartialMerkleTree,
+}
+
+impl MerkleBlockMessage {
+    /// Hashes of the txs that matched the loaded filter, if the partial
+    /// merkle tree is well-formed and commits to the header's merkle root.
+    pub fn matched_tx_hashes(&self) -> Option<Vec<[u8; 32]>> {
+        let (root, matches) = self.tree.extract_matches()?;
+        if root != self.header.merkle_root {
+            return None;
+        }
+        Some(matches)
+    }
+}
+
+impl Message for MerkleBlockMessage {
+    fn command() -> &'static [u8] {
+        b"merkleblock"
+    }
+
+    fn packet(&self) -> MessagePacket {
+        let mut payload = Vec::new();
+        self.header.write_to_stream(&mut payload).unwrap();
+        self.tree.write_to_stream(&mut payload).unwrap();
+        MessagePacket::from_payload(Self::command(), payload)
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self> {
+        let mut cur = io::Cursor::new(payload);
+        let header = BlockHeader::from_stream(&mut cur).chain_err(|| IoError)?;
+        let tree = PartialMerkleTree::from_stream(&mut cur).chain_err(|| IoError)?;
+        Ok(MerkleBlockMessage { header, tree })
+    }
+}
@@ -1,34 +1,65 @@
-use super::inv::InvVector;
+use super::inv::{read_inv_vectors, write_inv_vectors, InvVector, ObjectType};
 
 use crate::message::Message;
-use byteorder::{LittleEndian, WriteBytesExt};
-use cashcontracts::serialize::write_var_int;
 use cirrus_peer::{errors::Result, MessagePacket};
-use std::io::Write;
 
 #[derive(Clone, Debug)]
 pub struct GetDataMessage {
     pub inv_vectors: Vec<InvVector>,
 }
 
+impl GetDataMessage {
+    pub fn contains(&self, type_id: ObjectType, hash: &[u8; 32]) -> bool {
+        self.inv_vectors
+            .iter()
+            .any(|inv_vector| inv_vector.type_id == type_id && &inv_vector.hash == hash)
+    }
+}
+
 impl Message for GetDataMessage {
     fn command() -> &'static [u8] {
         b"getdata"
     }
 
     fn packet(&self) -> MessagePacket {
-        let mut payload = Vec::new();
-        write_var_int(&mut payload, self.inv_vectors.len() as u64).unwrap();
-        for inv_vector in self.inv_vectors.iter() {
-            payload
-                .write_u32::<LittleEndian>(inv_vector.type_id as u32)
-                .unwrap();
-            payload.write_all(&inv_vector.hash).unwrap();
-        }
-        MessagePacket::from_payload(Self::command(), payload)
+        MessagePacket::from_payload(Self::command(), write_inv_vectors(&self.inv_vectors))
     }
 
-    fn from_payload(_payload: &[u8]) -> Result<Self> {
-        unimplemented!()
+    fn from_payload(payload: &[u8]) -> Result<Self> {
+        Ok(GetDataMessage {
+            inv_vectors: read_inv_vectors(payload)?,
+        })
     }
 }
+
+#[test]
+fn test_getdata_message() {
+    let getdata = GetDataMessage {
+        inv_vectors: vec![
+            InvVector {
+                type_id: ObjectType::Tx,
+                hash: [1; 32],
+            },
+            InvVector {
+                type_id: ObjectType::Block,
+                hash: [2; 32],
+            },
+        ],
+    };
+    let packet = getdata.packet();
+    assert_eq!(packet.header().command_name(), GetDataMessage::command());
+    let parsed = GetDataMessage::from_payload(packet.payload()).unwrap();
+    assert_eq!(parsed.inv_vectors.len(), 2);
+    assert!(parsed.contains(ObjectType::Tx, &[1; 32]));
+    assert!(parsed.contains(ObjectType::Block, &[2; 32]));
+    assert!(!parsed.contains(ObjectType::Tx, &[2; 32]));
+
+    // unknown object types are skipped, truncated payloads are errors
+    let mut payload = vec![1, 9, 0, 0, 0];
+    payload.extend_from_slice(&[3; 32]);
+    assert!(GetDataMessage::from_payload(&payload)
+        .unwrap()
+        .inv_vectors
+        .is_empty());
+    assert!(GetDataMessage::from_payload(&payload[..20]).is_err());
+}
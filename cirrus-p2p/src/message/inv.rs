@@ -10,16 +10,26 @@ use std::io::{Cursor, Read, Write};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ObjectType {
-    #[allow(dead_code)]
     Error = 0,
     Tx = 1,
     Block = 2,
-    #[allow(dead_code)]
     FilteredBlock = 3,
-    #[allow(dead_code)]
     CmpctBlock = 4,
 }
 
+impl ObjectType {
+    pub fn from_u32(type_id: u32) -> Option<ObjectType> {
+        match type_id {
+            0 => Some(ObjectType::Error),
+            1 => Some(ObjectType::Tx),
+            2 => Some(ObjectType::Block),
+            3 => Some(ObjectType::FilteredBlock),
+            4 => Some(ObjectType::CmpctBlock),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct InvVector {
     pub type_id: ObjectType,
@@ -31,38 +41,56 @@ pub struct InvMessage {
     pub inv_vectors: Vec<InvVector>,
 }
 
+pub(crate) fn write_inv_vectors(inv_vectors: &[InvVector]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    write_var_int(&mut payload, inv_vectors.len() as u64).unwrap();
+    for inv_vector in inv_vectors.iter() {
+        payload
+            .write_u32::<LittleEndian>(inv_vector.type_id as u32)
+            .unwrap();
+        payload.write_all(&inv_vector.hash).unwrap();
+    }
+    payload
+}
+
+pub(crate) fn read_inv_vectors(payload: &[u8]) -> Result<Vec<InvVector>> {
+    let mut cur = Cursor::new(payload);
+    let n_inv = read_var_int(&mut cur).chain_err(|| IoError)?;
+    let mut inv_vectors = Vec::new();
+    for _ in 0..n_inv {
+        let type_id = cur.read_u32::<LittleEndian>().chain_err(|| IoError)?;
+        let mut hash = [0; 32];
+        cur.read_exact(&mut hash).chain_err(|| IoError)?;
+        let type_id = match ObjectType::from_u32(type_id) {
+            Some(type_id) => type_id,
+            None => continue,
+        };
+        inv_vectors.push(InvVector { type_id, hash });
+    }
+    Ok(inv_vectors)
+}
+
+impl InvMessage {
+    pub fn contains(&self, type_id: ObjectType, hash: &[u8; 32]) -> bool {
+        self.inv_vectors
+            .iter()
+            .any(|inv_vector| inv_vector.type_id == type_id && &inv_vector.hash == hash)
+    }
+}
+
 impl Message for InvMessage {
     fn command() -> &'static [u8] {
         b"inv"
     }
 
     fn packet(&self) -> MessagePacket {
-        let mut payload = Vec::new();
-        write_var_int(&mut payload, self.inv_vectors.len() as u64).unwrap();
-        for inv_vector in self.inv_vectors.iter() {
-            payload
-                .write_u32::<LittleEndian>(inv_vector.type_id as u32)
-                .unwrap();
-            payload.write_all(&inv_vector.hash).unwrap();
-        }
-        MessagePacket::from_payload(Self::command(), payload)
+        MessagePacket::from_payload(Self::command(), write_inv_vectors(&self.inv_vectors))
     }
 
     fn from_payload(payload: &[u8]) -> Result<Self> {
-        let mut cur = Cursor::new(payload);
-        let n_inv = read_var_int(&mut cur).chain_err(|| IoError)?;
-        let mut inv_vectors = Vec::new();
-        for _ in 0..n_inv {
-            let type_id = match cur.read_u32::<LittleEndian>().chain_err(|| IoError)? {
-                1 => ObjectType::Tx,
-                2 => ObjectType::Block,
-                _ => continue,
-            };
-            let mut hash = [0; 32];
-            cur.read_exact(&mut hash).chain_err(|| IoError)?;
-            inv_vectors.push(InvVector { type_id, hash });
-        }
-        Ok(InvMessage { inv_vectors })
+        Ok(InvMessage {
+            inv_vectors: read_inv_vectors(payload)?,
+        })
     }
 }
 
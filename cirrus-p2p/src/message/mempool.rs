@@ -0,0 +1,19 @@
+use crate::message::Message;
+use cirrus_peer::{errors::Result, MessagePacket};
+
+#[derive(Clone, Debug)]
+pub struct MempoolMessage;
+
+impl Message for MempoolMessage {
+    fn command() -> &'static [u8] {
+        b"mempool"
+    }
+
+    fn packet(&self) -> MessagePacket {
+        MessagePacket::from_payload(Self::command(), vec![])
+    }
+
+    fn from_payload(_payload: &[u8]) -> Result<Self> {
+        Ok(MempoolMessage)
+    }
+}
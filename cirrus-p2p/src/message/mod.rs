@@ -1,13 +1,21 @@
+mod block;
 mod filterload;
 mod getdata;
 pub mod inv;
+mod mempool;
 mod message_trait;
 mod ping;
+mod reject;
+mod tx;
 mod version;
 
+pub use block::*;
 pub use filterload::*;
 pub use getdata::*;
 pub use inv::InvMessage;
+pub use mempool::*;
 pub use message_trait::*;
 pub use ping::*;
+pub use reject::*;
+pub use tx::*;
 pub use version::*;
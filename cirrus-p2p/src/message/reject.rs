@@ -0,0 +1,107 @@
+use crate::message::Message;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use cashcontracts::serialize::{read_var_str, write_var_str};
+use cirrus_peer::{
+    errors::{message::ErrorKind::IoError, Result, ResultExt},
+    MessagePacket,
+};
+use std::io::{self, Read, Write};
+
+pub const REJECT_MALFORMED: u8 = 0x01;
+pub const REJECT_INVALID: u8 = 0x10;
+pub const REJECT_OBSOLETE: u8 = 0x11;
+pub const REJECT_DUPLICATE: u8 = 0x12;
+pub const REJECT_NONSTANDARD: u8 = 0x40;
+pub const REJECT_DUST: u8 = 0x41;
+pub const REJECT_INSUFFICIENTFEE: u8 = 0x42;
+pub const REJECT_CHECKPOINT: u8 = 0x43;
+
+#[derive(Clone, Debug)]
+pub struct RejectMessage {
+    pub message: Vec<u8>,
+    pub code: u8,
+    pub reason: Vec<u8>,
+    /// Hash of the rejected tx or block, empty for other messages.
+    pub data: Vec<u8>,
+}
+
+impl RejectMessage {
+    pub fn rejects(&self, command: &[u8], hash: &[u8; 32]) -> bool {
+        self.message == command && self.data[..] == hash[..]
+    }
+}
+
+impl Message for RejectMessage {
+    fn command() -> &'static [u8] {
+        b"reject"
+    }
+
+    fn packet(&self) -> MessagePacket {
+        let mut payload = Vec::new();
+        write_var_str(&mut payload, &self.message).unwrap();
+        payload.write_u8(self.code).unwrap();
+        write_var_str(&mut payload, &self.reason).unwrap();
+        payload.write_all(&self.data).unwrap();
+        MessagePacket::from_payload(Self::command(), payload)
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self> {
+        let mut cur = io::Cursor::new(payload);
+        let message = read_var_str(&mut cur).chain_err(|| IoError)?;
+        let code = cur.read_u8().chain_err(|| IoError)?;
+        let reason = read_var_str(&mut cur).chain_err(|| IoError)?;
+        let mut data = Vec::new();
+        cur.read_to_end(&mut data).chain_err(|| IoError)?;
+        Ok(RejectMessage {
+            message,
+            code,
+            reason,
+            data,
+        })
+    }
+}
+
+impl std::fmt::Display for RejectMessage {
+    fn fmt<'a>(&self, f: &mut std::fmt::Formatter<'a>) -> std::result::Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "rejected {} (code {:#x}): {}",
+            String::from_utf8_lossy(&self.message),
+            self.code,
+            String::from_utf8_lossy(&self.reason),
+        )
+    }
+}
+
+#[test]
+fn test_reject_message() {
+    let reject = RejectMessage {
+        message: b"tx".to_vec(),
+        code: REJECT_DUST,
+        reason: b"dust".to_vec(),
+        data: vec![7; 32],
+    };
+    let packet = reject.packet();
+    assert_eq!(packet.header().command_name(), RejectMessage::command());
+    let parsed = RejectMessage::from_payload(packet.payload()).unwrap();
+    assert_eq!(parsed.message, b"tx");
+    assert_eq!(parsed.code, REJECT_DUST);
+    assert_eq!(parsed.reason, b"dust");
+    assert!(parsed.rejects(b"tx", &[7; 32]));
+    assert!(!parsed.rejects(b"block", &[7; 32]));
+    assert!(!parsed.rejects(b"tx", &[8; 32]));
+    assert_eq!(parsed.to_string(), "rejected tx (code 0x41): dust");
+
+    // rejects of other messages carry no hash
+    let reject = RejectMessage {
+        message: b"version".to_vec(),
+        code: REJECT_OBSOLETE,
+        reason: b"old".to_vec(),
+        data: vec![],
+    };
+    let parsed = RejectMessage::from_payload(reject.packet().payload()).unwrap();
+    assert!(parsed.data.is_empty());
+    assert!(!parsed.rejects(b"version", &[0; 32]));
+
+    assert!(RejectMessage::from_payload(&[2, b't']).is_err());
+}
@@ -0,0 +1,27 @@
+use crate::message::Message;
+use cirrus_consensus::Transaction;
+use cirrus_peer::{
+    errors::{message::ErrorKind::IoError, Result, ResultExt},
+    MessagePacket,
+};
+
+#[derive(Clone, Debug)]
+pub struct TxMessage {
+    pub tx: Transaction,
+}
+
+impl Message for TxMessage {
+    fn command() -> &'static [u8] {
+        b"tx"
+    }
+
+    fn packet(&self) -> MessagePacket {
+        MessagePacket::from_payload(Self::command(), self.tx.serialize())
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self> {
+        Ok(TxMessage {
+            tx: Transaction::from_slice(payload).chain_err(|| IoError)?,
+        })
+    }
+}
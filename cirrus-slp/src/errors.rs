@@ -0,0 +1,20 @@
+use error_chain::error_chain;
+
+error_chain! {
+    errors {
+        NoOpReturn {}
+        NotSlp {}
+        InvalidSlp(reason: String) {
+            description("Invalid SLP message")
+            display("Invalid SLP message: {}", reason)
+        }
+        UnsupportedTokenType(token_type: u16) {
+            description("Unsupported SLP token type")
+            display("Unsupported SLP token type: {}", token_type)
+        }
+        MissingTx(tx_hash: String) {
+            description("Tx missing for SLP validation")
+            display("Tx missing for SLP validation: {}", tx_hash)
+        }
+    }
+}
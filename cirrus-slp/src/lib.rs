@@ -0,0 +1,6 @@
+pub mod errors;
+mod message;
+mod validator;
+
+pub use message::*;
+pub use validator::*;
@@ -0,0 +1,423 @@
+use crate::errors::{ErrorKind::*, Result};
+use byteorder::{BigEndian, ByteOrder};
+use cirrus_consensus::script::opcodes::{OP_PUSHDATA1, OP_PUSHDATA2, OP_PUSHDATA4, OP_RETURN};
+use cirrus_consensus::script::read_op;
+use cirrus_consensus::Transaction;
+use error_chain::bail;
+
+pub const SLP_LOKAD_ID: &[u8] = b"SLP\0";
+
+/// SEND messages can assign tokens to at most 19 outputs.
+pub const MAX_SEND_OUTPUTS: usize = 19;
+
+const MAX_DECIMALS: u8 = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SlpTokenType {
+    Fungible,
+    Nft1Group,
+    Nft1Child,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlpGenesis {
+    pub ticker: Vec<u8>,
+    pub name: Vec<u8>,
+    pub document_url: Vec<u8>,
+    pub document_hash: Option<[u8; 32]>,
+    pub decimals: u8,
+    pub mint_baton_vout: Option<u8>,
+    pub initial_quantity: u64,
+}
+
+/// Token ids are in the byte order of tx hashes, i.e. reversed compared to
+/// the OP_RETURN, which pushes them in display order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SlpTxType {
+    Genesis(SlpGenesis),
+    Mint {
+        token_id: [u8; 32],
+        mint_baton_vout: Option<u8>,
+        additional_quantity: u64,
+    },
+    Send {
+        token_id: [u8; 32],
+        output_amounts: Vec<u64>,
+    },
+}
+
+/// A Simple Ledger Protocol message, found in the OP_RETURN of output 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlpMessage {
+    pub token_type: SlpTokenType,
+    pub tx_type: SlpTxType,
+}
+
+/// Tokens held by an output of a valid SLP tx.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlpToken {
+    pub token_id: [u8; 32],
+    pub token_type: SlpTokenType,
+    pub amount: u64,
+    pub is_mint_baton: bool,
+}
+
+impl SlpTokenType {
+    pub fn from_u16(token_type: u16) -> Option<Self> {
+        match token_type {
+            0x01 => Some(SlpTokenType::Fungible),
+            0x81 => Some(SlpTokenType::Nft1Group),
+            0x41 => Some(SlpTokenType::Nft1Child),
+            _ => None,
+        }
+    }
+
+    pub fn to_u16(self) -> u16 {
+        match self {
+            SlpTokenType::Fungible => 0x01,
+            SlpTokenType::Nft1Group => 0x81,
+            SlpTokenType::Nft1Child => 0x41,
+        }
+    }
+}
+
+fn invalid<T>(reason: &str) -> Result<T> {
+    Err(InvalidSlp(reason.to_string()).into())
+}
+
+fn parse_token_id(chunk: &[u8]) -> Result<[u8; 32]> {
+    if chunk.len() != 32 {
+        return invalid("token id must be 32 bytes");
+    }
+    let mut token_id = [0; 32];
+    token_id.copy_from_slice(chunk);
+    Ok(reversed(&token_id))
+}
+
+fn parse_amount(chunk: &[u8]) -> Result<u64> {
+    if chunk.len() != 8 {
+        return invalid("amount must be 8 bytes");
+    }
+    Ok(BigEndian::read_u64(chunk))
+}
+
+fn parse_mint_baton_vout(chunk: &[u8]) -> Result<Option<u8>> {
+    match chunk {
+        [] => Ok(None),
+        [vout] if *vout >= 2 => Ok(Some(*vout)),
+        [_] => invalid("mint baton vout must be at least 2"),
+        _ => invalid("mint baton vout must be 0 or 1 bytes"),
+    }
+}
+
+/// SLP requires empty chunks to be pushed with OP_PUSHDATA1, not OP_0.
+fn push_chunk(script: &mut Vec<u8>, chunk: &[u8]) {
+    match chunk.len() {
+        0 => script.extend_from_slice(&[OP_PUSHDATA1, 0]),
+        len @ 1..=0x4b => script.push(len as u8),
+        len @ 0x4c..=0xff => script.extend_from_slice(&[OP_PUSHDATA1, len as u8]),
+        len => {
+            script.push(OP_PUSHDATA2);
+            script.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(chunk);
+}
+
+fn reversed(token_id: &[u8; 32]) -> [u8; 32] {
+    let mut reversed = *token_id;
+    reversed.reverse();
+    reversed
+}
+
+fn parse_genesis(token_type: SlpTokenType, chunks: &[&[u8]]) -> Result<SlpTxType> {
+    if chunks.len() != 7 {
+        return invalid("GENESIS must have 10 pushes");
+    }
+    let document_hash = match chunks[3].len() {
+        0 => None,
+        32 => {
+            let mut document_hash = [0; 32];
+            document_hash.copy_from_slice(chunks[3]);
+            Some(document_hash)
+        }
+        _ => return invalid("document hash must be 0 or 32 bytes"),
+    };
+    let decimals = match chunks[4] {
+        [decimals] if *decimals <= MAX_DECIMALS => *decimals,
+        [_] => return invalid("decimals must be at most 9"),
+        _ => return invalid("decimals must be 1 byte"),
+    };
+    let genesis = SlpGenesis {
+        ticker: chunks[0].to_vec(),
+        name: chunks[1].to_vec(),
+        document_url: chunks[2].to_vec(),
+        document_hash,
+        decimals,
+        mint_baton_vout: parse_mint_baton_vout(chunks[5])?,
+        initial_quantity: parse_amount(chunks[6])?,
+    };
+    if token_type == SlpTokenType::Nft1Child {
+        if genesis.decimals != 0 {
+            return invalid("NFT1 child must have 0 decimals");
+        }
+        if genesis.mint_baton_vout.is_some() {
+            return invalid("NFT1 child must not have a mint baton");
+        }
+        if genesis.initial_quantity != 1 {
+            return invalid("NFT1 child must have a quantity of 1");
+        }
+    }
+    Ok(SlpTxType::Genesis(genesis))
+}
+
+fn parse_mint(token_type: SlpTokenType, chunks: &[&[u8]]) -> Result<SlpTxType> {
+    if token_type == SlpTokenType::Nft1Child {
+        return invalid("NFT1 child cannot be minted");
+    }
+    if chunks.len() != 3 {
+        return invalid("MINT must have 6 pushes");
+    }
+    Ok(SlpTxType::Mint {
+        token_id: parse_token_id(chunks[0])?,
+        mint_baton_vout: parse_mint_baton_vout(chunks[1])?,
+        additional_quantity: parse_amount(chunks[2])?,
+    })
+}
+
+fn parse_send(chunks: &[&[u8]]) -> Result<SlpTxType> {
+    if chunks.len() < 2 {
+        return invalid("SEND must have at least one amount");
+    }
+    if chunks.len() > 1 + MAX_SEND_OUTPUTS {
+        return invalid("SEND must have at most 19 amounts");
+    }
+    Ok(SlpTxType::Send {
+        token_id: parse_token_id(chunks[0])?,
+        output_amounts: chunks[1..]
+            .iter()
+            .map(|chunk| parse_amount(chunk))
+            .collect::<Result<_>>()?,
+    })
+}
+
+impl SlpMessage {
+    /// Parses an SLP OP_RETURN script. Fails with NoOpReturn or NotSlp if
+    /// the script isn't an OP_RETURN or has no SLP lokad id, and with
+    /// InvalidSlp if it has one but violates the protocol.
+    pub fn parse(script: &[u8]) -> Result<SlpMessage> {
+        if script.first() != Some(&OP_RETURN) {
+            bail!(NoOpReturn);
+        }
+        let mut chunks = Vec::new();
+        let mut parse_error = None;
+        let mut pc = 1;
+        while pc < script.len() {
+            match read_op(script, &mut pc) {
+                // OP_0 and the number opcodes are not allowed
+                Ok((0x01..=OP_PUSHDATA4, data)) => chunks.push(data),
+                Ok(_) => {
+                    parse_error = Some("non-push opcode");
+                    break;
+                }
+                Err(_) => {
+                    parse_error = Some("malformed push");
+                    break;
+                }
+            }
+        }
+        if chunks.first() != Some(&SLP_LOKAD_ID) {
+            bail!(NotSlp);
+        }
+        if let Some(parse_error) = parse_error {
+            return invalid(parse_error);
+        }
+        if chunks.len() < 3 {
+            return invalid("missing token type or transaction type");
+        }
+        let token_type = match chunks[1].len() {
+            1 | 2 => {
+                let mut token_type_bytes = [0; 2];
+                token_type_bytes[2 - chunks[1].len()..].copy_from_slice(chunks[1]);
+                u16::from_be_bytes(token_type_bytes)
+            }
+            _ => return invalid("token type must be 1 or 2 bytes"),
+        };
+        let token_type = match SlpTokenType::from_u16(token_type) {
+            Some(token_type) => token_type,
+            None => bail!(UnsupportedTokenType(token_type)),
+        };
+        let tx_type = match chunks[2] {
+            b"GENESIS" => parse_genesis(token_type, &chunks[3..])?,
+            b"MINT" => parse_mint(token_type, &chunks[3..])?,
+            b"SEND" => parse_send(&chunks[3..])?,
+            _ => return invalid("unknown transaction type"),
+        };
+        Ok(SlpMessage {
+            token_type,
+            tx_type,
+        })
+    }
+
+    /// Encodes the message as OP_RETURN script, inverse of `parse`.
+    pub fn to_script(&self) -> Vec<u8> {
+        let mut script = vec![OP_RETURN];
+        push_chunk(&mut script, SLP_LOKAD_ID);
+        push_chunk(&mut script, &[self.token_type.to_u16() as u8]);
+        let baton_chunk = |mint_baton_vout: &Option<u8>| match mint_baton_vout {
+            Some(vout) => vec![*vout],
+            None => vec![],
+        };
+        match &self.tx_type {
+            SlpTxType::Genesis(genesis) => {
+                push_chunk(&mut script, b"GENESIS");
+                push_chunk(&mut script, &genesis.ticker);
+                push_chunk(&mut script, &genesis.name);
+                push_chunk(&mut script, &genesis.document_url);
+                match &genesis.document_hash {
+                    Some(document_hash) => push_chunk(&mut script, document_hash),
+                    None => push_chunk(&mut script, &[]),
+                }
+                push_chunk(&mut script, &[genesis.decimals]);
+                push_chunk(&mut script, &baton_chunk(&genesis.mint_baton_vout));
+                push_chunk(&mut script, &genesis.initial_quantity.to_be_bytes());
+            }
+            SlpTxType::Mint {
+                token_id,
+                mint_baton_vout,
+                additional_quantity,
+            } => {
+                push_chunk(&mut script, b"MINT");
+                push_chunk(&mut script, &reversed(token_id));
+                push_chunk(&mut script, &baton_chunk(mint_baton_vout));
+                push_chunk(&mut script, &additional_quantity.to_be_bytes());
+            }
+            SlpTxType::Send {
+                token_id,
+                output_amounts,
+            } => {
+                push_chunk(&mut script, b"SEND");
+                push_chunk(&mut script, &reversed(token_id));
+                for amount in output_amounts {
+                    push_chunk(&mut script, &amount.to_be_bytes());
+                }
+            }
+        }
+        script
+    }
+
+    /// Parses the SLP message in output 0 of `tx`.
+    pub fn from_tx(tx: &Transaction) -> Result<SlpMessage> {
+        match tx.outputs.first() {
+            Some(output) => SlpMessage::parse(&output.script),
+            None => bail!(NoOpReturn),
+        }
+    }
+
+    /// The token id; for GENESIS, this is the hash of the tx itself.
+    pub fn token_id(&self, tx_hash: &[u8; 32]) -> [u8; 32] {
+        match &self.tx_type {
+            SlpTxType::Genesis(_) => *tx_hash,
+            SlpTxType::Mint { token_id, .. } | SlpTxType::Send { token_id, .. } => *token_id,
+        }
+    }
+
+    /// Amount of tokens the message assigns to output `vout`.
+    pub fn output_amount(&self, vout: usize) -> u64 {
+        match &self.tx_type {
+            SlpTxType::Genesis(SlpGenesis {
+                initial_quantity: amount,
+                ..
+            })
+            | SlpTxType::Mint {
+                additional_quantity: amount,
+                ..
+            } => {
+                if vout == 1 {
+                    *amount
+                } else {
+                    0
+                }
+            }
+            SlpTxType::Send { output_amounts, .. } => match vout {
+                0 => 0,
+                _ => output_amounts.get(vout - 1).cloned().unwrap_or(0),
+            },
+        }
+    }
+
+    /// Whether output `vout` receives the mint baton.
+    pub fn is_mint_baton(&self, vout: usize) -> bool {
+        let mint_baton_vout = match &self.tx_type {
+            SlpTxType::Genesis(genesis) => genesis.mint_baton_vout,
+            SlpTxType::Mint {
+                mint_baton_vout, ..
+            } => *mint_baton_vout,
+            SlpTxType::Send { .. } => None,
+        };
+        mint_baton_vout.map(usize::from) == Some(vout)
+    }
+
+    /// Tokens the message assigns to output `vout` of the tx with hash
+    /// `tx_hash`, None if the output receives neither tokens nor the baton.
+    pub fn output_token(&self, tx_hash: &[u8; 32], vout: usize) -> Option<SlpToken> {
+        let amount = self.output_amount(vout);
+        let is_mint_baton = self.is_mint_baton(vout);
+        if amount == 0 && !is_mint_baton {
+            return None;
+        }
+        Some(SlpToken {
+            token_id: self.token_id(tx_hash),
+            token_type: self.token_type,
+            amount,
+            is_mint_baton,
+        })
+    }
+}
+
+#[test]
+fn test_parse_slp() {
+    let script = hex::decode("6a04534c500001010747454e4553495304544553540a5465737420546f6b656e1368747470733a2f2f6578616d706c652e636f6d4c00010801020800000000000f4240").unwrap();
+    let message = SlpMessage::parse(&script).unwrap();
+    assert_eq!(message.token_type, SlpTokenType::Fungible);
+    assert_eq!(
+        message.tx_type,
+        SlpTxType::Genesis(SlpGenesis {
+            ticker: b"TEST".to_vec(),
+            name: b"Test Token".to_vec(),
+            document_url: b"https://example.com".to_vec(),
+            document_hash: None,
+            decimals: 8,
+            mint_baton_vout: Some(2),
+            initial_quantity: 1_000_000,
+        })
+    );
+    assert_eq!(message.token_id(&[7; 32]), [7; 32]);
+    assert_eq!(message.output_amount(1), 1_000_000);
+    assert_eq!(message.output_amount(2), 0);
+    assert!(message.is_mint_baton(2));
+    assert_eq!(message.to_script(), script);
+
+    let mut token_id = [0; 32];
+    token_id[0] = 0xaa;
+    let script = hex::decode(
+        "6a04534c500001010453454e4420\
+         00000000000000000000000000000000000000000000000000000000000000aa\
+         080000000000000005080000000000000007",
+    )
+    .unwrap();
+    let message = SlpMessage::parse(&script).unwrap();
+    assert_eq!(
+        message.tx_type,
+        SlpTxType::Send {
+            token_id,
+            output_amounts: vec![5, 7],
+        }
+    );
+    assert_eq!(message.token_id(&[7; 32]), token_id);
+    assert_eq!(message.output_amount(0), 0);
+    assert_eq!(message.output_amount(2), 7);
+    assert_eq!(message.output_amount(3), 0);
+    assert!(!message.is_mint_baton(2));
+    assert_eq!(message.to_script(), script);
+}
@@ -0,0 +1,251 @@
+use crate::errors::{ErrorKind::*, Result};
+use crate::message::{SlpMessage, SlpToken, SlpTokenType, SlpTxType};
+use cirrus_consensus::Transaction;
+use error_chain::bail;
+use std::collections::{hash_map::Entry, HashMap};
+
+/// Source of parent transactions for SLP validation, e.g. a local tx store
+/// or peers queried with `GetDataMessage`.
+pub trait TxProvider {
+    /// Returns the tx with hash `tx_hash`, or None if it's unknown.
+    fn get_tx(&self, tx_hash: &[u8; 32]) -> Result<Option<Transaction>>;
+}
+
+impl TxProvider for HashMap<[u8; 32], Transaction> {
+    fn get_tx(&self, tx_hash: &[u8; 32]) -> Result<Option<Transaction>> {
+        Ok(self.get(tx_hash).cloned())
+    }
+}
+
+/// Validates SLP transactions by walking their input DAG. Results are
+/// cached, so each tx of the DAG is fetched and validated at most once.
+pub struct SlpValidator<P: TxProvider> {
+    provider: P,
+    /// The SLP message of valid txs, None for invalid ones.
+    cache: HashMap<[u8; 32], Option<SlpMessage>>,
+}
+
+impl<P: TxProvider> SlpValidator<P> {
+    pub fn new(provider: P) -> Self {
+        SlpValidator {
+            provider,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// Records the validity of `tx` without validating it, e.g. for txs
+    /// already validated by a trusted source.
+    pub fn set_valid(&mut self, tx: &Transaction, is_valid: bool) {
+        let message = if is_valid {
+            SlpMessage::from_tx(tx).ok()
+        } else {
+            None
+        };
+        self.cache.insert(tx.hash(), message);
+    }
+
+    /// Cached validity of the tx with hash `tx_hash`, if it has been validated.
+    pub fn cached_validity(&self, tx_hash: &[u8; 32]) -> Option<bool> {
+        self.cache.get(tx_hash).map(Option::is_some)
+    }
+
+    pub fn validate(&mut self, tx: &Transaction) -> Result<bool> {
+        Ok(self.valid_message(tx)?.is_some())
+    }
+
+    /// Returns the SLP message of `tx` if it's a valid SLP tx. Fails with
+    /// MissingTx if a parent needed for validation can't be found, in which
+    /// case the validity is inconclusive.
+    pub fn valid_message(&mut self, tx: &Transaction) -> Result<Option<SlpMessage>> {
+        let tx_hash = tx.hash();
+        let mut txs = HashMap::new();
+        txs.insert(tx_hash, tx.clone());
+        // iterative depth first search, as SLP DAGs can be very deep
+        let mut stack = vec![tx_hash];
+        while let Some(&tx_hash) = stack.last() {
+            if self.cache.contains_key(&tx_hash) {
+                stack.pop();
+                continue;
+            }
+            let tx = match txs.entry(tx_hash) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => match self.provider.get_tx(&tx_hash)? {
+                    Some(tx) => entry.insert(tx),
+                    None => {
+                        let mut tx_hash = tx_hash;
+                        tx_hash.reverse();
+                        bail!(MissingTx(hex::encode(tx_hash)))
+                    }
+                },
+            };
+            let message = match SlpMessage::from_tx(tx) {
+                Ok(message) => message,
+                Err(_) => {
+                    self.cache.insert(tx_hash, None);
+                    txs.remove(&tx_hash);
+                    stack.pop();
+                    continue;
+                }
+            };
+            let unvalidated_parents = parent_hashes(tx, &message)
+                .filter(|parent_hash| !self.cache.contains_key(*parent_hash))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !unvalidated_parents.is_empty() {
+                stack.extend(unvalidated_parents);
+                continue;
+            }
+            let is_valid = self.check_inputs(tx, &message);
+            self.cache
+                .insert(tx_hash, if is_valid { Some(message) } else { None });
+            txs.remove(&tx_hash);
+            stack.pop();
+        }
+        Ok(self.cache[&tx_hash].clone())
+    }
+
+    /// Checks the token inputs of `tx`, whose parents must all be cached.
+    fn check_inputs(&self, tx: &Transaction, message: &SlpMessage) -> bool {
+        let input_tokens = tx.inputs.iter().map(|input| {
+            let parent_message = self.cache[&input.prev_out.tx_hash].as_ref()?;
+            parent_message.output_token(&input.prev_out.tx_hash, input.prev_out.vout as usize)
+        });
+        check_token_inputs(message, &tx.hash(), input_tokens)
+    }
+}
+
+/// Checks whether the tokens spent by a tx with hash `tx_hash` and SLP
+/// message `message` make it valid. `input_tokens` are the validated
+/// tokens of the spent outputs, in input order.
+pub fn check_token_inputs(
+    message: &SlpMessage,
+    tx_hash: &[u8; 32],
+    input_tokens: impl IntoIterator<Item = Option<SlpToken>>,
+) -> bool {
+    let token_id = message.token_id(tx_hash);
+    let mut input_tokens = input_tokens.into_iter();
+    match &message.tx_type {
+        SlpTxType::Genesis(_) => match message.token_type {
+            // NFT1 children must burn a group token at input 0
+            SlpTokenType::Nft1Child => match input_tokens.next() {
+                Some(Some(token)) => {
+                    token.token_type == SlpTokenType::Nft1Group && token.amount > 0
+                }
+                _ => false,
+            },
+            _ => true,
+        },
+        SlpTxType::Mint { .. } => input_tokens.flatten().any(|token| {
+            token.token_type == message.token_type
+                && token.token_id == token_id
+                && token.is_mint_baton
+        }),
+        SlpTxType::Send { output_amounts, .. } => {
+            let input_sum = input_tokens
+                .flatten()
+                .filter(|token| {
+                    token.token_type == message.token_type && token.token_id == token_id
+                })
+                .map(|token| u128::from(token.amount))
+                .sum::<u128>();
+            let output_sum = output_amounts.iter().cloned().map(u128::from).sum::<u128>();
+            input_sum >= output_sum
+        }
+    }
+}
+
+/// Hashes of the parents whose validity determines the validity of `tx`.
+fn parent_hashes<'a>(
+    tx: &'a Transaction,
+    message: &SlpMessage,
+) -> impl Iterator<Item = &'a [u8; 32]> {
+    let num_parents = match (&message.tx_type, message.token_type) {
+        (SlpTxType::Genesis(_), SlpTokenType::Nft1Child) => 1,
+        (SlpTxType::Genesis(_), _) => 0,
+        _ => tx.inputs.len(),
+    };
+    tx.inputs
+        .iter()
+        .take(num_parents)
+        .map(|input| &input.prev_out.tx_hash)
+}
+
+#[test]
+fn test_validate_dag() {
+    use crate::errors::ErrorKind;
+    use cashcontracts::TxOutpoint;
+    use cirrus_consensus::{TxInput, TxOutput};
+
+    fn slp_tx(inputs: &[(&Transaction, u32)], script: String) -> Transaction {
+        Transaction {
+            version: 2,
+            inputs: inputs
+                .iter()
+                .map(|(parent, vout)| TxInput {
+                    prev_out: TxOutpoint {
+                        tx_hash: parent.hash(),
+                        vout: *vout,
+                    },
+                    script: vec![],
+                    sequence: 0xffff_ffff,
+                })
+                .collect(),
+            outputs: vec![
+                TxOutput {
+                    value: 0,
+                    script: hex::decode(script).unwrap(),
+                    token: None,
+                },
+                TxOutput {
+                    value: 546,
+                    script: vec![0x51],
+                    token: None,
+                },
+            ],
+            lock_time: 0,
+        }
+    }
+    fn send(token: &Transaction, parent: &Transaction, amount: u64) -> Transaction {
+        let mut token_id = token.hash();
+        token_id.reverse();
+        let script = format!(
+            "6a04534c500001010453454e4420{}08{:016x}",
+            hex::encode(token_id),
+            amount,
+        );
+        slp_tx(&[(parent, 1)], script)
+    }
+
+    let funding = slp_tx(&[], "51".to_string());
+    let genesis = slp_tx(
+        &[(&funding, 0)],
+        "6a04534c500001010747454e455349530341424301414c004c0001004c00080000000000000064"
+            .to_string(),
+    );
+    let mut txs = HashMap::new();
+    let mut parent = genesis.clone();
+    for _ in 0..1000 {
+        let tx = send(&genesis, &parent, 100);
+        txs.insert(parent.hash(), parent);
+        parent = tx;
+    }
+    let mut validator = SlpValidator::new(txs);
+    assert!(validator.validate(&parent).unwrap());
+    assert!(!validator.validate(&send(&genesis, &parent, 101)).unwrap());
+    assert_eq!(validator.cached_validity(&genesis.hash()), Some(true));
+    assert_eq!(validator.cached_validity(&funding.hash()), None);
+
+    let orphan = send(&genesis, &funding, 100);
+    let mut validator = SlpValidator::new(HashMap::new());
+    match validator.validate(&send(&genesis, &orphan, 100)) {
+        Err(err) => match err.kind() {
+            ErrorKind::MissingTx(_) => {}
+            _ => panic!("unexpected error: {}", err),
+        },
+        Ok(_) => panic!("validated without parents"),
+    }
+}
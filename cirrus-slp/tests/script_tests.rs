@@ -0,0 +1,62 @@
+use cirrus_slp::errors::ErrorKind;
+use cirrus_slp::SlpMessage;
+use std::collections::HashMap;
+
+/// Name of the kind of the parse error of `script`, None if it's valid.
+fn parse_error(msg: &str, script: &[u8]) -> Option<&'static str> {
+    match SlpMessage::parse(script) {
+        Ok(_) => None,
+        Err(err) => Some(match err.kind() {
+            ErrorKind::NoOpReturn => "NoOpReturn",
+            ErrorKind::NotSlp => "NotSlp",
+            ErrorKind::InvalidSlp(_) => "InvalidSlp",
+            ErrorKind::UnsupportedTokenType(_) => "UnsupportedTokenType",
+            kind => panic!("{}: unexpected error {:?}", msg, kind),
+        }),
+    }
+}
+
+/// Hand-written cases, their codes name the expected error kind.
+#[test]
+fn test_slp_script_vectors() {
+    let json = include_str!("data/script_tests_extra.json");
+    let vectors: Vec<serde_json::Value> = serde_json::from_str(json).unwrap();
+    for vector in vectors {
+        let msg = vector["msg"].as_str().unwrap();
+        let script = hex::decode(vector["script"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            parse_error(msg, &script),
+            vector["code"].as_str(),
+            "{}",
+            msg
+        );
+    }
+}
+
+/// script_tests.json of slp-unit-test-data, unchanged.
+///
+/// Its integer codes distinguish more cases than our error kinds, so each
+/// code has to map to a single kind, and null to a valid message.
+#[test]
+#[ignore = "needs tests/data/script_tests.json, see scripts/fetch_test_vectors.sh"]
+fn test_slp_unit_test_script_vectors() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/script_tests.json");
+    let json = std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("can't read SLP script tests at {}: {}", path, err));
+    let vectors: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+    let mut kinds_by_code = HashMap::new();
+    for vector in vectors.iter() {
+        let msg = vector["msg"].as_str().unwrap();
+        let script = hex::decode(vector["script"].as_str().unwrap()).unwrap();
+        let error = parse_error(msg, &script);
+        match vector["code"].as_u64() {
+            None => assert_eq!(error, None, "{}", msg),
+            Some(code) => {
+                let error = error.unwrap_or_else(|| panic!("{}: parsed, expected {}", msg, code));
+                let kind = *kinds_by_code.entry(code).or_insert(error);
+                assert_eq!(error, kind, "{}: code {}", msg, code);
+            }
+        }
+    }
+    assert!(vectors.len() > 100);
+}
@@ -0,0 +1,57 @@
+use cirrus_consensus::Transaction;
+use cirrus_slp::errors::ErrorKind;
+use cirrus_slp::SlpValidator;
+use std::collections::HashMap;
+
+fn parse_tx(vector_tx: &serde_json::Value) -> (Transaction, bool) {
+    let tx_hex = vector_tx["tx"].as_str().unwrap();
+    let tx = Transaction::from_slice(&hex::decode(tx_hex).unwrap()).unwrap();
+    (tx, vector_tx["valid"].as_bool().unwrap())
+}
+
+/// Runs vectors in the format of slp-unit-test-data's tx_input_tests.json:
+/// the `when` txs are given as validated, the `should` txs have to validate
+/// as stated, or be inconclusive for lack of inputs if that's allowed.
+fn run_tx_input_vectors(json: &str) -> usize {
+    let vectors: Vec<serde_json::Value> = serde_json::from_str(json).unwrap();
+    for vector in vectors.iter() {
+        let description = vector["description"].as_str().unwrap();
+        let allow_inconclusive = vector["allow_inconclusive"].as_bool().unwrap_or(false);
+        let mut validator = SlpValidator::new(HashMap::new());
+        for tx in vector["when"].as_array().unwrap() {
+            let (tx, is_valid) = parse_tx(tx);
+            validator.set_valid(&tx, is_valid);
+        }
+        for tx in vector["should"].as_array().unwrap() {
+            let (tx, is_valid) = parse_tx(tx);
+            match validator.validate(&tx) {
+                Ok(result) => assert_eq!(result, is_valid, "{}", description),
+                Err(err) => match err.kind() {
+                    ErrorKind::MissingTx(_) if allow_inconclusive => {}
+                    _ => panic!("{}: {}", description, err),
+                },
+            }
+        }
+    }
+    vectors.len()
+}
+
+/// tx_input_tests.json of slp-unit-test-data, unchanged.
+#[test]
+#[ignore = "needs tests/data/tx_input_tests.json, see scripts/fetch_test_vectors.sh"]
+fn test_slp_unit_test_tx_input_vectors() {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/tx_input_tests.json"
+    );
+    let json = std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("can't read SLP tx input tests at {}: {}", path, err));
+    assert!(run_tx_input_vectors(&json) > 100);
+}
+
+/// Hand-written cases on synthetic outpoints.
+#[test]
+fn test_slp_tx_input_vectors() {
+    let num_tested = run_tx_input_vectors(include_str!("data/tx_input_tests_extra.json"));
+    assert!(num_tested >= 30);
+}
@@ -1,8 +1,45 @@
 use error_chain::error_chain;
 
 error_chain! {
+    foreign_links {
+        Io(std::io::Error);
+        Db(sled::Error);
+    }
+
     errors {
         ConnectionError {}
         ChannelError {}
+        InvalidUrl(url: String) {
+            description("invalid snapshot url")
+            display("invalid snapshot url: {}", url)
+        }
+        SnapshotVerificationFailed(reason: String) {
+            description("snapshot verification failed")
+            display("snapshot verification failed: {}", reason)
+        }
+        InvalidSnapshotHeader(reason: String) {
+            description("invalid snapshot header")
+            display("invalid snapshot header: {}", reason)
+        }
+        UnknownBestBlock {
+            description("utxo set has no best block")
+            display("utxo set has no best block")
+        }
+        CorruptUtxoEntry(key: String) {
+            description("corrupt utxo set entry")
+            display("corrupt utxo set entry: {}", key)
+        }
+        MissingUtxo(outpoint: String) {
+            description("spent utxo not in utxo set")
+            display("spent utxo not in utxo set: {}", outpoint)
+        }
+        BlockNotConnected(block_hash: String) {
+            description("block doesn't connect to the best block")
+            display("block doesn't connect to the best block: {}", block_hash)
+        }
+        InvalidUndoData(block_hash: String) {
+            description("missing or invalid undo data")
+            display("missing or invalid undo data for block {}", block_hash)
+        }
     }
 }
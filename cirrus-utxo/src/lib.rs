@@ -1,6 +1,12 @@
 pub mod errors;
+mod snapshot_reader;
 mod stream_ipfs;
+#[cfg(test)]
+mod test_utils;
 mod utxo;
+mod utxo_set;
 
+pub use snapshot_reader::*;
 pub use stream_ipfs::*;
 pub use utxo::*;
+pub use utxo_set::*;
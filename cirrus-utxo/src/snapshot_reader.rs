@@ -0,0 +1,417 @@
+use crate::errors::{ErrorKind::*, Result};
+use crate::utxo::Utxo;
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use cashcontracts::tx_hash_to_hex;
+use cirrus_consensus::script::MAX_SCRIPT_SIZE;
+use cirrus_consensus::MAX_TOKEN_PREFIX_SIZE;
+use cirrus_crypto::{EcMultiset, Network};
+use error_chain::bail;
+use futures::io::AsyncRead;
+use futures::stream::Stream;
+use futures::task::{Context, Poll};
+use sha2::{Digest, Sha256};
+use std::pin::Pin;
+use std::{
+    io,
+    io::{Cursor, Read, Write},
+};
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"UTXO";
+/// Version 2 adds the SLP status of each utxo, see `Utxo::read_slp_status`.
+pub const SNAPSHOT_VERSION: u32 = 2;
+pub const SNAPSHOT_HEADER_SIZE: usize = 4 + 4 + 1 + 32 + 4 + 8 + 32;
+
+const UTXO_HEADER_SIZE: usize = 32 + 4 + 4 + 8 + 4;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+/// Longer scripts are unspendable and never make it into the utxo set.
+const MAX_SCRIPT_FIELD_SIZE: usize = MAX_SCRIPT_SIZE + MAX_TOKEN_PREFIX_SIZE;
+
+/// What a snapshot is expected to contain. Obtain these from a trusted
+/// source, e.g. `gettxoutsetinfo ecmh` on your own node.
+#[derive(Clone, Debug)]
+pub struct SnapshotCommitment {
+    /// sha256 of the snapshot's bytes.
+    pub content_hash: [u8; 32],
+    /// ECMH of `Utxo::commitment_bytes` of all utxos.
+    pub utxo_commitment: [u8; 32],
+    /// Height of the snapshot; no utxo may be from a later block.
+    pub block_height: i32,
+}
+
+/// Header of snapshots written by `UtxoSet::write_snapshot`. Snapshots
+/// without one, like the original IPFS dump, consist of utxo records only.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    /// From 1 up to `SNAPSHOT_VERSION`.
+    pub version: u32,
+    pub network: Network,
+    /// Hash of the last block included in the snapshot.
+    pub block_hash: [u8; 32],
+    pub block_height: i32,
+    pub utxo_count: u64,
+    /// ECMH of `Utxo::commitment_bytes` of all utxos.
+    pub utxo_commitment: [u8; 32],
+}
+
+impl SnapshotHeader {
+    pub fn from_stream(stream: &mut impl Read) -> Result<SnapshotHeader> {
+        let mut magic = [0; 4];
+        stream.read_exact(&mut magic)?;
+        if magic != SNAPSHOT_MAGIC {
+            bail!(InvalidSnapshotHeader(format!(
+                "wrong magic {}",
+                hex::encode(magic)
+            )));
+        }
+        let version = stream.read_u32::<LittleEndian>()?;
+        if version == 0 || version > SNAPSHOT_VERSION {
+            bail!(InvalidSnapshotHeader(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        let network = match stream.read_u8()? {
+            0 => Network::Mainnet,
+            1 => Network::Testnet,
+            2 => Network::Regtest,
+            network => bail!(InvalidSnapshotHeader(format!(
+                "unknown network {}",
+                network
+            ))),
+        };
+        let mut block_hash = [0; 32];
+        stream.read_exact(&mut block_hash)?;
+        let block_height = stream.read_i32::<LittleEndian>()?;
+        let utxo_count = stream.read_u64::<LittleEndian>()?;
+        let mut utxo_commitment = [0; 32];
+        stream.read_exact(&mut utxo_commitment)?;
+        Ok(SnapshotHeader {
+            version,
+            network,
+            block_hash,
+            block_height,
+            utxo_count,
+            utxo_commitment,
+        })
+    }
+
+    pub fn write_to_stream(&self, stream: &mut impl Write) -> io::Result<()> {
+        stream.write_all(&SNAPSHOT_MAGIC)?;
+        stream.write_u32::<LittleEndian>(self.version)?;
+        stream.write_u8(match self.network {
+            Network::Mainnet => 0,
+            Network::Testnet => 1,
+            Network::Regtest => 2,
+        })?;
+        stream.write_all(&self.block_hash)?;
+        stream.write_i32::<LittleEndian>(self.block_height)?;
+        stream.write_u64::<LittleEndian>(self.utxo_count)?;
+        stream.write_all(&self.utxo_commitment)?;
+        Ok(())
+    }
+}
+
+/// Splits the snapshot bytes into utxos, hashing them along the way.
+pub(crate) struct SnapshotParser {
+    buffer: Vec<u8>,
+    position: usize,
+    content_hasher: Sha256,
+    multiset: EcMultiset,
+    max_height: i32,
+    num_utxos: u64,
+    /// None until it's known whether the snapshot starts with a header.
+    expects_header: Option<bool>,
+    header: Option<SnapshotHeader>,
+}
+
+impl SnapshotParser {
+    pub fn new(expects_header: bool) -> Self {
+        SnapshotParser {
+            expects_header: Some(expects_header),
+            ..SnapshotParser::detecting_header()
+        }
+    }
+
+    /// Parser for snapshots with or without header, told apart by
+    /// `SNAPSHOT_MAGIC`. A header-less snapshot starts with a tx hash, which
+    /// matches the magic with a chance of 2^-32.
+    pub fn detecting_header() -> Self {
+        SnapshotParser {
+            buffer: Vec::new(),
+            position: 0,
+            content_hasher: Sha256::new(),
+            multiset: EcMultiset::new(),
+            max_height: 0,
+            num_utxos: 0,
+            expects_header: None,
+            header: None,
+        }
+    }
+
+    pub fn header(&self) -> Option<&SnapshotHeader> {
+        self.header.as_ref()
+    }
+
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        self.content_hasher.input(bytes);
+        self.buffer.drain(..self.position);
+        self.position = 0;
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Parses the next utxo, or returns None if more bytes are needed.
+    pub fn next_utxo(&mut self) -> Result<Option<Utxo>> {
+        if self.expects_header.is_none() {
+            let remaining = &self.buffer[self.position..];
+            if remaining.len() < SNAPSHOT_MAGIC.len() {
+                return Ok(None);
+            }
+            self.expects_header = Some(remaining[..SNAPSHOT_MAGIC.len()] == SNAPSHOT_MAGIC);
+        }
+        if self.expects_header == Some(true) && self.header.is_none() {
+            if self.buffer.len() - self.position < SNAPSHOT_HEADER_SIZE {
+                return Ok(None);
+            }
+            let mut cur = Cursor::new(&self.buffer[self.position..]);
+            self.header = Some(SnapshotHeader::from_stream(&mut cur)?);
+            self.position += SNAPSHOT_HEADER_SIZE;
+        }
+        let remaining = &self.buffer[self.position..];
+        if remaining.len() < UTXO_HEADER_SIZE {
+            return Ok(None);
+        }
+        let script_len = LittleEndian::read_u32(&remaining[UTXO_HEADER_SIZE - 4..]) as usize;
+        // checked before buffering the script, so a bogus length can't make
+        // us hold the rest of the stream in memory
+        if script_len > MAX_SCRIPT_FIELD_SIZE {
+            bail!(SnapshotVerificationFailed(format!(
+                "script of {} bytes at utxo {}",
+                script_len, self.num_utxos
+            )));
+        }
+        let mut record_size = UTXO_HEADER_SIZE + script_len;
+        let has_slp_status = matches!(&self.header, Some(header) if header.version >= 2);
+        if has_slp_status {
+            match remaining.get(record_size) {
+                Some(&status) => record_size += Utxo::slp_status_size(status),
+                None => return Ok(None),
+            }
+        }
+        if remaining.len() < record_size {
+            return Ok(None);
+        }
+        let mut cur = Cursor::new(remaining);
+        let mut utxo = Utxo::from_stream(&mut cur)?;
+        if has_slp_status {
+            utxo.read_slp_status(&mut cur)?;
+        }
+        self.position += record_size;
+        self.multiset.insert(&utxo.commitment_bytes());
+        self.max_height = self.max_height.max(utxo.block_height);
+        self.num_utxos += 1;
+        Ok(Some(utxo))
+    }
+
+    /// Checks that all bytes have been consumed, that they match the
+    /// header, if any, and `expected`, if given.
+    pub fn finish(&mut self, expected: Option<&SnapshotCommitment>) -> Result<()> {
+        let num_trailing = self.buffer.len() - self.position;
+        if num_trailing != 0 {
+            bail!(SnapshotVerificationFailed(format!(
+                "{} trailing bytes",
+                num_trailing
+            )));
+        }
+        let utxo_commitment = self.multiset.hash();
+        if self.expects_header == Some(true) {
+            let header = match &self.header {
+                Some(header) => header,
+                None => bail!(SnapshotVerificationFailed("missing header".to_string())),
+            };
+            if header.utxo_count != self.num_utxos {
+                bail!(SnapshotVerificationFailed(format!(
+                    "{} utxos, header states {}",
+                    self.num_utxos, header.utxo_count
+                )));
+            }
+            if self.max_height > header.block_height {
+                bail!(SnapshotVerificationFailed(format!(
+                    "utxo at height {} above header height {}",
+                    self.max_height, header.block_height
+                )));
+            }
+            if utxo_commitment != header.utxo_commitment {
+                bail!(SnapshotVerificationFailed(format!(
+                    "utxo commitment is {}, header states {}",
+                    tx_hash_to_hex(&utxo_commitment),
+                    tx_hash_to_hex(&header.utxo_commitment)
+                )));
+            }
+        }
+        let expected = match expected {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+        let content_hash = self.content_hasher.clone().result();
+        if content_hash[..] != expected.content_hash[..] {
+            bail!(SnapshotVerificationFailed(format!(
+                "content hash is {}",
+                hex::encode(content_hash)
+            )));
+        }
+        if self.max_height > expected.block_height {
+            bail!(SnapshotVerificationFailed(format!(
+                "utxo at height {} above snapshot height {}",
+                self.max_height, expected.block_height
+            )));
+        }
+        if utxo_commitment != expected.utxo_commitment {
+            bail!(SnapshotVerificationFailed(format!(
+                "utxo commitment is {}",
+                tx_hash_to_hex(&utxo_commitment)
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Reads a snapshot from any `AsyncRead` as a stream of utxos. If a
+/// commitment is set or the snapshot has a header, the last item is a
+/// SnapshotVerificationFailed error if they don't match, in which case all
+/// previous utxos must be discarded.
+pub struct UtxoReader<R> {
+    reader: R,
+    parser: SnapshotParser,
+    expected_commitment: Option<SnapshotCommitment>,
+    chunk: Vec<u8>,
+    is_finished: bool,
+}
+
+impl<R: AsyncRead + Unpin> UtxoReader<R> {
+    /// Reader for snapshots without header.
+    pub fn new(reader: R) -> Self {
+        UtxoReader::with_parser(reader, SnapshotParser::new(false))
+    }
+
+    /// Reader for snapshots starting with a `SnapshotHeader`.
+    pub fn with_header(reader: R) -> Self {
+        UtxoReader::with_parser(reader, SnapshotParser::new(true))
+    }
+
+    /// Reader for snapshots with or without header, see
+    /// `SnapshotParser::detecting_header`.
+    pub fn detecting_header(reader: R) -> Self {
+        UtxoReader::with_parser(reader, SnapshotParser::detecting_header())
+    }
+
+    fn with_parser(reader: R, parser: SnapshotParser) -> Self {
+        UtxoReader {
+            reader,
+            parser,
+            expected_commitment: None,
+            chunk: vec![0; READ_CHUNK_SIZE],
+            is_finished: false,
+        }
+    }
+
+    /// The snapshot's header, available once the first utxo has been read.
+    pub fn header(&self) -> Option<&SnapshotHeader> {
+        self.parser.header()
+    }
+
+    pub fn verify_against(&mut self, commitment: SnapshotCommitment) {
+        self.expected_commitment = Some(commitment);
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for UtxoReader<R> {
+    type Item = Result<Utxo>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Utxo>>> {
+        let this = self.get_mut();
+        loop {
+            if this.is_finished {
+                return Poll::Ready(None);
+            }
+            match this.parser.next_utxo() {
+                Ok(Some(utxo)) => return Poll::Ready(Some(Ok(utxo))),
+                Ok(None) => {}
+                Err(err) => {
+                    this.is_finished = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+            let num_read = match Pin::new(&mut this.reader).poll_read(cx, &mut this.chunk) {
+                Poll::Ready(Ok(num_read)) => num_read,
+                Poll::Ready(Err(err)) => {
+                    this.is_finished = true;
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+            if num_read == 0 {
+                this.is_finished = true;
+                if let Err(err) = this.parser.finish(this.expected_commitment.as_ref()) {
+                    return Poll::Ready(Some(Err(err)));
+                }
+            } else {
+                this.parser.push_bytes(&this.chunk[..num_read]);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_utxo_reader() {
+    use crate::test_utils::{SNAPSHOT, SNAPSHOT_UTXO_COMMITMENT};
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use hex_literal::hex;
+
+    let snapshot = SNAPSHOT;
+    let commitment = SnapshotCommitment {
+        content_hash: hex!("e4088181bc4327386587c095cebb77fdf4fe3dade40e46b112cf25d9cfc69acc"),
+        utxo_commitment: SNAPSHOT_UTXO_COMMITMENT,
+        block_height: 300,
+    };
+    let read_all = |bytes: &[u8], commitment: &SnapshotCommitment| {
+        let mut reader = UtxoReader::new(bytes);
+        reader.verify_against(commitment.clone());
+        block_on(reader.collect::<Vec<_>>())
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+    };
+
+    let utxos = read_all(snapshot, &commitment).unwrap();
+    assert_eq!(utxos.len(), 3);
+    assert!(utxos[0].is_coinbase());
+    assert_eq!(utxos[0].block_height, 100);
+    assert_eq!(utxos[1].amount, 12345);
+    assert_eq!(utxos[2].outpoint.vout, 7);
+
+    let assert_tampered = |result: Result<Vec<Utxo>>| match result.unwrap_err().kind() {
+        SnapshotVerificationFailed(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    };
+    let mut tampered = snapshot.to_vec();
+    tampered[100] ^= 1;
+    assert_tampered(read_all(&tampered, &commitment));
+    assert_tampered(read_all(&snapshot[..200], &commitment));
+    let wrong_commitment = SnapshotCommitment {
+        utxo_commitment: [0; 32],
+        ..commitment.clone()
+    };
+    assert_tampered(read_all(snapshot, &wrong_commitment));
+    let lower_height = SnapshotCommitment {
+        block_height: 299,
+        ..commitment.clone()
+    };
+    assert_tampered(read_all(snapshot, &lower_height));
+
+    // a bogus script length fails before the script is buffered
+    let mut bogus = snapshot[..UTXO_HEADER_SIZE].to_vec();
+    bogus[UTXO_HEADER_SIZE - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut parser = SnapshotParser::new(false);
+    parser.push_bytes(&bogus);
+    assert_tampered(parser.next_utxo().map(|_| vec![]));
+}
@@ -1,67 +1,136 @@
 use crate::errors::{ErrorKind::*, Result, ResultExt};
+use crate::snapshot_reader::{SnapshotCommitment, SnapshotParser, UtxoReader};
 use crate::utxo::Utxo;
-use byteorder::{LittleEndian, ReadBytesExt};
-use cashcontracts::TxOutpoint;
+use futures::io::AllowStdIo;
+use futures::StreamExt;
 use futures_channel::mpsc::UnboundedSender;
 use hyper::Client;
-use std::io::{Cursor, Read};
+use std::fs::File;
+use std::path::PathBuf;
 
-pub struct UtxoStreamIpfs {
-    link: String,
+pub const DEFAULT_SNAPSHOT_URL: &str =
+    "http://ipfs.greyh.at/ipfs/QmXkBQJrMKkCKNbwv4m5xtnqwU9Sq7kucPigvZW8mWxcrv";
+
+#[derive(Clone, Debug)]
+pub enum SnapshotSource {
+    Url(String),
+    File(PathBuf),
 }
 
-const UTXO_HEADER_SIZE: usize = 32 + 4 + 4 + 8 + 4;
+pub struct UtxoStreamIpfs {
+    source: SnapshotSource,
+    expected_commitment: Option<SnapshotCommitment>,
+}
 
 impl UtxoStreamIpfs {
     pub fn new() -> Self {
+        UtxoStreamIpfs::from_url(DEFAULT_SNAPSHOT_URL)
+    }
+
+    pub fn from_url(url: impl Into<String>) -> Self {
         UtxoStreamIpfs {
-            link: "http://ipfs.greyh.at/ipfs/QmXkBQJrMKkCKNbwv4m5xtnqwU9Sq7kucPigvZW8mWxcrv"
-                .to_string(),
+            source: SnapshotSource::Url(url.into()),
+            expected_commitment: None,
         }
     }
 
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        UtxoStreamIpfs {
+            source: SnapshotSource::File(path.into()),
+            expected_commitment: None,
+        }
+    }
+
+    /// Makes `stream_to` fail if the snapshot doesn't match `commitment`.
+    pub fn verify_against(&mut self, commitment: SnapshotCommitment) {
+        self.expected_commitment = Some(commitment);
+    }
+
+    pub fn source(&self) -> &SnapshotSource {
+        &self.source
+    }
+
+    /// Sends all utxos of the snapshot to `utxo_sender`. Snapshots written
+    /// by `UtxoSet::write_snapshot` are verified against their header, dumps
+    /// without header are accepted as well. The snapshot can
+    /// only be verified once it's been read completely, so if this fails
+    /// with SnapshotVerificationFailed, all received utxos must be discarded.
     pub async fn stream_to(&self, utxo_sender: UnboundedSender<Utxo>) -> Result<()> {
-        let response = Client::new()
-            .get(self.link.parse().unwrap())
-            .await
-            .chain_err(|| ConnectionError)?;
-        let mut body = response.into_body();
-        let mut remaining_bytes = Vec::new();
-        while let Some(chunk) = body.next().await {
-            remaining_bytes.extend_from_slice(chunk.chain_err(|| ConnectionError)?.as_ref());
-            let mut i = 0;
-            while remaining_bytes.len() >= i {
-                let num_remaining = remaining_bytes.len() - i;
-                if num_remaining < UTXO_HEADER_SIZE {
-                    break;
+        let send = |utxo| utxo_sender.unbounded_send(utxo).chain_err(|| ChannelError);
+        match &self.source {
+            SnapshotSource::Url(url) => {
+                let uri = url.parse().chain_err(|| InvalidUrl(url.clone()))?;
+                let response = Client::new().get(uri).await.chain_err(|| ConnectionError)?;
+                let mut body = response.into_body();
+                let mut parser = SnapshotParser::detecting_header();
+                while let Some(chunk) = body.next().await {
+                    parser.push_bytes(chunk.chain_err(|| ConnectionError)?.as_ref());
+                    while let Some(utxo) = parser.next_utxo()? {
+                        send(utxo)?;
+                    }
                 }
-                let mut cur = Cursor::new(&remaining_bytes[i..]);
-                let mut tx_hash = [0; 32];
-                cur.read_exact(&mut tx_hash).unwrap();
-                let vout = cur.read_u32::<LittleEndian>().unwrap();
-                let height_flagged = cur.read_i32::<LittleEndian>().unwrap();
-                let flags = ((height_flagged & 0x0100_0000) >> 24) as u8;
-                let block_height = height_flagged & 0x00ff_ffff;
-                let amount = cur.read_u64::<LittleEndian>().unwrap();
-                let script_len = cur.read_u32::<LittleEndian>().unwrap() as usize;
-                if num_remaining < UTXO_HEADER_SIZE + script_len {
-                    break;
+                parser.finish(self.expected_commitment.as_ref())
+            }
+            SnapshotSource::File(path) => {
+                let file = AllowStdIo::new(File::open(path)?);
+                let mut reader = UtxoReader::detecting_header(file);
+                if let Some(commitment) = &self.expected_commitment {
+                    reader.verify_against(commitment.clone());
+                }
+                while let Some(utxo) = reader.next().await {
+                    send(utxo?)?;
                 }
-                let mut script = vec![0; script_len];
-                cur.read_exact(&mut script).unwrap();
-                utxo_sender
-                    .unbounded_send(Utxo {
-                        outpoint: TxOutpoint { tx_hash, vout },
-                        amount,
-                        script,
-                        block_height,
-                        flags,
-                    })
-                    .chain_err(|| ChannelError)?;
-                i += UTXO_HEADER_SIZE + script_len;
+                Ok(())
             }
-            remaining_bytes.drain(..i);
         }
-        Ok(())
+    }
+}
+
+#[test]
+fn test_stream_file() {
+    use crate::snapshot_reader::SnapshotHeader;
+    use crate::test_utils::{TempDir, SNAPSHOT, SNAPSHOT_UTXO_COMMITMENT};
+    use cirrus_crypto::Network;
+    use futures::executor::block_on;
+
+    let dir = TempDir::new("stream");
+    let path = dir.path().join("snapshot");
+    std::fs::write(&path, SNAPSHOT).unwrap();
+    let stream = UtxoStreamIpfs::from_path(&path);
+    let (sender, receiver) = futures_channel::mpsc::unbounded();
+    block_on(stream.stream_to(sender)).unwrap();
+    let utxos = block_on(receiver.collect::<Vec<_>>());
+    assert_eq!(utxos.len(), 3);
+    assert_eq!(utxos[0].amount, 5_000_000_000);
+    assert_eq!(utxos[0].script.len(), 25);
+
+    // snapshots written by write_snapshot are verified against their header
+    let mut header = SnapshotHeader {
+        version: 1,
+        network: Network::Mainnet,
+        block_hash: [9; 32],
+        block_height: 300,
+        utxo_count: 3,
+        utxo_commitment: SNAPSHOT_UTXO_COMMITMENT,
+    };
+    for utxo_count in &[3, 4] {
+        header.utxo_count = *utxo_count;
+        let mut snapshot = Vec::new();
+        header.write_to_stream(&mut snapshot).unwrap();
+        snapshot.extend_from_slice(SNAPSHOT);
+        std::fs::write(&path, &snapshot).unwrap();
+        let (sender, receiver) = futures_channel::mpsc::unbounded();
+        let result = block_on(stream.stream_to(sender));
+        let utxos = block_on(receiver.collect::<Vec<_>>());
+        assert_eq!(utxos.len(), 3);
+        assert_eq!(utxos[2].outpoint.vout, 7);
+        match (*utxo_count, result) {
+            (3, Ok(())) => {}
+            (4, Err(err)) => match err.kind() {
+                SnapshotVerificationFailed(_) => {}
+                kind => panic!("unexpected error {:?}", kind),
+            },
+            (_, result) => panic!("unexpected result {:?}", result),
+        }
     }
 }
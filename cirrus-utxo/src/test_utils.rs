@@ -0,0 +1,55 @@
+use crate::errors::{ErrorKind, Result};
+use hex_literal::hex;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Snapshot without header of three utxos: a coinbase P2PKH utxo [1; 32]:0
+/// at height 100 of 50 BCH, a P2SH utxo [2; 32]:1 at height 200 of 12345
+/// sats and a P2PKH utxo [3; 32]:7 at height 300 of 546 sats.
+pub(crate) const SNAPSHOT: &[u8] = &hex!("0101010101010101010101010101010101010101010101010101010101010101000000006400000100f2052a010000001900000076a914111111111111111111111111111111111111111188ac020202020202020202020202020202020202020202020202020202020202020201000000c8000000393000000000000017000000a9142222222222222222222222222222222222222222870303030303030303030303030303030303030303030303030303030303030303070000002c01000022020000000000001900000076a914333333333333333333333333333333333333333388ac");
+
+/// ECMH of the utxos of `SNAPSHOT`.
+pub(crate) const SNAPSHOT_UTXO_COMMITMENT: [u8; 32] =
+    hex!("a57439f1404a58ece9be767af128ea8fe7856c8db0c59dba83c628548e0b300a");
+
+/// Directory of a test, emptied when created and removed when dropped, also
+/// if the test panics.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new(name: &str) -> TempDir {
+        let path =
+            std::env::temp_dir().join(format!("cirrus-utxo-{}-test-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Reopens a store whose sled db was just dropped. sled's background threads
+/// release the db's file lock a moment after the drop, until then opening
+/// fails with an I/O error.
+pub(crate) fn reopen<T>(open: impl Fn() -> Result<T>) -> Result<T> {
+    for _ in 0..200 {
+        match open() {
+            Err(err) => match err.kind() {
+                ErrorKind::Db(sled::Error::Io(_)) => std::thread::sleep(Duration::from_millis(10)),
+                _ => return Err(err),
+            },
+            result => return result,
+        }
+    }
+    open()
+}
@@ -1,12 +1,154 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use cashcontracts::serialize::write_var_str;
 use cashcontracts::{tx_hash_to_hex, TxOutpoint};
+use cirrus_consensus::{join_token_prefix, split_token_prefix, TokenData};
+use cirrus_slp::{SlpToken, SlpTokenType};
+use std::{
+    io,
+    io::{Read, Write},
+};
+
+/// Set in `Utxo::flags` if the utxo is a coinbase output.
+pub const UTXO_FLAG_COINBASE: u8 = 0x01;
+/// Set in `Utxo::flags` if it's unknown whether the utxo holds SLP tokens,
+/// as it was read from a snapshot without them, or its tx is only valid
+/// SLP if such utxos it spends hold the right tokens.
+pub const UTXO_FLAG_SLP_INCONCLUSIVE: u8 = 0x02;
+
+/// Following each record of version 2 snapshots, the SLP status of the
+/// utxo, followed by the `SlpToken` for `SLP_STATUS_TOKEN`.
+const SLP_STATUS_NONE: u8 = 0;
+const SLP_STATUS_INCONCLUSIVE: u8 = 1;
+const SLP_STATUS_TOKEN: u8 = 2;
+const SLP_TOKEN_SIZE: usize = 32 + 2 + 8 + 1;
 
 #[derive(Clone, Debug)]
 pub struct Utxo {
     pub outpoint: TxOutpoint,
     pub amount: u64,
+    /// Locking script, without the token prefix.
     pub script: Vec<u8>,
+    /// CashTokens held by the utxo.
+    pub token: Option<TokenData>,
     pub block_height: i32,
     pub flags: u8,
+    /// Validated SLP tokens held by the utxo.
+    pub slp_token: Option<SlpToken>,
+}
+
+impl Utxo {
+    /// Reads a record of the snapshot format: tx hash, vout, height with
+    /// the flags in the top byte, amount, script length and script, which
+    /// starts with the token prefix if the utxo holds tokens. As the record
+    /// doesn't tell, the utxo's SLP tokens are inconclusive.
+    pub fn from_stream(stream: &mut impl Read) -> io::Result<Utxo> {
+        let mut tx_hash = [0; 32];
+        stream.read_exact(&mut tx_hash)?;
+        let vout = stream.read_u32::<LittleEndian>()?;
+        let height_flagged = stream.read_i32::<LittleEndian>()?;
+        let flags = ((height_flagged & 0x0100_0000) >> 24) as u8 | UTXO_FLAG_SLP_INCONCLUSIVE;
+        let block_height = height_flagged & 0x00ff_ffff;
+        let amount = stream.read_u64::<LittleEndian>()?;
+        let script_len = stream.read_u32::<LittleEndian>()? as usize;
+        let mut script_field = vec![0; script_len];
+        stream.read_exact(&mut script_field)?;
+        let (token, script) = split_token_prefix(&script_field);
+        Ok(Utxo {
+            outpoint: TxOutpoint { tx_hash, vout },
+            amount,
+            script: script.to_vec(),
+            token,
+            block_height,
+            flags,
+            slp_token: None,
+        })
+    }
+
+    pub fn write_to_stream(&self, stream: &mut impl Write) -> io::Result<()> {
+        let flags = i32::from(self.flags & UTXO_FLAG_COINBASE);
+        stream.write_all(&self.outpoint.tx_hash)?;
+        stream.write_u32::<LittleEndian>(self.outpoint.vout)?;
+        stream.write_i32::<LittleEndian>(self.block_height | (flags << 24))?;
+        stream.write_u64::<LittleEndian>(self.amount)?;
+        let script_field = self.script_field();
+        stream.write_u32::<LittleEndian>(script_field.len() as u32)?;
+        stream.write_all(&script_field)?;
+        Ok(())
+    }
+
+    /// Size of the SLP status of version 2 snapshots starting with `status`.
+    pub(crate) fn slp_status_size(status: u8) -> usize {
+        match status {
+            SLP_STATUS_TOKEN => 1 + SLP_TOKEN_SIZE,
+            _ => 1,
+        }
+    }
+
+    /// Reads the SLP status following the record in version 2 snapshots.
+    pub fn read_slp_status(&mut self, stream: &mut impl Read) -> io::Result<()> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        self.flags &= !UTXO_FLAG_SLP_INCONCLUSIVE;
+        self.slp_token = None;
+        match stream.read_u8()? {
+            SLP_STATUS_NONE => {}
+            SLP_STATUS_INCONCLUSIVE => self.flags |= UTXO_FLAG_SLP_INCONCLUSIVE,
+            SLP_STATUS_TOKEN => {
+                let mut token_id = [0; 32];
+                stream.read_exact(&mut token_id)?;
+                let token_type = SlpTokenType::from_u16(stream.read_u16::<LittleEndian>()?)
+                    .ok_or_else(|| invalid("unknown SLP token type"))?;
+                self.slp_token = Some(SlpToken {
+                    token_id,
+                    token_type,
+                    amount: stream.read_u64::<LittleEndian>()?,
+                    is_mint_baton: stream.read_u8()? != 0,
+                });
+            }
+            _ => return Err(invalid("unknown SLP status")),
+        }
+        Ok(())
+    }
+
+    pub fn write_slp_status(&self, stream: &mut impl Write) -> io::Result<()> {
+        match &self.slp_token {
+            Some(token) => {
+                stream.write_u8(SLP_STATUS_TOKEN)?;
+                stream.write_all(&token.token_id)?;
+                stream.write_u16::<LittleEndian>(token.token_type.to_u16())?;
+                stream.write_u64::<LittleEndian>(token.amount)?;
+                stream.write_u8(token.is_mint_baton as u8)?;
+            }
+            None if self.is_slp_inconclusive() => stream.write_u8(SLP_STATUS_INCONCLUSIVE)?,
+            None => stream.write_u8(SLP_STATUS_NONE)?,
+        }
+        Ok(())
+    }
+
+    /// The script with the token prefix, as serialized in outputs.
+    pub fn script_field(&self) -> Vec<u8> {
+        join_token_prefix(self.token.as_ref(), &self.script)
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.flags & UTXO_FLAG_COINBASE != 0
+    }
+
+    pub fn is_slp_inconclusive(&self) -> bool {
+        self.flags & UTXO_FLAG_SLP_INCONCLUSIVE != 0
+    }
+
+    /// Serialization hashed into the UTXO set commitment: outpoint,
+    /// height * 2 + coinbase flag and the output, like BCHN's coin stats.
+    pub fn commitment_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + 4 + 4 + 8 + 9 + self.script.len());
+        bytes.extend_from_slice(&self.outpoint.tx_hash);
+        bytes.write_u32::<LittleEndian>(self.outpoint.vout).unwrap();
+        let height_coinbase = (self.block_height as u32) * 2 + self.is_coinbase() as u32;
+        bytes.write_u32::<LittleEndian>(height_coinbase).unwrap();
+        bytes.write_u64::<LittleEndian>(self.amount).unwrap();
+        write_var_str(&mut bytes, &self.script_field()).unwrap();
+        bytes
+    }
 }
 
 impl std::fmt::Display for Utxo {
@@ -19,8 +161,81 @@ impl std::fmt::Display for Utxo {
         )?;
         writeln!(f, " amount:       {}", self.amount)?;
         writeln!(f, " script:       {}", hex::encode(&self.script))?;
+        if let Some(token) = &self.token {
+            writeln!(f, " category:     {}", tx_hash_to_hex(&token.category))?;
+            writeln!(f, " ft_amount:    {}", token.amount)?;
+            writeln!(f, " commitment:   {}", hex::encode(token.commitment()))?;
+        }
         writeln!(f, " block_height: {}", self.block_height)?;
         writeln!(f, " flags:        {:x}", self.flags)?;
+        if let Some(token) = &self.slp_token {
+            writeln!(f, " token_id:     {}", tx_hash_to_hex(&token.token_id))?;
+            writeln!(f, " token_amount: {}", token.amount)?;
+            writeln!(f, " mint_baton:   {}", token.is_mint_baton)?;
+        }
         Ok(())
     }
 }
+
+#[test]
+fn test_token_utxo() {
+    use hex_literal::hex;
+
+    let record = hex!("0404040404040404040404040404040404040404040404040404040404040404020000002c010000220200000000000041000000efcccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc7202abcdfde80376a914333333333333333333333333333333333333333388ac");
+    let utxo = Utxo::from_stream(&mut &record[..]).unwrap();
+    let token = utxo.token.as_ref().unwrap();
+    assert_eq!(token.category, [0xcc; 32]);
+    assert_eq!(token.commitment(), &[0xab, 0xcd]);
+    assert_eq!(token.amount, 1000);
+    assert_eq!(
+        utxo.script,
+        hex!("76a914333333333333333333333333333333333333333388ac")
+    );
+    let mut written = Vec::new();
+    utxo.write_to_stream(&mut written).unwrap();
+    assert_eq!(&written[..], &record[..]);
+    assert!(utxo
+        .commitment_bytes()
+        .ends_with(&record[record.len() - 65..]));
+}
+
+#[test]
+fn test_slp_status() {
+    let mut utxo = Utxo {
+        outpoint: TxOutpoint {
+            tx_hash: [4; 32],
+            vout: 2,
+        },
+        amount: 546,
+        script: vec![0x51],
+        token: None,
+        block_height: 300,
+        flags: UTXO_FLAG_COINBASE | UTXO_FLAG_SLP_INCONCLUSIVE,
+        slp_token: None,
+    };
+    let token = SlpToken {
+        token_id: [5; 32],
+        token_type: SlpTokenType::Nft1Group,
+        amount: 1000,
+        is_mint_baton: true,
+    };
+    for slp_token in &[None, Some(token)] {
+        for &flags in &[UTXO_FLAG_COINBASE, UTXO_FLAG_SLP_INCONCLUSIVE] {
+            utxo.flags = flags;
+            utxo.slp_token = slp_token.clone();
+            let mut status = Vec::new();
+            utxo.write_slp_status(&mut status).unwrap();
+            assert_eq!(status.len(), Utxo::slp_status_size(status[0]));
+            let mut read = utxo.clone();
+            read.flags = UTXO_FLAG_SLP_INCONCLUSIVE;
+            read.slp_token = None;
+            read.read_slp_status(&mut &status[..]).unwrap();
+            assert_eq!(read.slp_token, utxo.slp_token);
+            assert_eq!(
+                read.is_slp_inconclusive(),
+                utxo.is_slp_inconclusive() && slp_token.is_none()
+            );
+        }
+    }
+    assert!(utxo.read_slp_status(&mut &[3][..]).is_err());
+}
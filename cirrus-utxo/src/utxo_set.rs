@@ -0,0 +1,1413 @@
+use crate::errors::{Error, ErrorKind::*, Result};
+use crate::snapshot_reader::{SnapshotHeader, UtxoReader, SNAPSHOT_VERSION};
+use crate::utxo::{Utxo, UTXO_FLAG_COINBASE, UTXO_FLAG_SLP_INCONCLUSIVE};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use cashcontracts::{tx_hash_to_hex, TxOutpoint};
+use cirrus_consensus::{split_token_prefix, Block, Transaction};
+use cirrus_crypto::{sha256, Address, EcMultiset, Network};
+use cirrus_slp::{check_token_inputs, SlpMessage, SlpToken, SlpTokenType};
+use error_chain::bail;
+use futures::io::AsyncRead;
+use futures::stream::StreamExt;
+use sled::transaction::{
+    abort, ConflictableTransactionResult, TransactionError, TransactionalTree,
+    UnabortableTransactionError,
+};
+use sled::Transactional;
+use std::io::Write;
+use std::ops::Bound;
+use std::path::Path;
+
+const TOTALS_KEY: &[u8] = b"totals";
+const COMMITMENT_KEY: &[u8] = b"commitment";
+const BEST_BLOCK_KEY: &[u8] = b"best_block";
+const OP_RETURN: u8 = 0x6a;
+const LOAD_BATCH_SIZE: usize = 10_000;
+const ENTRY_HEADER_SIZE: usize = 8 + 4 + 1;
+/// Set in the flags of an entry if the SLP token precedes the script.
+const ENTRY_FLAG_SLP: u8 = 0x80;
+const ENTRY_SLP_TOKEN_SIZE: usize = 32 + 2 + 8 + 1;
+
+/// Number of utxos in a `UtxoSet` and the sum of their amounts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UtxoSetTotals {
+    pub count: u64,
+    pub amount: u64,
+}
+
+/// Number of utxos tagged with an SLP token, the tokens held by them and
+/// the number of mint batons. Utxos whose tokens are inconclusive aren't
+/// counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SlpTokenTotals {
+    pub utxo_count: u64,
+    pub amount: u128,
+    pub mint_baton_count: u64,
+}
+
+/// UTXO set persisted in a sled database, keyed by outpoint and indexed
+/// by script hash and SLP token.
+pub struct UtxoSet {
+    db: sled::Db,
+    utxos: sled::Tree,
+    script_index: sled::Tree,
+    token_index: sled::Tree,
+    token_script_index: sled::Tree,
+    undo: sled::Tree,
+    meta: sled::Tree,
+}
+
+/// The trees storing utxos, within a transaction.
+struct UtxoTrees<'a> {
+    utxos: &'a TransactionalTree,
+    script_index: &'a TransactionalTree,
+    token_index: &'a TransactionalTree,
+    token_script_index: &'a TransactionalTree,
+}
+
+/// Electrum-style script hash: the sha256 of the output script.
+pub fn script_hash(script: &[u8]) -> [u8; 32] {
+    sha256(script)
+}
+
+/// Script hash followed by the outpoint key; the value is the amount.
+fn script_index_key(utxo: &Utxo) -> [u8; 68] {
+    let mut key = [0; 68];
+    key[..32].copy_from_slice(&script_hash(&utxo.script));
+    key[32..].copy_from_slice(&outpoint_key(&utxo.outpoint));
+    key
+}
+
+/// Token id followed by the outpoint key.
+fn token_index_key(token: &SlpToken, utxo: &Utxo) -> [u8; 68] {
+    let mut key = [0; 68];
+    key[..32].copy_from_slice(&token.token_id);
+    key[32..].copy_from_slice(&outpoint_key(&utxo.outpoint));
+    key
+}
+
+/// Token id followed by the script index key.
+fn token_script_index_key(token: &SlpToken, utxo: &Utxo) -> [u8; 100] {
+    let mut key = [0; 100];
+    key[..32].copy_from_slice(&token.token_id);
+    key[32..].copy_from_slice(&script_index_key(utxo));
+    key
+}
+
+/// Value of the token indices: token amount and mint baton flag.
+fn encode_token_value(token: &SlpToken) -> [u8; 9] {
+    let mut value = [0; 9];
+    LittleEndian::write_u64(&mut value[..8], token.amount);
+    value[8] = token.is_mint_baton as u8;
+    value
+}
+
+fn token_script_prefix(token_id: &[u8; 32], script_hash: &[u8; 32]) -> [u8; 64] {
+    let mut prefix = [0; 64];
+    prefix[..32].copy_from_slice(token_id);
+    prefix[32..].copy_from_slice(script_hash);
+    prefix
+}
+
+/// tx hash followed by the big endian vout, so outputs of a tx are adjacent.
+pub(crate) fn outpoint_key(outpoint: &TxOutpoint) -> [u8; 36] {
+    let mut key = [0; 36];
+    key[..32].copy_from_slice(&outpoint.tx_hash);
+    BigEndian::write_u32(&mut key[32..], outpoint.vout);
+    key
+}
+
+/// Amount, height and flags, followed by the SLP token if there is one,
+/// and the script with the token prefix.
+fn encode_entry(utxo: &Utxo) -> Vec<u8> {
+    let mut entry = vec![0; ENTRY_HEADER_SIZE];
+    LittleEndian::write_u64(&mut entry[..8], utxo.amount);
+    LittleEndian::write_i32(&mut entry[8..12], utxo.block_height);
+    entry[12] = utxo.flags & !ENTRY_FLAG_SLP;
+    if let Some(token) = &utxo.slp_token {
+        entry[12] |= ENTRY_FLAG_SLP;
+        let mut token_type = [0; 2];
+        LittleEndian::write_u16(&mut token_type, token.token_type.to_u16());
+        entry.extend_from_slice(&token.token_id);
+        entry.extend_from_slice(&token_type);
+        entry.extend_from_slice(&encode_token_value(token));
+    }
+    entry.extend_from_slice(&utxo.script_field());
+    entry
+}
+
+fn decode_entry(key: &[u8], entry: &[u8]) -> Result<Utxo> {
+    let corrupt = || CorruptUtxoEntry(hex::encode(key));
+    if key.len() != 36 || entry.len() < ENTRY_HEADER_SIZE {
+        bail!(corrupt());
+    }
+    let mut tx_hash = [0; 32];
+    tx_hash.copy_from_slice(&key[..32]);
+    let flags = entry[12];
+    let mut script_start = ENTRY_HEADER_SIZE;
+    let slp_token = if flags & ENTRY_FLAG_SLP != 0 {
+        script_start += ENTRY_SLP_TOKEN_SIZE;
+        let token = match entry.get(ENTRY_HEADER_SIZE..script_start) {
+            Some(token) => token,
+            None => bail!(corrupt()),
+        };
+        let mut token_id = [0; 32];
+        token_id.copy_from_slice(&token[..32]);
+        let token_type = match SlpTokenType::from_u16(LittleEndian::read_u16(&token[32..34])) {
+            Some(token_type) => token_type,
+            None => bail!(corrupt()),
+        };
+        Some(SlpToken {
+            token_id,
+            token_type,
+            amount: LittleEndian::read_u64(&token[34..42]),
+            is_mint_baton: token[42] != 0,
+        })
+    } else {
+        None
+    };
+    let (token, script) = split_token_prefix(&entry[script_start..]);
+    Ok(Utxo {
+        outpoint: TxOutpoint {
+            tx_hash,
+            vout: BigEndian::read_u32(&key[32..]),
+        },
+        amount: LittleEndian::read_u64(&entry[..8]),
+        block_height: LittleEndian::read_i32(&entry[8..12]),
+        flags: flags & !ENTRY_FLAG_SLP,
+        script: script.to_vec(),
+        token,
+        slp_token,
+    })
+}
+
+fn decode_totals(bytes: Option<&[u8]>) -> UtxoSetTotals {
+    match bytes {
+        Some(bytes) if bytes.len() == 16 => UtxoSetTotals {
+            count: LittleEndian::read_u64(&bytes[..8]),
+            amount: LittleEndian::read_u64(&bytes[8..]),
+        },
+        _ => UtxoSetTotals::default(),
+    }
+}
+
+fn encode_totals(totals: UtxoSetTotals) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    LittleEndian::write_u64(&mut bytes[..8], totals.count);
+    LittleEndian::write_u64(&mut bytes[8..], totals.amount);
+    bytes
+}
+
+fn decode_best_block(bytes: Option<&[u8]>) -> Option<([u8; 32], i32)> {
+    match bytes {
+        Some(bytes) if bytes.len() == 36 => {
+            let mut block_hash = [0; 32];
+            block_hash.copy_from_slice(&bytes[..32]);
+            Some((block_hash, LittleEndian::read_i32(&bytes[32..])))
+        }
+        _ => None,
+    }
+}
+
+fn encode_best_block(block_hash: &[u8; 32], height: i32) -> [u8; 36] {
+    let mut bytes = [0; 36];
+    bytes[..32].copy_from_slice(block_hash);
+    LittleEndian::write_i32(&mut bytes[32..], height);
+    bytes
+}
+
+/// Undo data of a block: the utxos spent by it, in order, each as outpoint
+/// key, entry length and entry.
+fn encode_undo(spent_utxos: &[Utxo]) -> Vec<u8> {
+    let mut undo = Vec::new();
+    for utxo in spent_utxos {
+        let entry = encode_entry(utxo);
+        let mut entry_len = [0; 4];
+        LittleEndian::write_u32(&mut entry_len, entry.len() as u32);
+        undo.extend_from_slice(&outpoint_key(&utxo.outpoint));
+        undo.extend_from_slice(&entry_len);
+        undo.extend_from_slice(&entry);
+    }
+    undo
+}
+
+fn decode_undo(mut undo: &[u8]) -> Option<Vec<Utxo>> {
+    let mut spent_utxos = Vec::new();
+    while !undo.is_empty() {
+        if undo.len() < 36 + 4 {
+            return None;
+        }
+        let entry_len = LittleEndian::read_u32(&undo[36..40]) as usize;
+        let entry = undo.get(40..40 + entry_len)?;
+        spent_utxos.push(decode_entry(&undo[..36], entry).ok()?);
+        undo = &undo[40 + entry_len..];
+    }
+    Some(spent_utxos)
+}
+
+/// The utxos created by `tx`. OP_RETURN outputs are provably unspendable
+/// and never enter the set.
+fn tx_utxos(tx: &Transaction, height: i32) -> Vec<Utxo> {
+    let tx_hash = tx.hash();
+    let flags = if tx.is_coinbase() {
+        UTXO_FLAG_COINBASE
+    } else {
+        0
+    };
+    tx.outputs
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| output.script.first() != Some(&OP_RETURN))
+        .map(|(vout, output)| Utxo {
+            outpoint: TxOutpoint {
+                tx_hash,
+                vout: vout as u32,
+            },
+            amount: output.value,
+            script: output.script.clone(),
+            token: output.token.clone(),
+            block_height: height,
+            flags,
+            slp_token: None,
+        })
+        .collect()
+}
+
+/// Tags the utxos created by `tx` with their SLP tokens, if `tx` is a
+/// valid SLP tx given the utxos it spends. If it's only valid if spent
+/// utxos with inconclusive tokens hold the right ones, so are its utxos.
+fn tag_slp_tokens(tx: &Transaction, spent_utxos: &[Utxo], tx_utxos: &mut [Utxo]) {
+    let message = match SlpMessage::from_tx(tx) {
+        Ok(message) => message,
+        Err(_) => return,
+    };
+    let tx_hash = tx.hash();
+    let input_tokens = spent_utxos.iter().map(|utxo| utxo.slp_token.clone());
+    if !check_token_inputs(&message, &tx_hash, input_tokens) {
+        if spent_utxos.iter().any(Utxo::is_slp_inconclusive) {
+            for utxo in tx_utxos {
+                utxo.flags |= UTXO_FLAG_SLP_INCONCLUSIVE;
+            }
+        }
+        return;
+    }
+    for utxo in tx_utxos {
+        utxo.slp_token = message.output_token(&tx_hash, utxo.outpoint.vout as usize);
+    }
+}
+
+fn transaction_error(err: TransactionError<Error>) -> Error {
+    match err {
+        TransactionError::Storage(err) => err.into(),
+        TransactionError::Abort(err) => err,
+    }
+}
+
+fn decode_multiset(bytes: Option<&[u8]>) -> Result<EcMultiset> {
+    let bytes = match bytes {
+        Some(bytes) => bytes,
+        None => return Ok(EcMultiset::new()),
+    };
+    let mut coordinates = [0; 64];
+    if bytes.len() != coordinates.len() {
+        bail!(CorruptUtxoEntry(hex::encode(COMMITMENT_KEY)));
+    }
+    coordinates.copy_from_slice(bytes);
+    match EcMultiset::from_bytes(&coordinates) {
+        Some(multiset) => Ok(multiset),
+        None => bail!(CorruptUtxoEntry(hex::encode(COMMITMENT_KEY))),
+    }
+}
+
+/// Totals and ECMH commitment of the set, updated along with its utxos.
+struct SetState {
+    totals: UtxoSetTotals,
+    multiset: EcMultiset,
+}
+
+impl SetState {
+    fn read(meta: &TransactionalTree) -> ConflictableTransactionResult<SetState, Error> {
+        let totals = decode_totals(meta.get(TOTALS_KEY)?.as_deref());
+        match decode_multiset(meta.get(COMMITMENT_KEY)?.as_deref()) {
+            Ok(multiset) => Ok(SetState { totals, multiset }),
+            Err(err) => abort(err),
+        }
+    }
+
+    fn write(
+        &self,
+        meta: &TransactionalTree,
+    ) -> std::result::Result<(), UnabortableTransactionError> {
+        meta.insert(TOTALS_KEY, &encode_totals(self.totals)[..])?;
+        meta.insert(COMMITMENT_KEY, &self.multiset.to_bytes()[..])?;
+        Ok(())
+    }
+
+    fn add(&mut self, utxo: &Utxo) {
+        self.totals.count += 1;
+        self.totals.amount += utxo.amount;
+        self.multiset.insert(&utxo.commitment_bytes());
+    }
+
+    fn remove(&mut self, utxo: &Utxo) {
+        self.totals.count -= 1;
+        self.totals.amount -= utxo.amount;
+        self.multiset.remove(&utxo.commitment_bytes());
+    }
+}
+
+fn index_utxo(
+    trees: &UtxoTrees,
+    utxo: &Utxo,
+) -> std::result::Result<(), UnabortableTransactionError> {
+    trees
+        .script_index
+        .insert(&script_index_key(utxo)[..], &utxo.amount.to_le_bytes()[..])?;
+    if let Some(token) = &utxo.slp_token {
+        let value = encode_token_value(token);
+        trees
+            .token_index
+            .insert(&token_index_key(token, utxo)[..], &value[..])?;
+        trees
+            .token_script_index
+            .insert(&token_script_index_key(token, utxo)[..], &value[..])?;
+    }
+    Ok(())
+}
+
+fn unindex_utxo(
+    trees: &UtxoTrees,
+    utxo: &Utxo,
+) -> std::result::Result<(), UnabortableTransactionError> {
+    trees.script_index.remove(&script_index_key(utxo)[..])?;
+    if let Some(token) = &utxo.slp_token {
+        trees
+            .token_index
+            .remove(&token_index_key(token, utxo)[..])?;
+        trees
+            .token_script_index
+            .remove(&token_script_index_key(token, utxo)[..])?;
+    }
+    Ok(())
+}
+
+/// Inserts utxos within a transaction, updating `state`. Existing utxos
+/// are replaced.
+fn insert_utxos(
+    trees: &UtxoTrees,
+    state: &mut SetState,
+    new_utxos: &[Utxo],
+) -> ConflictableTransactionResult<(), Error> {
+    for utxo in new_utxos {
+        let key = outpoint_key(&utxo.outpoint);
+        if let Some(old_entry) = trees.utxos.insert(&key[..], encode_entry(utxo))? {
+            match decode_entry(&key, &old_entry) {
+                Ok(old_utxo) => {
+                    unindex_utxo(trees, &old_utxo)?;
+                    state.remove(&old_utxo);
+                }
+                Err(err) => return abort(err),
+            }
+        }
+        index_utxo(trees, utxo)?;
+        state.add(utxo);
+    }
+    Ok(())
+}
+
+/// Removes a utxo within a transaction, updating `state`. Aborts if it
+/// doesn't exist.
+fn spend_utxo(
+    trees: &UtxoTrees,
+    state: &mut SetState,
+    outpoint: &TxOutpoint,
+) -> ConflictableTransactionResult<Utxo, Error> {
+    let key = outpoint_key(outpoint);
+    let entry = match trees.utxos.remove(&key[..])? {
+        Some(entry) => entry,
+        None => {
+            return abort(
+                MissingUtxo(format!(
+                    "{}:{}",
+                    tx_hash_to_hex(&outpoint.tx_hash),
+                    outpoint.vout
+                ))
+                .into(),
+            )
+        }
+    };
+    let utxo = match decode_entry(&key, &entry) {
+        Ok(utxo) => utxo,
+        Err(err) => return abort(err),
+    };
+    unindex_utxo(trees, &utxo)?;
+    state.remove(&utxo);
+    Ok(utxo)
+}
+
+impl UtxoSet {
+    /// Opens the set at `path`, creating it if it doesn't exist yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let db = sled::open(path)?;
+        let utxos = db.open_tree(b"utxos")?;
+        let script_index = db.open_tree(b"script_index")?;
+        let token_index = db.open_tree(b"token_index")?;
+        let token_script_index = db.open_tree(b"token_script_index")?;
+        let undo = db.open_tree(b"undo")?;
+        let meta = db.open_tree(b"meta")?;
+        Ok(UtxoSet {
+            db,
+            utxos,
+            script_index,
+            token_index,
+            token_script_index,
+            undo,
+            meta,
+        })
+    }
+
+    pub fn get(&self, outpoint: &TxOutpoint) -> Result<Option<Utxo>> {
+        let key = outpoint_key(outpoint);
+        match self.utxos.get(&key[..])? {
+            Some(entry) => Ok(Some(decode_entry(&key, &entry)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn contains(&self, outpoint: &TxOutpoint) -> Result<bool> {
+        Ok(self.utxos.contains_key(&outpoint_key(outpoint)[..])?)
+    }
+
+    pub fn totals(&self) -> Result<UtxoSetTotals> {
+        Ok(decode_totals(self.meta.get(TOTALS_KEY)?.as_deref()))
+    }
+
+    /// ECMH of `Utxo::commitment_bytes` of all utxos, maintained as utxos
+    /// are added and removed.
+    pub fn commitment(&self) -> Result<[u8; 32]> {
+        Ok(decode_multiset(self.meta.get(COMMITMENT_KEY)?.as_deref())?.hash())
+    }
+
+    /// Number and total amount of the utxos with the given script hash.
+    pub fn script_balance(&self, script_hash: &[u8; 32]) -> Result<UtxoSetTotals> {
+        let mut balance = UtxoSetTotals::default();
+        for entry in self.script_index.scan_prefix(&script_hash[..]) {
+            let (_, amount) = entry?;
+            balance.count += 1;
+            balance.amount += LittleEndian::read_u64(&amount);
+        }
+        Ok(balance)
+    }
+
+    /// Up to `limit` utxos with the given script hash, ordered by outpoint.
+    /// Pass the last outpoint of the previous page as `after` to continue.
+    pub fn script_utxos(
+        &self,
+        script_hash: &[u8; 32],
+        after: Option<&TxOutpoint>,
+        limit: usize,
+    ) -> Result<Vec<Utxo>> {
+        self.index_utxos(&self.script_index, script_hash, after, limit)
+    }
+
+    /// Up to `limit` utxos of `index` whose key starts with `prefix`,
+    /// followed by the outpoint key, continuing after `after`.
+    fn index_utxos(
+        &self,
+        index: &sled::Tree,
+        prefix: &[u8],
+        after: Option<&TxOutpoint>,
+        limit: usize,
+    ) -> Result<Vec<Utxo>> {
+        let mut start = prefix.to_vec();
+        let start = match after {
+            Some(outpoint) => {
+                start.extend_from_slice(&outpoint_key(outpoint));
+                Bound::Excluded(start)
+            }
+            None => Bound::Included(start),
+        };
+        let mut utxos = Vec::new();
+        for entry in index.range((start, Bound::Unbounded)) {
+            let (key, _) = entry?;
+            if !key.starts_with(prefix) || utxos.len() == limit {
+                break;
+            }
+            let utxo_key = &key[prefix.len()..];
+            if let Some(entry) = self.utxos.get(utxo_key)? {
+                utxos.push(decode_entry(utxo_key, &entry)?);
+            }
+        }
+        Ok(utxos)
+    }
+
+    fn token_index_totals(&self, index: &sled::Tree, prefix: &[u8]) -> Result<SlpTokenTotals> {
+        let mut totals = SlpTokenTotals::default();
+        for entry in index.scan_prefix(prefix) {
+            let (_, value) = entry?;
+            totals.utxo_count += 1;
+            totals.amount += u128::from(LittleEndian::read_u64(&value[..8]));
+            totals.mint_baton_count += u64::from(value[8]);
+        }
+        Ok(totals)
+    }
+
+    /// Totals of the utxos holding the SLP token `token_id`.
+    pub fn token_totals(&self, token_id: &[u8; 32]) -> Result<SlpTokenTotals> {
+        self.token_index_totals(&self.token_index, token_id)
+    }
+
+    /// Up to `limit` utxos holding the SLP token `token_id`, ordered by
+    /// outpoint. Pass the last outpoint of the previous page as `after`.
+    pub fn token_utxos(
+        &self,
+        token_id: &[u8; 32],
+        after: Option<&TxOutpoint>,
+        limit: usize,
+    ) -> Result<Vec<Utxo>> {
+        self.index_utxos(&self.token_index, token_id, after, limit)
+    }
+
+    /// Totals of the utxos with the given script hash holding `token_id`.
+    pub fn token_script_totals(
+        &self,
+        token_id: &[u8; 32],
+        script_hash: &[u8; 32],
+    ) -> Result<SlpTokenTotals> {
+        self.token_index_totals(
+            &self.token_script_index,
+            &token_script_prefix(token_id, script_hash),
+        )
+    }
+
+    /// Like `token_utxos`, but only utxos with the given script hash.
+    pub fn token_script_utxos(
+        &self,
+        token_id: &[u8; 32],
+        script_hash: &[u8; 32],
+        after: Option<&TxOutpoint>,
+        limit: usize,
+    ) -> Result<Vec<Utxo>> {
+        let prefix = token_script_prefix(token_id, script_hash);
+        self.index_utxos(&self.token_script_index, &prefix, after, limit)
+    }
+
+    pub fn token_address_totals(
+        &self,
+        token_id: &[u8; 32],
+        address: &Address,
+    ) -> Result<SlpTokenTotals> {
+        self.token_script_totals(token_id, &script_hash(&address.to_script()))
+    }
+
+    pub fn token_address_utxos(
+        &self,
+        token_id: &[u8; 32],
+        address: &Address,
+        after: Option<&TxOutpoint>,
+        limit: usize,
+    ) -> Result<Vec<Utxo>> {
+        self.token_script_utxos(token_id, &script_hash(&address.to_script()), after, limit)
+    }
+
+    pub fn address_balance(&self, address: &Address) -> Result<UtxoSetTotals> {
+        self.script_balance(&script_hash(&address.to_script()))
+    }
+
+    pub fn address_utxos(
+        &self,
+        address: &Address,
+        after: Option<&TxOutpoint>,
+        limit: usize,
+    ) -> Result<Vec<Utxo>> {
+        self.script_utxos(&script_hash(&address.to_script()), after, limit)
+    }
+
+    /// Hash and height of the last applied block, None if no block has
+    /// been applied since the set was loaded.
+    pub fn best_block(&self) -> Result<Option<([u8; 32], i32)>> {
+        Ok(decode_best_block(self.meta.get(BEST_BLOCK_KEY)?.as_deref()))
+    }
+
+    /// Sets the best block, e.g. to the snapshot's block after loading it,
+    /// so that subsequent blocks can be applied.
+    pub fn set_best_block(&self, block_hash: &[u8; 32], height: i32) -> Result<()> {
+        self.meta
+            .insert(BEST_BLOCK_KEY, &encode_best_block(block_hash, height)[..])?;
+        Ok(())
+    }
+
+    /// Iterates over all utxos, ordered by outpoint.
+    pub fn iter(&self) -> impl Iterator<Item = Result<Utxo>> {
+        self.utxos.iter().map(|entry| {
+            let (key, entry) = entry?;
+            decode_entry(&key, &entry)
+        })
+    }
+
+    pub fn insert_batch(&self, new_utxos: &[Utxo]) -> Result<()> {
+        (
+            &self.utxos,
+            &self.script_index,
+            &self.token_index,
+            &self.token_script_index,
+            &self.meta,
+        )
+            .transaction(
+                |(utxos, script_index, token_index, token_script_index, meta)| {
+                    let trees = UtxoTrees {
+                        utxos,
+                        script_index,
+                        token_index,
+                        token_script_index,
+                    };
+                    let mut state = SetState::read(meta)?;
+                    insert_utxos(&trees, &mut state, new_utxos)?;
+                    state.write(meta)?;
+                    Ok(())
+                },
+            )
+            .map_err(transaction_error)
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        self.utxos.clear()?;
+        self.script_index.clear()?;
+        self.token_index.clear()?;
+        self.token_script_index.clear()?;
+        self.undo.clear()?;
+        self.meta.clear()?;
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Spends the inputs and adds the outputs of `block` at `height`, and
+    /// stores the spent utxos for `undo_block`. `block` has to build on the
+    /// best block, so it has to be known.
+    pub fn apply_block(&self, block: &Block, height: i32) -> Result<()> {
+        let block_hash = block.hash();
+        let new_utxos = block
+            .txs
+            .iter()
+            .map(|tx| tx_utxos(tx, height))
+            .collect::<Vec<_>>();
+        (
+            &self.utxos,
+            &self.script_index,
+            &self.token_index,
+            &self.token_script_index,
+            &self.undo,
+            &self.meta,
+        )
+            .transaction(
+                |(utxos, script_index, token_index, token_script_index, undo, meta)| {
+                    let trees = UtxoTrees {
+                        utxos,
+                        script_index,
+                        token_index,
+                        token_script_index,
+                    };
+                    match decode_best_block(meta.get(BEST_BLOCK_KEY)?.as_deref()) {
+                        Some((best_hash, best_height))
+                            if best_hash == block.header.prev_block
+                                && best_height + 1 == height => {}
+                        Some(_) => {
+                            return abort(BlockNotConnected(tx_hash_to_hex(&block_hash)).into())
+                        }
+                        None => return abort(UnknownBestBlock.into()),
+                    }
+                    let mut state = SetState::read(meta)?;
+                    let mut spent_utxos = Vec::new();
+                    for (tx, tx_utxos) in block.txs.iter().zip(&new_utxos) {
+                        let mut tx_utxos = tx_utxos.clone();
+                        if !tx.is_coinbase() {
+                            let num_spent = spent_utxos.len();
+                            for input in tx.inputs.iter() {
+                                spent_utxos.push(spend_utxo(&trees, &mut state, &input.prev_out)?);
+                            }
+                            tag_slp_tokens(tx, &spent_utxos[num_spent..], &mut tx_utxos);
+                        }
+                        insert_utxos(&trees, &mut state, &tx_utxos)?;
+                    }
+                    undo.insert(&block_hash[..], encode_undo(&spent_utxos))?;
+                    state.write(meta)?;
+                    meta.insert(BEST_BLOCK_KEY, &encode_best_block(&block_hash, height)[..])?;
+                    Ok(())
+                },
+            )
+            .map_err(transaction_error)
+    }
+
+    /// Reverts `block`, which has to be the best block, e.g. during a reorg.
+    pub fn undo_block(&self, block: &Block) -> Result<()> {
+        let block_hash = block.hash();
+        let new_utxos = block
+            .txs
+            .iter()
+            .map(|tx| tx_utxos(tx, 0))
+            .collect::<Vec<_>>();
+        (
+            &self.utxos,
+            &self.script_index,
+            &self.token_index,
+            &self.token_script_index,
+            &self.undo,
+            &self.meta,
+        )
+            .transaction(
+                |(utxos, script_index, token_index, token_script_index, undo, meta)| {
+                    let trees = UtxoTrees {
+                        utxos,
+                        script_index,
+                        token_index,
+                        token_script_index,
+                    };
+                    let height = match decode_best_block(meta.get(BEST_BLOCK_KEY)?.as_deref()) {
+                        Some((best_hash, height)) if best_hash == block_hash => height,
+                        _ => return abort(BlockNotConnected(tx_hash_to_hex(&block_hash)).into()),
+                    };
+                    let invalid_undo = || InvalidUndoData(tx_hash_to_hex(&block_hash)).into();
+                    let mut spent_utxos = match undo.remove(&block_hash[..])? {
+                        Some(undo_data) => match decode_undo(&undo_data) {
+                            Some(spent_utxos) => spent_utxos,
+                            None => return abort(invalid_undo()),
+                        },
+                        None => return abort(invalid_undo()),
+                    };
+                    let mut state = SetState::read(meta)?;
+                    // Undo in reverse, so outputs spent within the block are
+                    // restored and then removed again.
+                    for (tx, tx_utxos) in block.txs.iter().zip(&new_utxos).rev() {
+                        for utxo in tx_utxos {
+                            spend_utxo(&trees, &mut state, &utxo.outpoint)?;
+                        }
+                        if tx.is_coinbase() {
+                            continue;
+                        }
+                        if spent_utxos.len() < tx.inputs.len() {
+                            return abort(invalid_undo());
+                        }
+                        let restored = spent_utxos.split_off(spent_utxos.len() - tx.inputs.len());
+                        insert_utxos(&trees, &mut state, &restored)?;
+                    }
+                    if !spent_utxos.is_empty() {
+                        return abort(invalid_undo());
+                    }
+                    state.write(meta)?;
+                    meta.insert(
+                        BEST_BLOCK_KEY,
+                        &encode_best_block(&block.header.prev_block, height - 1)[..],
+                    )?;
+                    Ok(())
+                },
+            )
+            .map_err(transaction_error)
+    }
+
+    /// Writes the set as a snapshot with header at the best block, which
+    /// can be read using `UtxoReader::with_header`. The SLP status of the
+    /// utxos is written along, but isn't covered by the utxo commitment.
+    /// The set must not be modified while writing.
+    pub fn write_snapshot(
+        &self,
+        network: Network,
+        writer: &mut impl Write,
+    ) -> Result<SnapshotHeader> {
+        let (block_hash, block_height) = match self.best_block()? {
+            Some(best_block) => best_block,
+            None => bail!(UnknownBestBlock),
+        };
+        let header = SnapshotHeader {
+            version: SNAPSHOT_VERSION,
+            network,
+            block_hash,
+            block_height,
+            utxo_count: self.totals()?.count,
+            utxo_commitment: self.commitment()?,
+        };
+        header.write_to_stream(writer)?;
+        for utxo in self.iter() {
+            let utxo = utxo?;
+            utxo.write_to_stream(writer)?;
+            utxo.write_slp_status(writer)?;
+        }
+        writer.flush()?;
+        Ok(header)
+    }
+
+    /// Replaces the contents of the set with the utxos of `snapshot`. If it
+    /// has a header, its block becomes the best block; otherwise, it has to
+    /// be set using `set_best_block` before applying blocks. If the snapshot
+    /// yields an error, the set is left empty. Returns the number of utxos
+    /// loaded.
+    pub async fn load_snapshot<R: AsyncRead + Unpin>(
+        &self,
+        snapshot: &mut UtxoReader<R>,
+    ) -> Result<u64> {
+        self.clear()?;
+        let mut batch = Vec::with_capacity(LOAD_BATCH_SIZE);
+        let mut num_loaded = 0;
+        while let Some(utxo) = snapshot.next().await {
+            match utxo {
+                Ok(utxo) => batch.push(utxo),
+                Err(err) => {
+                    self.clear()?;
+                    return Err(err);
+                }
+            }
+            if batch.len() == LOAD_BATCH_SIZE {
+                self.insert_batch(&batch)?;
+                num_loaded += batch.len() as u64;
+                batch.clear();
+            }
+        }
+        self.insert_batch(&batch)?;
+        num_loaded += batch.len() as u64;
+        if let Some(header) = snapshot.header() {
+            self.set_best_block(&header.block_hash, header.block_height)?;
+        }
+        self.flush()?;
+        Ok(num_loaded)
+    }
+}
+
+#[test]
+fn test_utxo_set() {
+    use crate::snapshot_reader::UtxoReader;
+    use crate::test_utils::{reopen, TempDir, SNAPSHOT, SNAPSHOT_UTXO_COMMITMENT};
+    use futures::executor::block_on;
+    use hex_literal::hex;
+
+    let dir = TempDir::new("set");
+    let path = dir.path();
+    let outpoint = TxOutpoint {
+        tx_hash: [2; 32],
+        vout: 1,
+    };
+    {
+        let utxo_set = UtxoSet::open(path).unwrap();
+        let num_loaded = block_on(utxo_set.load_snapshot(&mut UtxoReader::new(SNAPSHOT))).unwrap();
+        assert_eq!(num_loaded, 3);
+        assert_eq!(utxo_set.best_block().unwrap(), None);
+        assert_eq!(utxo_set.commitment().unwrap(), SNAPSHOT_UTXO_COMMITMENT);
+        let utxo = utxo_set.get(&outpoint).unwrap().unwrap();
+        assert_eq!(utxo.amount, 12345);
+        assert_eq!(utxo.block_height, 200);
+        assert!(utxo.is_slp_inconclusive());
+        assert_eq!(
+            utxo.script,
+            hex!("a914222222222222222222222222222222222222222287")
+        );
+        assert!(!utxo_set
+            .contains(&TxOutpoint {
+                tx_hash: [2; 32],
+                vout: 0
+            })
+            .unwrap());
+    }
+    {
+        let utxo_set = reopen(|| UtxoSet::open(path)).unwrap();
+        let totals = utxo_set.totals().unwrap();
+        assert_eq!(
+            totals,
+            UtxoSetTotals {
+                count: 3,
+                amount: 5_000_012_891
+            }
+        );
+        assert!(utxo_set.contains(&outpoint).unwrap());
+        // A truncated snapshot leaves the set empty
+        let result = block_on(utxo_set.load_snapshot(&mut UtxoReader::new(&SNAPSHOT[..200])));
+        assert!(result.is_err());
+        assert_eq!(utxo_set.totals().unwrap(), UtxoSetTotals::default());
+        assert!(!utxo_set.contains(&outpoint).unwrap());
+    }
+}
+
+#[test]
+fn test_apply_block() {
+    use crate::test_utils::TempDir;
+    use cirrus_consensus::{BlockHeader, TxInput, TxOutput};
+
+    let dir = TempDir::new("block");
+    let path = dir.path();
+    let utxo_set = UtxoSet::open(path).unwrap();
+    let snapshot_utxo = Utxo {
+        outpoint: TxOutpoint {
+            tx_hash: [2; 32],
+            vout: 1,
+        },
+        amount: 12345,
+        script: vec![0x51],
+        token: None,
+        block_height: 200,
+        flags: 0,
+        slp_token: None,
+    };
+    utxo_set
+        .insert_batch(std::slice::from_ref(&snapshot_utxo))
+        .unwrap();
+    let tx = |prev_out: TxOutpoint, values: &[u64]| Transaction {
+        version: 1,
+        inputs: vec![TxInput {
+            prev_out,
+            script: vec![],
+            sequence: 0xffff_ffff,
+        }],
+        outputs: values
+            .iter()
+            .map(|&value| TxOutput {
+                value,
+                script: vec![0x51],
+                token: None,
+            })
+            .collect(),
+        lock_time: 0,
+    };
+    let mut coinbase = tx(
+        TxOutpoint {
+            tx_hash: [0; 32],
+            vout: 0xffff_ffff,
+        },
+        &[5_000_000_000],
+    );
+    coinbase.outputs.push(TxOutput {
+        value: 0,
+        script: vec![OP_RETURN, 0x00],
+        token: None,
+    });
+    let tx1 = tx(snapshot_utxo.outpoint.clone(), &[10000, 2000]);
+    let tx2 = tx(
+        TxOutpoint {
+            tx_hash: tx1.hash(),
+            vout: 1,
+        },
+        &[1500],
+    );
+    let block = Block {
+        header: BlockHeader {
+            version: 1,
+            prev_block: [7; 32],
+            merkle_root: [0; 32],
+            timestamp: 0,
+            bits: 0,
+            nonce: 0,
+        },
+        txs: vec![coinbase.clone(), tx1.clone(), tx2.clone()],
+    };
+    let commitment_before = utxo_set.commitment().unwrap();
+    // blocks can only be applied once the best block is known
+    match utxo_set.apply_block(&block, 301).unwrap_err().kind() {
+        UnknownBestBlock => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    utxo_set.set_best_block(&[7; 32], 300).unwrap();
+    utxo_set.apply_block(&block, 301).unwrap();
+    assert_ne!(utxo_set.commitment().unwrap(), commitment_before);
+    assert_eq!(utxo_set.best_block().unwrap(), Some((block.hash(), 301)));
+    assert_eq!(
+        utxo_set.totals().unwrap(),
+        UtxoSetTotals {
+            count: 3,
+            amount: 5_000_011_500
+        }
+    );
+    let coinbase_outpoint = TxOutpoint {
+        tx_hash: coinbase.hash(),
+        vout: 0,
+    };
+    let coinbase_utxo = utxo_set.get(&coinbase_outpoint).unwrap().unwrap();
+    assert!(coinbase_utxo.is_coinbase());
+    assert_eq!(coinbase_utxo.block_height, 301);
+    assert!(!utxo_set.contains(&snapshot_utxo.outpoint).unwrap());
+    assert_eq!(
+        utxo_set.script_balance(&script_hash(&[0x51])).unwrap(),
+        utxo_set.totals().unwrap()
+    );
+    assert!(!utxo_set
+        .contains(&TxOutpoint {
+            tx_hash: coinbase.hash(),
+            vout: 1
+        })
+        .unwrap());
+
+    // The block doesn't build on itself, and its inputs are spent already
+    match utxo_set.apply_block(&block, 302).unwrap_err().kind() {
+        BlockNotConnected(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    let mut next_block = block.clone();
+    next_block.header.prev_block = block.hash();
+    match utxo_set.apply_block(&next_block, 302).unwrap_err().kind() {
+        MissingUtxo(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+
+    utxo_set.undo_block(&block).unwrap();
+    assert_eq!(utxo_set.best_block().unwrap(), Some(([7; 32], 300)));
+    assert_eq!(utxo_set.commitment().unwrap(), commitment_before);
+    assert_eq!(
+        utxo_set.script_balance(&script_hash(&[0x51])).unwrap(),
+        utxo_set.totals().unwrap()
+    );
+    assert_eq!(
+        utxo_set.totals().unwrap(),
+        UtxoSetTotals {
+            count: 1,
+            amount: 12345
+        }
+    );
+    let restored = utxo_set.get(&snapshot_utxo.outpoint).unwrap().unwrap();
+    assert_eq!(restored.amount, 12345);
+    assert_eq!(restored.block_height, 200);
+    assert!(!utxo_set.contains(&coinbase_outpoint).unwrap());
+}
+
+#[test]
+fn test_write_snapshot() {
+    use crate::snapshot_reader::{UtxoReader, SNAPSHOT_HEADER_SIZE};
+    use crate::test_utils::{TempDir, SNAPSHOT, SNAPSHOT_UTXO_COMMITMENT};
+    use futures::executor::block_on;
+
+    let dir = TempDir::new("write");
+    let path = dir.path();
+    let utxo_set = UtxoSet::open(path.join("a")).unwrap();
+    block_on(utxo_set.load_snapshot(&mut UtxoReader::new(SNAPSHOT))).unwrap();
+    let mut written = Vec::new();
+    match utxo_set.write_snapshot(Network::Mainnet, &mut written) {
+        Err(err) => match err.kind() {
+            UnknownBestBlock => {}
+            kind => panic!("unexpected error {:?}", kind),
+        },
+        Ok(_) => panic!("wrote snapshot without best block"),
+    }
+    utxo_set.set_best_block(&[9; 32], 300).unwrap();
+    let header = utxo_set
+        .write_snapshot(Network::Mainnet, &mut written)
+        .unwrap();
+    assert_eq!(header.version, SNAPSHOT_VERSION);
+    assert_eq!(header.utxo_count, 3);
+    assert_eq!(header.utxo_commitment, SNAPSHOT_UTXO_COMMITMENT);
+
+    let copy = UtxoSet::open(path.join("b")).unwrap();
+    let mut reader = UtxoReader::with_header(&written[..]);
+    block_on(copy.load_snapshot(&mut reader)).unwrap();
+    assert_eq!(reader.header(), Some(&header));
+    assert_eq!(copy.best_block().unwrap(), Some(([9; 32], 300)));
+    assert_eq!(copy.totals().unwrap(), utxo_set.totals().unwrap());
+    assert!(copy.iter().all(|utxo| utxo.unwrap().is_slp_inconclusive()));
+
+    // SLP tokens survive the round trip
+    let token = SlpToken {
+        token_id: [5; 32],
+        token_type: SlpTokenType::Fungible,
+        amount: 100,
+        is_mint_baton: false,
+    };
+    let mut token_utxo = utxo_set.iter().next().unwrap().unwrap();
+    token_utxo.flags &= !UTXO_FLAG_SLP_INCONCLUSIVE;
+    token_utxo.slp_token = Some(token.clone());
+    utxo_set.insert_batch(&[token_utxo.clone()]).unwrap();
+    let mut written = Vec::new();
+    let header = utxo_set
+        .write_snapshot(Network::Mainnet, &mut written)
+        .unwrap();
+    assert_eq!(header.utxo_commitment, SNAPSHOT_UTXO_COMMITMENT);
+    block_on(copy.load_snapshot(&mut UtxoReader::with_header(&written[..]))).unwrap();
+    let copied = copy.get(&token_utxo.outpoint).unwrap().unwrap();
+    assert_eq!(copied.slp_token, Some(token.clone()));
+    assert!(!copied.is_slp_inconclusive());
+    assert_eq!(copy.token_totals(&token.token_id).unwrap().amount, 100);
+
+    // The header has to match the utxos
+    let mut tampered = written.clone();
+    tampered[SNAPSHOT_HEADER_SIZE - 33] ^= 1;
+    let result = block_on(copy.load_snapshot(&mut UtxoReader::with_header(&tampered[..])));
+    match result.unwrap_err().kind() {
+        SnapshotVerificationFailed(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn test_script_index() {
+    use crate::snapshot_reader::UtxoReader;
+    use crate::test_utils::{TempDir, SNAPSHOT};
+    use cirrus_crypto::AddressType;
+    use futures::executor::block_on;
+    use hex_literal::hex;
+
+    let dir = TempDir::new("index");
+    let path = dir.path();
+    let utxo_set = UtxoSet::open(path).unwrap();
+    block_on(utxo_set.load_snapshot(&mut UtxoReader::new(SNAPSHOT))).unwrap();
+    let address = Address::from_hash(Network::Mainnet, AddressType::P2PKH, &[0x11; 20]).unwrap();
+    let utxo = |vout, amount, script: &[u8]| Utxo {
+        outpoint: TxOutpoint {
+            tx_hash: [4; 32],
+            vout,
+        },
+        amount,
+        script: script.to_vec(),
+        token: None,
+        block_height: 300,
+        flags: 0,
+        slp_token: None,
+    };
+    utxo_set
+        .insert_batch(&[
+            utxo(0, 1000, &address.to_script()),
+            utxo(1, 2000, &address.to_script()),
+        ])
+        .unwrap();
+    assert_eq!(
+        utxo_set.address_balance(&address).unwrap(),
+        UtxoSetTotals {
+            count: 3,
+            amount: 5_000_003_000
+        }
+    );
+    let page = utxo_set.address_utxos(&address, None, 2).unwrap();
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0].outpoint.tx_hash, [1; 32]);
+    assert_eq!(page[1].amount, 1000);
+    let page = utxo_set
+        .address_utxos(&address, Some(&page[1].outpoint), 2)
+        .unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].amount, 2000);
+    assert!(utxo_set
+        .address_utxos(&address, Some(&page[0].outpoint), 2)
+        .unwrap()
+        .is_empty());
+
+    let p2sh_script = hex!("a914222222222222222222222222222222222222222287");
+    assert_eq!(
+        utxo_set.script_balance(&script_hash(&p2sh_script)).unwrap(),
+        UtxoSetTotals {
+            count: 1,
+            amount: 12345
+        }
+    );
+    // Replacing a utxo moves it to the new script's index
+    utxo_set
+        .insert_batch(&[utxo(0, 1000, &p2sh_script)])
+        .unwrap();
+    assert_eq!(
+        utxo_set.script_balance(&script_hash(&p2sh_script)).unwrap(),
+        UtxoSetTotals {
+            count: 2,
+            amount: 13345
+        }
+    );
+    assert_eq!(utxo_set.address_balance(&address).unwrap().count, 2);
+}
+
+#[test]
+fn test_token_index() {
+    use crate::test_utils::TempDir;
+    use cirrus_consensus::{BlockHeader, TxInput, TxOutput};
+    use cirrus_crypto::AddressType;
+
+    let dir = TempDir::new("token");
+    let path = dir.path();
+    let utxo_set = UtxoSet::open(path).unwrap();
+    let funding_outpoint = TxOutpoint {
+        tx_hash: [2; 32],
+        vout: 1,
+    };
+    utxo_set
+        .insert_batch(&[Utxo {
+            outpoint: funding_outpoint.clone(),
+            amount: 12345,
+            script: vec![0x51],
+            token: None,
+            block_height: 200,
+            flags: 0,
+            slp_token: None,
+        }])
+        .unwrap();
+    let address = Address::from_hash(Network::Mainnet, AddressType::P2PKH, &[0x11; 20]).unwrap();
+    let tx = |prev_out: TxOutpoint, op_return: String| Transaction {
+        version: 1,
+        inputs: vec![TxInput {
+            prev_out,
+            script: vec![],
+            sequence: 0xffff_ffff,
+        }],
+        outputs: vec![
+            TxOutput {
+                value: 0,
+                script: hex::decode(op_return).unwrap(),
+                token: None,
+            },
+            TxOutput {
+                value: 546,
+                script: address.to_script(),
+                token: None,
+            },
+            TxOutput {
+                value: 546,
+                script: vec![0x51],
+                token: None,
+            },
+        ],
+        lock_time: 0,
+    };
+    let block = |prev_block: [u8; 32], txs: Vec<Transaction>| Block {
+        header: BlockHeader {
+            version: 1,
+            prev_block,
+            merkle_root: [0; 32],
+            timestamp: 0,
+            bits: 0,
+            nonce: 0,
+        },
+        txs,
+    };
+    let coinbase = |height: u8| Transaction {
+        version: 1,
+        inputs: vec![TxInput {
+            prev_out: TxOutpoint {
+                tx_hash: [0; 32],
+                vout: 0xffff_ffff,
+            },
+            script: vec![height],
+            sequence: 0xffff_ffff,
+        }],
+        outputs: vec![],
+        lock_time: 0,
+    };
+    let genesis = tx(
+        funding_outpoint,
+        "6a04534c500001010747454e455349530341424301414c004c00010001020800000000000000\
+         64"
+        .to_string(),
+    );
+    let token_id = genesis.hash();
+    let send = |prev_out: TxOutpoint, amounts: &[u64]| {
+        let mut token_id_le = token_id;
+        token_id_le.reverse();
+        let mut script = format!("6a04534c500001010453454e4420{}", hex::encode(token_id_le));
+        for amount in amounts {
+            script.push_str(&format!("08{:016x}", amount));
+        }
+        tx(prev_out, script)
+    };
+    let send1 = send(
+        TxOutpoint {
+            tx_hash: token_id,
+            vout: 1,
+        },
+        &[60, 40],
+    );
+    let block1 = block([7; 32], vec![coinbase(1), genesis.clone(), send1.clone()]);
+    utxo_set.set_best_block(&[7; 32], 300).unwrap();
+    utxo_set.apply_block(&block1, 301).unwrap();
+
+    let utxo = utxo_set
+        .get(&TxOutpoint {
+            tx_hash: send1.hash(),
+            vout: 1,
+        })
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        utxo.slp_token,
+        Some(SlpToken {
+            token_id,
+            token_type: SlpTokenType::Fungible,
+            amount: 60,
+            is_mint_baton: false,
+        })
+    );
+    assert_eq!(
+        utxo_set.token_totals(&token_id).unwrap(),
+        SlpTokenTotals {
+            utxo_count: 3,
+            amount: 100,
+            mint_baton_count: 1,
+        }
+    );
+    let address_totals = utxo_set.token_address_totals(&token_id, &address).unwrap();
+    assert_eq!(address_totals.utxo_count, 1);
+    assert_eq!(address_totals.amount, 60);
+    let page = utxo_set.token_utxos(&token_id, None, 2).unwrap();
+    assert_eq!(page.len(), 2);
+    let page = utxo_set
+        .token_utxos(&token_id, Some(&page[1].outpoint), 2)
+        .unwrap();
+    assert_eq!(page.len(), 1);
+    let address_utxos = utxo_set
+        .token_address_utxos(&token_id, &address, None, 10)
+        .unwrap();
+    assert_eq!(address_utxos.len(), 1);
+    assert_eq!(address_utxos[0].outpoint, utxo.outpoint);
+
+    // Sending more than the inputs hold burns the tokens
+    let invalid_send = send(utxo.outpoint.clone(), &[61]);
+    let block2 = block(block1.hash(), vec![coinbase(2), invalid_send.clone()]);
+    utxo_set.apply_block(&block2, 302).unwrap();
+    let invalid_utxo = utxo_set
+        .get(&TxOutpoint {
+            tx_hash: invalid_send.hash(),
+            vout: 1,
+        })
+        .unwrap()
+        .unwrap();
+    assert_eq!(invalid_utxo.slp_token, None);
+    assert_eq!(utxo_set.token_totals(&token_id).unwrap().amount, 40);
+    assert!(utxo_set
+        .token_address_utxos(&token_id, &address, None, 10)
+        .unwrap()
+        .is_empty());
+
+    assert!(!invalid_utxo.is_slp_inconclusive());
+
+    utxo_set.undo_block(&block2).unwrap();
+    assert_eq!(utxo_set.token_totals(&token_id).unwrap().amount, 100);
+    assert_eq!(
+        utxo_set.token_address_totals(&token_id, &address).unwrap(),
+        address_totals
+    );
+
+    // Spending utxos with inconclusive tokens, e.g. from a snapshot
+    // without them, makes the outputs inconclusive rather than burned
+    let snapshot_outpoint = TxOutpoint {
+        tx_hash: [3; 32],
+        vout: 0,
+    };
+    utxo_set
+        .insert_batch(&[Utxo {
+            outpoint: snapshot_outpoint.clone(),
+            amount: 546,
+            script: address.to_script(),
+            token: None,
+            block_height: 200,
+            flags: UTXO_FLAG_SLP_INCONCLUSIVE,
+            slp_token: None,
+        }])
+        .unwrap();
+    let mut inconclusive_send = send(utxo.outpoint.clone(), &[61]);
+    inconclusive_send.inputs.push(TxInput {
+        prev_out: snapshot_outpoint,
+        script: vec![],
+        sequence: 0xffff_ffff,
+    });
+    let block2 = block(block1.hash(), vec![coinbase(2), inconclusive_send.clone()]);
+    utxo_set.apply_block(&block2, 302).unwrap();
+    let inconclusive_utxo = utxo_set
+        .get(&TxOutpoint {
+            tx_hash: inconclusive_send.hash(),
+            vout: 1,
+        })
+        .unwrap()
+        .unwrap();
+    assert_eq!(inconclusive_utxo.slp_token, None);
+    assert!(inconclusive_utxo.is_slp_inconclusive());
+    assert_eq!(utxo_set.token_totals(&token_id).unwrap().amount, 40);
+}
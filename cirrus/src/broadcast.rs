@@ -0,0 +1,277 @@
+use crate::errors::{ErrorKind, Result};
+use cirrus_consensus::Transaction;
+use cirrus_p2p::{
+    inv::{InvVector, ObjectType},
+    GetDataMessage, InvMessage, Message, PingMessage, PongMessage, RejectMessage, TxMessage,
+};
+use cirrus_peer::{MessagePacket, Peer};
+use futures::{future::select_all, prelude::*};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+/// Outcome of a tx broadcast which has been seen propagating through the network.
+#[derive(Clone, Debug)]
+pub struct BroadcastReport {
+    pub tx_hash: [u8; 32],
+    pub requested_by: Vec<SocketAddr>,
+    pub seen_from: SocketAddr,
+}
+
+#[derive(Clone, Debug)]
+pub enum BroadcastEvent {
+    Reply(MessagePacket),
+    Rejected(RejectMessage),
+    Propagated(SocketAddr),
+    Ignored,
+}
+
+/// State of a single tx broadcast. Feed it every packet received from the
+/// peers and act on the returned events.
+pub struct TxBroadcast {
+    tx: Transaction,
+    tx_hash: [u8; 32],
+    announced_to: HashSet<SocketAddr>,
+    requested_by: Vec<SocketAddr>,
+}
+
+impl TxBroadcast {
+    pub fn new(tx: Transaction) -> Self {
+        TxBroadcast {
+            tx_hash: tx.hash(),
+            tx,
+            announced_to: HashSet::new(),
+            requested_by: Vec::new(),
+        }
+    }
+
+    pub fn tx_hash(&self) -> &[u8; 32] {
+        &self.tx_hash
+    }
+
+    pub fn requested_by(&self) -> &[SocketAddr] {
+        &self.requested_by
+    }
+
+    pub fn announce_packet(&mut self, peer_addr: SocketAddr) -> MessagePacket {
+        self.announced_to.insert(peer_addr);
+        InvMessage {
+            inv_vectors: vec![InvVector {
+                type_id: ObjectType::Tx,
+                hash: self.tx_hash,
+            }],
+        }
+        .packet()
+    }
+
+    pub fn handle_packet(
+        &mut self,
+        peer_addr: SocketAddr,
+        packet: &MessagePacket,
+    ) -> Result<BroadcastEvent> {
+        match packet.header().command_name() {
+            b"getdata" => {
+                let getdata = GetDataMessage::from_payload(packet.payload())?;
+                if !getdata.contains(ObjectType::Tx, &self.tx_hash) {
+                    return Ok(BroadcastEvent::Ignored);
+                }
+                if !self.requested_by.contains(&peer_addr) {
+                    self.requested_by.push(peer_addr);
+                }
+                Ok(BroadcastEvent::Reply(
+                    TxMessage {
+                        tx: self.tx.clone(),
+                    }
+                    .packet(),
+                ))
+            }
+            b"inv" => {
+                let inv = InvMessage::from_payload(packet.payload())?;
+                if inv.contains(ObjectType::Tx, &self.tx_hash)
+                    && !self.announced_to.contains(&peer_addr)
+                {
+                    return Ok(BroadcastEvent::Propagated(peer_addr));
+                }
+                Ok(BroadcastEvent::Ignored)
+            }
+            b"reject" => {
+                let reject = RejectMessage::from_payload(packet.payload())?;
+                if reject.rejects(TxMessage::command(), &self.tx_hash) {
+                    return Ok(BroadcastEvent::Rejected(reject));
+                }
+                Ok(BroadcastEvent::Ignored)
+            }
+            _ => Ok(BroadcastEvent::Ignored),
+        }
+    }
+}
+
+/// Announces `tx` to half of `peers` and serves it to those requesting it.
+/// Resolves once one of the remaining peers announces the tx back to us,
+/// which means it propagated through the network.
+///
+/// While broadcasting, pings are answered and all other messages are dropped.
+pub async fn broadcast(peers: &mut [Peer], tx: Transaction) -> Result<BroadcastReport> {
+    if peers.len() < 2 {
+        return Err(ErrorKind::NotEnoughPeers.into());
+    }
+    let mut broadcast = TxBroadcast::new(tx);
+    let num_announce = peers.len() / 2;
+    for peer in peers[..num_announce].iter_mut() {
+        let packet = broadcast.announce_packet(*peer.peer_addr());
+        peer.send_message(packet)?;
+    }
+    let mut connected = (0..peers.len()).collect::<Vec<_>>();
+    while !connected.is_empty() {
+        let (packet, idx) = {
+            let next_packets = peers
+                .iter_mut()
+                .enumerate()
+                .filter(|(idx, _)| connected.contains(idx))
+                .map(|(_, peer)| peer.message_stream().next());
+            let (packet, idx, _) = select_all(next_packets).await;
+            (packet, connected[idx])
+        };
+        let peer = &mut peers[idx];
+        let packet = match packet {
+            Some(packet) => packet,
+            None => {
+                connected.retain(|connected_idx| *connected_idx != idx);
+                continue;
+            }
+        };
+        if packet.header().command_name() == PingMessage::command() {
+            let ping = PingMessage::from_payload(packet.payload())?;
+            peer.send_message(PongMessage { nonce: ping.nonce }.packet())?;
+            continue;
+        }
+        match broadcast.handle_packet(*peer.peer_addr(), &packet)? {
+            BroadcastEvent::Reply(reply) => peer.send_message(reply)?,
+            BroadcastEvent::Rejected(reject) => {
+                return Err(ErrorKind::TxRejected(reject.to_string()).into())
+            }
+            BroadcastEvent::Propagated(seen_from) => {
+                return Ok(BroadcastReport {
+                    tx_hash: broadcast.tx_hash,
+                    requested_by: broadcast.requested_by,
+                    seen_from,
+                })
+            }
+            BroadcastEvent::Ignored => {}
+        }
+    }
+    Err(ErrorKind::AllPeersDisconnected.into())
+}
+
+#[test]
+fn test_tx_broadcast() {
+    use cashcontracts::TxOutpoint;
+    use cirrus_consensus::{TxInput, TxOutput};
+    use cirrus_p2p::REJECT_INSUFFICIENTFEE;
+
+    let tx = Transaction {
+        version: 1,
+        inputs: vec![TxInput {
+            prev_out: TxOutpoint {
+                tx_hash: [1; 32],
+                vout: 0,
+            },
+            script: vec![],
+            sequence: 0xffff_ffff,
+        }],
+        outputs: vec![TxOutput {
+            value: 1000,
+            script: vec![],
+            token: None,
+        }],
+        lock_time: 0,
+    };
+    let tx_hash = tx.hash();
+    let tx_inv = |hash: [u8; 32]| {
+        vec![InvVector {
+            type_id: ObjectType::Tx,
+            hash,
+        }]
+    };
+    let announced: SocketAddr = "10.0.0.1:8333".parse().unwrap();
+    let other: SocketAddr = "10.0.0.2:8333".parse().unwrap();
+    let mut broadcast = TxBroadcast::new(tx);
+    assert_eq!(broadcast.tx_hash(), &tx_hash);
+    let announce = broadcast.announce_packet(announced);
+    let inv = InvMessage::from_payload(announce.payload()).unwrap();
+    assert!(inv.contains(ObjectType::Tx, &tx_hash));
+
+    // getdata for the tx is served, once per peer in requested_by
+    let getdata = GetDataMessage {
+        inv_vectors: tx_inv(tx_hash),
+    }
+    .packet();
+    for _ in 0..2 {
+        match broadcast.handle_packet(announced, &getdata).unwrap() {
+            BroadcastEvent::Reply(reply) => {
+                assert_eq!(reply.header().command_name(), TxMessage::command());
+                let reply = TxMessage::from_payload(reply.payload()).unwrap();
+                assert_eq!(reply.tx.hash(), tx_hash);
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+    }
+    assert_eq!(broadcast.requested_by(), &[announced]);
+    let getdata_other = GetDataMessage {
+        inv_vectors: tx_inv([2; 32]),
+    }
+    .packet();
+    match broadcast.handle_packet(other, &getdata_other).unwrap() {
+        BroadcastEvent::Ignored => {}
+        event => panic!("unexpected {:?}", event),
+    }
+
+    // the inv only counts as propagation when sent by a peer we didn't announce to
+    let inv = InvMessage {
+        inv_vectors: tx_inv(tx_hash),
+    }
+    .packet();
+    match broadcast.handle_packet(announced, &inv).unwrap() {
+        BroadcastEvent::Ignored => {}
+        event => panic!("unexpected {:?}", event),
+    }
+    match broadcast.handle_packet(other, &inv).unwrap() {
+        BroadcastEvent::Propagated(seen_from) => assert_eq!(seen_from, other),
+        event => panic!("unexpected {:?}", event),
+    }
+
+    // only rejects of this tx are reported
+    let reject = |message: &[u8], data: &[u8]| {
+        RejectMessage {
+            message: message.to_vec(),
+            code: REJECT_INSUFFICIENTFEE,
+            reason: b"insufficient fee".to_vec(),
+            data: data.to_vec(),
+        }
+        .packet()
+    };
+    for packet in &[
+        reject(b"tx", &[2; 32]),
+        reject(b"block", &tx_hash),
+        reject(b"version", &[]),
+        PingMessage { nonce: 1 }.packet(),
+    ] {
+        match broadcast.handle_packet(other, packet).unwrap() {
+            BroadcastEvent::Ignored => {}
+            event => panic!("unexpected {:?}", event),
+        }
+    }
+    match broadcast
+        .handle_packet(announced, &reject(b"tx", &tx_hash))
+        .unwrap()
+    {
+        BroadcastEvent::Rejected(reject) => {
+            assert_eq!(reject.code, REJECT_INSUFFICIENTFEE);
+            assert_eq!(reject.reason, b"insufficient fee");
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+
+    // malformed payloads are errors
+    let truncated = MessagePacket::from_payload(b"getdata", vec![1, 1, 0, 0, 0]);
+    assert!(broadcast.handle_packet(other, &truncated).is_err());
+}
@@ -0,0 +1,495 @@
+use crate::errors::{ErrorKind, Result};
+use crate::mempool::{outpoint_key, OutpointKey};
+use crate::tx_builder::{TransactionBuilder, UtxoSigner, DUST_LIMIT, SEQUENCE_FINAL, TX_VERSION};
+use cashcontracts::TxOutpoint;
+use cirrus_consensus::script::opcodes::OP_RETURN;
+use cirrus_consensus::script::{
+    encode_push, read_op, sighash_spending, verify_script, ScriptExecutionContext, ScriptFlags,
+    SigHashCache, SigHashType, SIGHASH_ANYONECANPAY, SIGHASH_FORKID, SIGHASH_SINGLE,
+    UPGRADE9_HEIGHT,
+};
+use cirrus_consensus::{Transaction, TxInput, TxOutput};
+use cirrus_crypto::{Address, PrivateKey};
+use cirrus_slp::{SlpMessage, SlpToken, SlpTxType};
+use cirrus_utxo::Utxo;
+use error_chain::bail;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+/// The maker's signature only commits to its own input and the output at
+/// the same index, so the taker can add the rest of the trade.
+pub const OFFER_SIGHASH_TYPE: u8 = SIGHASH_SINGLE | SIGHASH_ANYONECANPAY | SIGHASH_FORKID;
+
+/// Index of the maker's input and payment output in the completed tx.
+/// Output 0 is taken by the SLP OP_RETURN.
+pub const OFFER_INPUT_INDEX: usize = 1;
+
+/// A signed offer to sell all SLP tokens of a utxo for a fixed amount of
+/// sats. `tx` is the maker's half of the trade: a single input spending the
+/// utxo, signed with `OFFER_SIGHASH_TYPE`, and a single output paying the
+/// maker.
+#[derive(Clone, Debug)]
+pub struct SlpOffer {
+    tx: Transaction,
+    utxo: Utxo,
+}
+
+/// Price of an offer, compared by sats per token.
+#[derive(Clone, Copy, Debug)]
+pub struct OfferPrice {
+    pub sats: u64,
+    pub tokens: u64,
+}
+
+/// Offers by token id, cheapest first. Offers spending the same utxo
+/// replace each other.
+#[derive(Default)]
+pub struct OrderBook {
+    offers: HashMap<[u8; 32], BTreeMap<(OfferPrice, OutpointKey), SlpOffer>>,
+    by_outpoint: HashMap<OutpointKey, ([u8; 32], OfferPrice)>,
+}
+
+fn invalid_offer(reason: &str) -> ErrorKind {
+    ErrorKind::InvalidOffer(reason.to_string())
+}
+
+fn spent_output(utxo: &Utxo) -> TxOutput {
+    TxOutput {
+        value: utxo.amount,
+        script: utxo.script.clone(),
+        token: utxo.token.clone(),
+    }
+}
+
+fn check_offer_utxo(utxo: &Utxo, price: u64) -> Result<()> {
+    match &utxo.slp_token {
+        None => bail!(invalid_offer("utxo holds no valid SLP tokens")),
+        Some(token) if token.is_mint_baton => bail!(invalid_offer("mint batons can't be sold")),
+        Some(_) => {}
+    }
+    if utxo.token.is_some() {
+        bail!(invalid_offer("utxo holds CashTokens"));
+    }
+    if price < DUST_LIMIT {
+        bail!(ErrorKind::DustOutput(price));
+    }
+    Ok(())
+}
+
+impl SlpOffer {
+    /// Signs an offer to sell the tokens of the P2PKH `utxo` for `price`
+    /// sats, paid to `payment_address`.
+    pub fn create(
+        utxo: Utxo,
+        private_key: &PrivateKey,
+        price: u64,
+        payment_address: &Address,
+    ) -> Result<SlpOffer> {
+        let signer = UtxoSigner::P2PKH(private_key.clone());
+        if utxo.script != signer.script_pubkey()? {
+            bail!(ErrorKind::SignerMismatch(
+                utxo.outpoint.tx_hash,
+                utxo.outpoint.vout
+            ));
+        }
+        check_offer_utxo(&utxo, price)?;
+        let mut tx = Transaction {
+            version: TX_VERSION,
+            inputs: vec![TxInput {
+                prev_out: utxo.outpoint.clone(),
+                script: vec![],
+                sequence: SEQUENCE_FINAL,
+            }],
+            outputs: vec![TxOutput {
+                value: price,
+                script: payment_address.to_script(),
+                token: None,
+            }],
+            lock_time: 0,
+        };
+        // Neither other inputs nor other outputs are part of the digest, so
+        // it's the same at OFFER_INPUT_INDEX of the completed tx.
+        let sighash_type = SigHashType::from(OFFER_SIGHASH_TYPE);
+        let msg_hash = sighash_spending(
+            &SigHashCache::new(&tx),
+            &tx,
+            0,
+            &utxo.script,
+            &spent_output(&utxo),
+            sighash_type,
+            ScriptFlags::ENABLE_SIGHASH_FORKID,
+        )?;
+        let mut sig = private_key.sign_schnorr(&msg_hash).to_bytes().to_vec();
+        sig.push(sighash_type.to_byte());
+        let mut script_sig = encode_push(&sig);
+        script_sig.extend_from_slice(&encode_push(&private_key.public_key().serialize()));
+        tx.inputs[0].script = script_sig;
+        Ok(SlpOffer { tx, utxo })
+    }
+
+    /// Validates an offer received from a maker. `utxo` is the spent utxo
+    /// as found in the utxo set, its SLP tokens having been validated, and
+    /// `price` the amount of sats the offer is quoted at.
+    pub fn from_tx(tx: Transaction, utxo: Utxo, price: u64) -> Result<SlpOffer> {
+        if tx.inputs.len() != 1 || tx.outputs.len() != 1 {
+            bail!(invalid_offer("expected exactly one input and output"));
+        }
+        if tx.version != TX_VERSION {
+            bail!(invalid_offer("unsupported tx version"));
+        }
+        let input = &tx.inputs[0];
+        // the taker can't wait for a lock time, and has to sign a final tx
+        if tx.lock_time != 0 || input.sequence != SEQUENCE_FINAL {
+            bail!(invalid_offer("tx must have no lock time and a final input"));
+        }
+        let payment = &tx.outputs[0];
+        if outpoint_key(&input.prev_out) != outpoint_key(&utxo.outpoint) {
+            bail!(invalid_offer("tx doesn't spend the utxo"));
+        }
+        check_offer_utxo(&utxo, price)?;
+        if payment.value != price || payment.token.is_some() {
+            bail!(invalid_offer("tx doesn't pay the quoted price"));
+        }
+        let mut pc = 0;
+        let sighash_type = read_op(&input.script, &mut pc)
+            .ok()
+            .map(|(_, sig)| SigHashType::from_sig(sig));
+        if sighash_type != Some(SigHashType::from(OFFER_SIGHASH_TYPE)) {
+            bail!(invalid_offer(
+                "signature must use SIGHASH_SINGLE|ANYONECANPAY"
+            ));
+        }
+
+        // Check the signature as part of a completed tx, with placeholders
+        // for the taker's input and the OP_RETURN.
+        let completed_tx = Transaction {
+            version: tx.version,
+            inputs: vec![
+                TxInput {
+                    prev_out: TxOutpoint {
+                        tx_hash: [0; 32],
+                        vout: 0,
+                    },
+                    script: vec![],
+                    sequence: SEQUENCE_FINAL,
+                },
+                input.clone(),
+            ],
+            outputs: vec![
+                TxOutput {
+                    value: 0,
+                    script: vec![OP_RETURN],
+                    token: None,
+                },
+                payment.clone(),
+            ],
+            lock_time: tx.lock_time,
+        };
+        let spent_outputs = [
+            TxOutput {
+                value: 0,
+                script: vec![],
+                token: None,
+            },
+            spent_output(&utxo),
+        ];
+        let context = ScriptExecutionContext {
+            tx: &completed_tx,
+            input_index: OFFER_INPUT_INDEX,
+            spent_outputs: &spent_outputs,
+            sighash_cache: None,
+        };
+        let flags = ScriptFlags::for_height(UPGRADE9_HEIGHT);
+        if let Err(err) = verify_script(&input.script, &utxo.script, flags, &context) {
+            bail!(ErrorKind::InvalidOffer(format!("script failed: {}", err)));
+        }
+        Ok(SlpOffer { tx, utxo })
+    }
+
+    /// The maker's partially signed tx.
+    pub fn tx(&self) -> &Transaction {
+        &self.tx
+    }
+
+    pub fn utxo(&self) -> &Utxo {
+        &self.utxo
+    }
+
+    pub fn outpoint(&self) -> &TxOutpoint {
+        &self.utxo.outpoint
+    }
+
+    /// The tokens for sale.
+    pub fn token(&self) -> &SlpToken {
+        self.utxo.slp_token.as_ref().unwrap()
+    }
+
+    pub fn price(&self) -> OfferPrice {
+        OfferPrice {
+            sats: self.tx.outputs[0].value,
+            tokens: self.token().amount,
+        }
+    }
+
+    /// Completes the trade: `builder`, holding the taker's utxos, pays the
+    /// maker and the fee, and the tokens are sent to `receive_address`.
+    /// Fails if any of the builder's utxos holds SLP tokens or may hold
+    /// them, as they'd be burned.
+    pub fn take(
+        &self,
+        mut builder: TransactionBuilder,
+        receive_address: &Address,
+    ) -> Result<Transaction> {
+        for utxo in builder.utxos() {
+            if utxo.slp_token.is_some() || utxo.is_slp_inconclusive() {
+                bail!(ErrorKind::TokenUtxo(
+                    utxo.outpoint.tx_hash,
+                    utxo.outpoint.vout
+                ));
+            }
+        }
+        let token = self.token();
+        let send = SlpMessage {
+            token_type: token.token_type,
+            tx_type: SlpTxType::Send {
+                token_id: token.token_id,
+                output_amounts: vec![0, token.amount],
+            },
+        };
+        let payment = &self.tx.outputs[0];
+        builder.insert_output_script(0, send.to_script(), 0);
+        builder.insert_output_script(OFFER_INPUT_INDEX, payment.script.clone(), payment.value);
+        builder.insert_output_script(2, receive_address.to_script(), DUST_LIMIT);
+        builder.insert_signed_input(
+            OFFER_INPUT_INDEX,
+            self.tx.inputs[0].clone(),
+            self.utxo.amount,
+        );
+        builder.set_lock_time(self.tx.lock_time);
+        builder.build()
+    }
+}
+
+impl Ord for OfferPrice {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lhs = self.sats as u128 * other.tokens as u128;
+        let rhs = other.sats as u128 * self.tokens as u128;
+        lhs.cmp(&rhs)
+    }
+}
+
+impl PartialOrd for OfferPrice {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for OfferPrice {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OfferPrice {}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        OrderBook::default()
+    }
+
+    /// Adds a validated offer, returning the offer for the same utxo it replaced.
+    pub fn add_offer(&mut self, offer: SlpOffer) -> Option<SlpOffer> {
+        let replaced = self.remove_offer(offer.outpoint());
+        let token_id = offer.token().token_id;
+        let price = offer.price();
+        let key = outpoint_key(offer.outpoint());
+        self.by_outpoint.insert(key, (token_id, price));
+        self.offers
+            .entry(token_id)
+            .or_default()
+            .insert((price, key), offer);
+        replaced
+    }
+
+    pub fn remove_offer(&mut self, outpoint: &TxOutpoint) -> Option<SlpOffer> {
+        let key = outpoint_key(outpoint);
+        let (token_id, price) = self.by_outpoint.remove(&key)?;
+        let token_offers = self.offers.get_mut(&token_id)?;
+        let offer = token_offers.remove(&(price, key));
+        if token_offers.is_empty() {
+            self.offers.remove(&token_id);
+        }
+        offer
+    }
+
+    /// Removes the offers whose utxos are spent by `tx`, e.g. once it's seen
+    /// in the mempool or a block.
+    pub fn remove_spent(&mut self, tx: &Transaction) -> Vec<SlpOffer> {
+        tx.inputs
+            .iter()
+            .filter_map(|input| self.remove_offer(&input.prev_out))
+            .collect()
+    }
+
+    /// Offers for `token_id`, cheapest first.
+    pub fn offers<'a>(&'a self, token_id: &[u8; 32]) -> impl Iterator<Item = &'a SlpOffer> {
+        self.offers
+            .get(token_id)
+            .into_iter()
+            .flat_map(|offers| offers.values())
+    }
+
+    pub fn best_offer(&self, token_id: &[u8; 32]) -> Option<&SlpOffer> {
+        self.offers(token_id).next()
+    }
+
+    pub fn token_ids(&self) -> impl Iterator<Item = &[u8; 32]> {
+        self.offers.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_outpoint.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_outpoint.is_empty()
+    }
+}
+
+#[test]
+fn test_dex() {
+    use cirrus_crypto::Network;
+    use cirrus_slp::{check_token_inputs, SlpTokenType};
+    use cirrus_utxo::UTXO_FLAG_SLP_INCONCLUSIVE;
+
+    let key = |i: u8| PrivateKey::from_slice(&[i; 32]).unwrap();
+    let address = |i: u8| Address::p2pkh(Network::Mainnet, &key(i).public_key());
+    let slp_token = |amount: u64, is_mint_baton: bool| SlpToken {
+        token_id: [7; 32],
+        token_type: SlpTokenType::Fungible,
+        amount,
+        is_mint_baton,
+    };
+    let utxo = |i: u8, amount: u64, slp_token: Option<SlpToken>| Utxo {
+        outpoint: TxOutpoint {
+            tx_hash: [i; 32],
+            vout: i as u32,
+        },
+        amount,
+        script: address(1).to_script(),
+        token: None,
+        block_height: 600_000,
+        flags: 0,
+        slp_token,
+    };
+    let maker = key(1);
+    let offer_utxo = utxo(1, DUST_LIMIT, Some(slp_token(1_000, false)));
+    let offer = SlpOffer::create(offer_utxo.clone(), &maker, 50_000, &address(1)).unwrap();
+    let offer = SlpOffer::from_tx(offer.tx().clone(), offer_utxo.clone(), 50_000).unwrap();
+    assert!(SlpOffer::from_tx(offer.tx().clone(), offer_utxo.clone(), 40_000).is_err());
+    let mut tampered_tx = offer.tx().clone();
+    tampered_tx.outputs[0].value = 40_000;
+    assert!(SlpOffer::from_tx(tampered_tx, offer_utxo.clone(), 40_000).is_err());
+    for (lock_time, sequence) in &[(500_000_000, SEQUENCE_FINAL), (0, 0)] {
+        let mut locked_tx = offer.tx().clone();
+        locked_tx.lock_time = *lock_time;
+        locked_tx.inputs[0].sequence = *sequence;
+        match SlpOffer::from_tx(locked_tx, offer_utxo.clone(), 50_000)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::InvalidOffer(reason) => assert!(reason.contains("lock time")),
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+    let baton_utxo = utxo(2, DUST_LIMIT, Some(slp_token(0, true)));
+    assert!(SlpOffer::create(baton_utxo, &maker, 50_000, &address(1)).is_err());
+    assert!(SlpOffer::create(utxo(2, DUST_LIMIT, None), &maker, 50_000, &address(1)).is_err());
+    assert!(SlpOffer::create(offer_utxo.clone(), &key(2), 50_000, &address(1)).is_err());
+
+    // the taker funds the trade from a utxo of their own, one that holds
+    // no SLP tokens, or they'd be burned
+    let taker = UtxoSigner::P2PKH(key(2));
+    for (slp_token, flags) in &[
+        (Some(slp_token(10, false)), 0),
+        (None, UTXO_FLAG_SLP_INCONCLUSIVE),
+    ] {
+        let mut builder = TransactionBuilder::new(address(2));
+        let mut token_utxo = utxo(5, 100_000, slp_token.clone());
+        token_utxo.script = taker.script_pubkey().unwrap();
+        token_utxo.flags = *flags;
+        builder.add_utxo(token_utxo, taker.clone()).unwrap();
+        match offer.take(builder, &address(2)).unwrap_err().kind() {
+            ErrorKind::TokenUtxo(..) => {}
+            kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+    let mut builder = TransactionBuilder::new(address(2));
+    let mut taker_utxo = utxo(3, 100_000, None);
+    taker_utxo.script = taker.script_pubkey().unwrap();
+    builder.add_utxo(taker_utxo.clone(), taker).unwrap();
+    let tx = offer.take(builder, &address(2)).unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert_eq!(
+        tx.inputs[OFFER_INPUT_INDEX].script,
+        offer.tx().inputs[0].script
+    );
+    assert_eq!(tx.outputs[OFFER_INPUT_INDEX].value, 50_000);
+    assert_eq!(tx.outputs[OFFER_INPUT_INDEX].script, address(1).to_script());
+    assert_eq!(tx.outputs[2].script, address(2).to_script());
+    assert_eq!(tx.outputs[3].script, address(2).to_script());
+
+    let spent_outputs = [spent_output(&taker_utxo), spent_output(&offer_utxo)];
+    for input_index in 0..2 {
+        let context = ScriptExecutionContext {
+            tx: &tx,
+            input_index,
+            spent_outputs: &spent_outputs,
+            sighash_cache: None,
+        };
+        verify_script(
+            &tx.inputs[input_index].script,
+            &spent_outputs[input_index].script,
+            ScriptFlags::for_height(UPGRADE9_HEIGHT),
+            &context,
+        )
+        .unwrap();
+    }
+    let message = SlpMessage::from_tx(&tx).unwrap();
+    assert_eq!(message.output_amount(2), 1_000);
+    assert!(check_token_inputs(
+        &message,
+        &tx.hash(),
+        vec![None, Some(slp_token(1_000, false))]
+    ));
+
+    let mut order_book = OrderBook::new();
+    let cheaper_utxo = utxo(4, DUST_LIMIT, Some(slp_token(500, false)));
+    let cheaper_offer = SlpOffer::create(cheaper_utxo, &maker, 20_000, &address(1)).unwrap();
+    assert!(order_book.add_offer(offer.clone()).is_none());
+    assert!(order_book.add_offer(cheaper_offer.clone()).is_none());
+    assert!(order_book.add_offer(offer.clone()).is_some());
+    assert_eq!(order_book.len(), 2);
+    let prices = order_book
+        .offers(&[7; 32])
+        .map(|offer| offer.price().sats)
+        .collect::<Vec<_>>();
+    assert_eq!(prices, vec![20_000, 50_000]);
+    assert_eq!(
+        order_book.best_offer(&[7; 32]).unwrap().price().sats,
+        20_000
+    );
+    assert!(cheaper_offer.price() < offer.price());
+    assert_eq!(
+        OfferPrice { sats: 2, tokens: 4 },
+        OfferPrice { sats: 1, tokens: 2 }
+    );
+
+    let removed = order_book.remove_spent(&tx);
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].price().sats, 50_000);
+    assert_eq!(order_book.len(), 1);
+    assert!(order_book.remove_offer(cheaper_offer.outpoint()).is_some());
+    assert!(order_book.is_empty());
+    assert_eq!(order_book.token_ids().count(), 0);
+}
@@ -0,0 +1,89 @@
+use error_chain::error_chain;
+
+error_chain! {
+    foreign_links {
+        Io(std::io::Error);
+        Db(sled::Error);
+    }
+
+    links {
+        Peer(cirrus_peer::errors::Error, cirrus_peer::errors::ErrorKind);
+        Script(cirrus_consensus::errors::script::Error, cirrus_consensus::errors::script::ErrorKind);
+    }
+
+    errors {
+        NotEnoughPeers {}
+        AllPeersDisconnected {}
+        TxRejected(reason: String) {
+            description("Tx rejected by peer")
+            display("Tx rejected by peer: {}", reason)
+        }
+        InsufficientFunds(needed: u64, available: u64) {
+            description("Insufficient funds")
+            display("Insufficient funds: need {} sats, have {} sats", needed, available)
+        }
+        DustOutput(amount: u64) {
+            description("Output below dust limit")
+            display("Output below dust limit: {} sats", amount)
+        }
+        AmountOverflow {
+            description("Sum of amounts overflows")
+        }
+        InvalidMultisig(threshold: usize, num_keys: usize) {
+            description("Invalid multisig")
+            display("Invalid {}-of-{} multisig", threshold, num_keys)
+        }
+        InvalidInputIndex(index: usize, num_inputs: usize) {
+            description("Invalid input index")
+            display("Can't place input at index {} of {} inputs", index, num_inputs)
+        }
+        InvalidOffer(reason: String) {
+            description("Invalid offer")
+            display("Invalid offer: {}", reason)
+        }
+        CorruptHeaderStore(reason: String) {
+            description("Corrupt header store")
+            display("Corrupt header store: {}", reason)
+        }
+        InvalidTrustedHeader(reason: String) {
+            description("Invalid trusted header")
+            display("Invalid trusted header: {}", reason)
+        }
+        UnconnectedHeaders(prev_block: String) {
+            description("Headers don't connect to the header chain")
+            display("Headers don't connect to the header chain, unknown block {}", prev_block)
+        }
+        InvalidProofOfWork(block_hash: String) {
+            description("Invalid proof of work")
+            display("Invalid proof of work of header {}", block_hash)
+        }
+        WrongDifficulty(block_hash: String) {
+            description("Header doesn't have the required difficulty")
+            display("Header {} doesn't have the difficulty required by ASERT", block_hash)
+        }
+        CheckpointMismatch(height: i32) {
+            description("Header doesn't match checkpoint")
+            display("Header doesn't match the checkpoint at height {}", height)
+        }
+        ForkBelowCheckpoint(fork_height: i32) {
+            description("Headers fork below the last checkpoint")
+            display("Headers fork at height {}, below the last checkpoint", fork_height)
+        }
+        TimeTooOld(block_hash: String) {
+            description("Header not after the median time past")
+            display("Header {} isn't after the median time past", block_hash)
+        }
+        TimeTooNew(block_hash: String) {
+            description("Header too far in the future")
+            display("Header {} is too far in the future", block_hash)
+        }
+        TokenUtxo(tx_hash: [u8; 32], vout: u32) {
+            description("Utxo may hold tokens, which would be burned")
+            display("Utxo {}:{} may hold tokens, which would be burned", cashcontracts::tx_hash_to_hex(tx_hash), vout)
+        }
+        SignerMismatch(tx_hash: [u8; 32], vout: u32) {
+            description("Utxo can't be spent by its signer")
+            display("Utxo {}:{} can't be spent by its signer", cashcontracts::tx_hash_to_hex(tx_hash), vout)
+        }
+    }
+}
@@ -0,0 +1,543 @@
+use crate::errors::{ErrorKind, Result};
+use crate::header_store::HeaderStore;
+use cashcontracts::tx_hash_to_hex;
+use cirrus_consensus::{
+    checkpoint_at, genesis, last_checkpoint, median_time_past, next_bits, BlockHeader, Upgrade,
+    MAX_FUTURE_BLOCK_TIME, MEDIAN_TIME_SPAN,
+};
+use cirrus_crypto::Network;
+use error_chain::bail;
+use std::path::Path;
+
+/// How `HeaderChain::add_headers` changed the chain.
+#[derive(Clone, Debug)]
+pub enum HeadersUpdate {
+    /// All headers were known, or their branch has less work.
+    Unchanged,
+    /// The headers were appended to the tip.
+    Extended { num_added: usize },
+    /// The headers above `fork_height` were replaced by a branch with more work.
+    Reorganized {
+        fork_height: i32,
+        disconnected: Vec<BlockHeader>,
+    },
+}
+
+/// Header an SPV client starts its chain from instead of genesis. The
+/// headers below it aren't downloaded, their work is taken on trust.
+#[derive(Clone, Debug)]
+pub struct TrustedHeader {
+    pub height: i32,
+    pub header: BlockHeader,
+    /// Timestamps of the `MEDIAN_TIME_SPAN - 1` headers before it, oldest
+    /// first, so the median time past of the headers after it is exact.
+    /// Fewer only if there aren't as many headers before it.
+    pub prev_timestamps: Vec<u32>,
+}
+
+/// Best header chain of `network`, persisted in a `HeaderStore`, so it
+/// starts at the tip. Headers have to connect, meet the target of their
+/// `bits`, match the checkpoints and have a timestamp after the median time
+/// past of their parent and not too far in the future. Above the ASERT
+/// anchor, their `bits` have to be the ones required by ASERT, so branches
+/// can't be spammed with low-work headers. Below it, the checkpoints pin
+/// the chain, except on testnet between its last checkpoint and the anchor.
+///
+/// Only the best chain is kept: a competing branch replaces it once a
+/// single call to `add_headers` brings it to more work. Branches forking
+/// below the last passed checkpoint or the start of the chain are rejected.
+pub struct HeaderChain {
+    store: HeaderStore,
+    network: Network,
+    /// `TrustedHeader::prev_timestamps` of the start of the chain.
+    prev_timestamps: Vec<u32>,
+}
+
+impl HeaderChain {
+    /// Opens the chain at `path`, which starts at genesis if it's new.
+    pub fn open(path: impl AsRef<Path>, network: Network) -> Result<Self> {
+        let genesis = TrustedHeader {
+            height: 0,
+            header: genesis(network),
+            prev_timestamps: vec![],
+        };
+        HeaderChain::open_from(path, network, genesis)
+    }
+
+    /// Opens the chain at `path`, which starts at `trusted` if it's new.
+    /// An existing chain has to contain `trusted`.
+    pub fn open_from(
+        path: impl AsRef<Path>,
+        network: Network,
+        trusted: TrustedHeader,
+    ) -> Result<Self> {
+        let trusted_hash = trusted.header.hash();
+        if trusted.height < 0 || !trusted.header.check_proof_of_work(network) {
+            bail!(ErrorKind::InvalidProofOfWork(tx_hash_to_hex(&trusted_hash)));
+        }
+        match checkpoint_at(network, trusted.height) {
+            Some(checkpoint) if checkpoint.hash() != trusted_hash => {
+                bail!(ErrorKind::CheckpointMismatch(trusted.height))
+            }
+            _ => {}
+        }
+        let num_prev_timestamps = trusted.height.min(MEDIAN_TIME_SPAN as i32 - 1);
+        if trusted.prev_timestamps.len() != num_prev_timestamps as usize {
+            bail!(ErrorKind::InvalidTrustedHeader(format!(
+                "{} previous timestamps, expected {}",
+                trusted.prev_timestamps.len(),
+                num_prev_timestamps
+            )));
+        }
+        let mut store = HeaderStore::open(path, trusted.height as usize)?;
+        if store.is_empty() && store.start_height() == trusted.height as usize {
+            store.append(&[trusted.header])?;
+        } else if store.height(&trusted_hash)? != Some(trusted.height as usize) {
+            bail!(ErrorKind::CorruptHeaderStore(format!(
+                "no header {} at height {} on {:?}",
+                tx_hash_to_hex(&trusted_hash),
+                trusted.height,
+                network
+            )));
+        }
+        Ok(HeaderChain {
+            store,
+            network,
+            prev_timestamps: trusted.prev_timestamps,
+        })
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Height of the first header of the chain.
+    pub fn start_height(&self) -> i32 {
+        self.store.start_height() as i32
+    }
+
+    /// Height below which the chain can't be reorganized: the last passed
+    /// checkpoint, or the start of the chain.
+    pub fn min_fork_height(&self) -> i32 {
+        let checkpoint_height = last_checkpoint(self.network, self.tip_height())
+            .map(|checkpoint| checkpoint.height)
+            .unwrap_or(0);
+        checkpoint_height.max(self.start_height())
+    }
+
+    pub fn tip_height(&self) -> i32 {
+        self.store.len() as i32 - 1
+    }
+
+    pub fn tip(&self) -> Result<BlockHeader> {
+        Ok(self.store.tip()?.unwrap())
+    }
+
+    pub fn header(&self, height: i32) -> Result<Option<BlockHeader>> {
+        if height < 0 {
+            return Ok(None);
+        }
+        self.store.header(height as usize)
+    }
+
+    /// Height of the header with hash `block_hash`, if in the best chain.
+    pub fn height(&self, block_hash: &[u8; 32]) -> Result<Option<i32>> {
+        Ok(self.store.height(block_hash)?.map(|height| height as i32))
+    }
+
+    /// Median time past of the block at `height`, which can't be below the
+    /// start of the chain.
+    pub fn median_time_past(&self, height: i32) -> Result<u32> {
+        let timestamps = self.timestamps(height)?;
+        Ok(median_time_past(&timestamps))
+    }
+
+    /// Whether `upgrade` applies to the block at `height`.
+    pub fn is_upgrade_active(&self, upgrade: Upgrade, height: i32) -> Result<bool> {
+        Ok(upgrade.is_active(self.median_time_past(height - 1)?))
+    }
+
+    /// First height `upgrade` applies to, None if it doesn't apply to the
+    /// next block yet. If it applies to the first block after the start of
+    /// the chain, that block's height is returned.
+    pub fn activation_height(&self, upgrade: Upgrade) -> Result<Option<i32>> {
+        // the median time past never decreases, so the activation is
+        // binary searched
+        let mut low = self.start_height() + 1;
+        let mut high = self.tip_height() + 1;
+        if !self.is_upgrade_active(upgrade, high)? {
+            return Ok(None);
+        }
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.is_upgrade_active(upgrade, mid)? {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        Ok(Some(low))
+    }
+
+    /// Timestamps of the blocks making up the median time past at `height`,
+    /// those before the start of the chain from its trusted header.
+    fn timestamps(&self, height: i32) -> Result<Vec<u32>> {
+        let start = (height + 1 - MEDIAN_TIME_SPAN as i32).max(0);
+        let end = (height + 1).max(0);
+        let num_prev = self.prev_timestamps.len() as i32;
+        let prev_start = self.start_height() - num_prev;
+        let prev_end = (end - prev_start).max(0).min(num_prev);
+        let prev_start = (start - prev_start).max(0).min(prev_end);
+        let mut timestamps = self.prev_timestamps[prev_start as usize..prev_end as usize].to_vec();
+        let headers = self.store.headers(start as usize, end as usize)?;
+        timestamps.extend(headers.iter().map(|header| header.timestamp));
+        Ok(timestamps)
+    }
+
+    /// Adds headers as received in a `headers` message. They have to form a
+    /// chain whose first header connects to a header of the best chain.
+    /// `adjusted_time` is the network-adjusted time, see `NetworkTime`.
+    pub fn add_headers(
+        &mut self,
+        headers: &[BlockHeader],
+        adjusted_time: i64,
+    ) -> Result<HeadersUpdate> {
+        let first = match headers.first() {
+            Some(first) => first,
+            None => return Ok(HeadersUpdate::Unchanged),
+        };
+        let mut fork_height = match self.height(&first.prev_block)? {
+            Some(height) => height,
+            None => bail!(ErrorKind::UnconnectedHeaders(tx_hash_to_hex(
+                &first.prev_block
+            ))),
+        };
+        let mut prev_hash = first.prev_block;
+        let mut timestamps = self.timestamps(fork_height)?;
+        let mut hashes = Vec::with_capacity(headers.len());
+        for (offset, header) in headers.iter().enumerate() {
+            let hash = header.hash();
+            let height = fork_height + 1 + offset as i32;
+            if header.prev_block != prev_hash {
+                bail!(ErrorKind::UnconnectedHeaders(tx_hash_to_hex(
+                    &header.prev_block
+                )));
+            }
+            let prev_timestamp = timestamps.last().cloned().unwrap_or(0);
+            match next_bits(self.network, height - 1, prev_timestamp, header.timestamp) {
+                Some(bits) if bits != header.bits => {
+                    bail!(ErrorKind::WrongDifficulty(tx_hash_to_hex(&hash)))
+                }
+                _ => {}
+            }
+            if !header.check_proof_of_work(self.network) {
+                bail!(ErrorKind::InvalidProofOfWork(tx_hash_to_hex(&hash)));
+            }
+            match checkpoint_at(self.network, height) {
+                Some(checkpoint) if checkpoint.hash() != hash => {
+                    bail!(ErrorKind::CheckpointMismatch(height))
+                }
+                _ => {}
+            }
+            if header.timestamp <= median_time_past(&timestamps) {
+                bail!(ErrorKind::TimeTooOld(tx_hash_to_hex(&hash)));
+            }
+            if header.timestamp as i64 > adjusted_time + MAX_FUTURE_BLOCK_TIME {
+                bail!(ErrorKind::TimeTooNew(tx_hash_to_hex(&hash)));
+            }
+            timestamps.push(header.timestamp);
+            hashes.push(hash);
+            prev_hash = hash;
+        }
+
+        // skip headers already in the best chain
+        let mut num_known = 0;
+        for hash in hashes.iter() {
+            if self.height(hash)? != Some(fork_height + 1) {
+                break;
+            }
+            fork_height += 1;
+            num_known += 1;
+        }
+        let new_headers = &headers[num_known..];
+        if new_headers.is_empty() {
+            return Ok(HeadersUpdate::Unchanged);
+        }
+        if fork_height < self.min_fork_height() {
+            bail!(ErrorKind::ForkBelowCheckpoint(fork_height));
+        }
+        if fork_height == self.tip_height() {
+            self.store.append(new_headers)?;
+            return Ok(HeadersUpdate::Extended {
+                num_added: new_headers.len(),
+            });
+        }
+
+        let replaced = self
+            .store
+            .headers(fork_height as usize + 1, self.store.len())?;
+        if chain_work(new_headers) <= chain_work(&replaced) {
+            return Ok(HeadersUpdate::Unchanged);
+        }
+        let disconnected = self.store.truncate(fork_height as usize + 1)?;
+        self.store.append(new_headers)?;
+        Ok(HeadersUpdate::Reorganized {
+            fork_height,
+            disconnected,
+        })
+    }
+}
+
+fn chain_work(headers: &[BlockHeader]) -> u128 {
+    headers
+        .iter()
+        .fold(0, |work, header| work.saturating_add(header.work()))
+}
+
+#[cfg(test)]
+pub(crate) fn mine_header(prev: &BlockHeader, timestamp: u32, network: Network) -> BlockHeader {
+    let mut header = BlockHeader {
+        prev_block: prev.hash(),
+        timestamp,
+        ..prev.clone()
+    };
+    while !header.check_proof_of_work(network) {
+        header.nonce += 1;
+    }
+    header
+}
+
+#[test]
+fn test_header_chain() {
+    use crate::test_utils::{reopen, TempDir};
+    use cirrus_consensus::{asert_anchor, GENESIS, REGTEST_GENESIS};
+
+    let dir = TempDir::new("header-chain");
+    let path = dir.path().join("genesis");
+    const NOW: i64 = 2_000_000_000;
+    let network = Network::Regtest;
+    let branch = |prev: &BlockHeader, len: usize, timestamp: u32| {
+        let mut headers = Vec::new();
+        let mut prev = prev.clone();
+        for i in 0..len {
+            prev = mine_header(&prev, timestamp + i as u32, network);
+            headers.push(prev.clone());
+        }
+        headers
+    };
+
+    let mut chain = HeaderChain::open(&path, network).unwrap();
+    assert_eq!(chain.tip_height(), 0);
+    let headers = branch(&REGTEST_GENESIS, 5, 1_600_000_000);
+    match chain.add_headers(&headers, NOW).unwrap() {
+        HeadersUpdate::Extended { num_added: 5 } => {}
+        update => panic!("unexpected update {:?}", update),
+    }
+    match chain.add_headers(&headers[2..], NOW).unwrap() {
+        HeadersUpdate::Unchanged => {}
+        update => panic!("unexpected update {:?}", update),
+    }
+    assert_eq!(chain.tip_height(), 5);
+    assert_eq!(chain.height(&headers[4].hash()).unwrap(), Some(5));
+
+    // a fork at height 3 needs more than two headers to win
+    let fork = branch(&headers[2], 3, 1_700_000_000);
+    match chain.add_headers(&fork[..2], NOW).unwrap() {
+        HeadersUpdate::Unchanged => {}
+        update => panic!("unexpected update {:?}", update),
+    }
+    match chain.add_headers(&fork, NOW).unwrap() {
+        HeadersUpdate::Reorganized {
+            fork_height: 3,
+            disconnected,
+        } => assert_eq!(disconnected.len(), 2),
+        update => panic!("unexpected update {:?}", update),
+    }
+    assert_eq!(chain.tip_height(), 6);
+    assert_eq!(chain.height(&headers[4].hash()).unwrap(), None);
+
+    match chain.add_headers(&fork[1..], NOW).unwrap() {
+        HeadersUpdate::Unchanged => {}
+        update => panic!("unexpected update {:?}", update),
+    }
+    match chain.add_headers(&headers[4..], NOW).unwrap_err().kind() {
+        ErrorKind::UnconnectedHeaders(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    let mut invalid = mine_header(&fork[2], 1_700_000_003, network);
+    while invalid.check_proof_of_work(network) {
+        invalid.nonce += 1;
+    }
+    match chain.add_headers(&[invalid], NOW).unwrap_err().kind() {
+        ErrorKind::InvalidProofOfWork(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    drop(chain);
+
+    let chain = reopen(|| HeaderChain::open(&path, network)).unwrap();
+    assert_eq!(chain.tip_height(), 6);
+    assert_eq!(chain.tip().unwrap().hash(), fork[2].hash());
+    drop(chain);
+    match reopen(|| HeaderChain::open(&path, Network::Mainnet)) {
+        Err(err) => match err.kind() {
+            ErrorKind::CorruptHeaderStore(_) => {}
+            kind => panic!("unexpected error {:?}", kind),
+        },
+        Ok(_) => panic!("opened a regtest chain as mainnet"),
+    }
+    let path = dir.path().join("trusted");
+
+    // an SPV client starting from a trusted header can't fork below it
+    let trusted = TrustedHeader {
+        height: 3,
+        header: headers[2].clone(),
+        prev_timestamps: vec![
+            REGTEST_GENESIS.timestamp,
+            headers[0].timestamp,
+            headers[1].timestamp,
+        ],
+    };
+    let mut chain = HeaderChain::open_from(&path, network, trusted.clone()).unwrap();
+    assert_eq!(chain.start_height(), 3);
+    assert_eq!(chain.tip_height(), 3);
+    assert!(chain.header(2).unwrap().is_none());
+    match chain.add_headers(&headers[3..], NOW).unwrap() {
+        HeadersUpdate::Extended { num_added: 2 } => {}
+        update => panic!("unexpected update {:?}", update),
+    }
+    match chain.add_headers(&headers[1..], NOW).unwrap_err().kind() {
+        ErrorKind::UnconnectedHeaders(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    match chain.add_headers(&fork, NOW).unwrap() {
+        HeadersUpdate::Reorganized { fork_height: 3, .. } => {}
+        update => panic!("unexpected update {:?}", update),
+    }
+    drop(chain);
+    let chain = reopen(|| HeaderChain::open_from(&path, network, trusted.clone())).unwrap();
+    assert_eq!(chain.tip().unwrap().hash(), fork[2].hash());
+    drop(chain);
+    match reopen(|| HeaderChain::open(&path, network)) {
+        Err(err) => match err.kind() {
+            ErrorKind::CorruptHeaderStore(_) => {}
+            kind => panic!("unexpected error {:?}", kind),
+        },
+        Ok(_) => panic!("opened a chain starting at a trusted header from genesis"),
+    }
+    let path = dir.path().join("mainnet");
+
+    // checkpoints can't be skipped by trusting another header
+    let trusted = TrustedHeader {
+        height: 478_559,
+        header: GENESIS,
+        prev_timestamps: vec![GENESIS.timestamp; MEDIAN_TIME_SPAN - 1],
+    };
+    match HeaderChain::open_from(&path, Network::Mainnet, trusted) {
+        Err(err) => match err.kind() {
+            ErrorKind::CheckpointMismatch(478_559) => {}
+            kind => panic!("unexpected error {:?}", kind),
+        },
+        Ok(_) => panic!("checkpoint not checked"),
+    }
+
+    // above the ASERT anchor, headers need the bits it requires; genesis
+    // stands in for a trusted header, as it's the only one we can verify
+    let trusted = TrustedHeader {
+        height: asert_anchor(Network::Mainnet).unwrap().height + 10,
+        header: GENESIS,
+        prev_timestamps: vec![GENESIS.timestamp - 600; MEDIAN_TIME_SPAN - 1],
+    };
+    let mut chain = HeaderChain::open_from(&path, Network::Mainnet, trusted).unwrap();
+    let low_work = BlockHeader {
+        prev_block: GENESIS.hash(),
+        timestamp: GENESIS.timestamp + 600,
+        ..GENESIS
+    };
+    match chain.add_headers(&[low_work], NOW).unwrap_err().kind() {
+        ErrorKind::WrongDifficulty(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+}
+
+#[test]
+fn test_header_time() {
+    use crate::test_utils::TempDir;
+    use cirrus_consensus::REGTEST_GENESIS;
+
+    let dir = TempDir::new("header-time");
+    let path = dir.path().join("genesis");
+    let network = Network::Regtest;
+    let axion_time = Upgrade::Axion.activation_time();
+    let mut chain = HeaderChain::open(&path, network).unwrap();
+    let mut headers = vec![REGTEST_GENESIS];
+    for i in 1..=20 {
+        let timestamp = axion_time - 6000 + 600 * i;
+        headers.push(mine_header(&headers[i as usize - 1], timestamp, network));
+    }
+    let now = axion_time as i64 + 6000;
+    chain.add_headers(&headers[1..], now).unwrap();
+
+    // the median of the last 11 timestamps is the one 5 blocks back
+    assert_eq!(chain.median_time_past(20).unwrap(), headers[15].timestamp);
+    assert_eq!(chain.median_time_past(1).unwrap(), headers[1].timestamp);
+    assert_eq!(chain.activation_height(Upgrade::Axion).unwrap(), Some(16));
+    assert!(!chain.is_upgrade_active(Upgrade::Axion, 15).unwrap());
+    assert!(chain.is_upgrade_active(Upgrade::Axion, 16).unwrap());
+    assert_eq!(chain.activation_height(Upgrade::Uahf).unwrap(), Some(2));
+    assert_eq!(chain.activation_height(Upgrade::Upgrade9).unwrap(), None);
+
+    let tip = chain.tip().unwrap();
+    let too_old = mine_header(&tip, headers[15].timestamp, network);
+    match chain.add_headers(&[too_old], now).unwrap_err().kind() {
+        ErrorKind::TimeTooOld(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    let too_new = mine_header(&tip, (now + MAX_FUTURE_BLOCK_TIME) as u32 + 1, network);
+    match chain
+        .add_headers(std::slice::from_ref(&too_new), now)
+        .unwrap_err()
+        .kind()
+    {
+        ErrorKind::TimeTooNew(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    let oldest_valid = mine_header(&tip, headers[15].timestamp + 1, network);
+    match chain.add_headers(&[oldest_valid], now).unwrap() {
+        HeadersUpdate::Extended { num_added: 1 } => {}
+        update => panic!("unexpected update {:?}", update),
+    }
+    // valid once the clock caught up
+    let too_new = mine_header(&chain.tip().unwrap(), too_new.timestamp, network);
+    chain.add_headers(&[too_new], now + 1).unwrap();
+    assert_eq!(chain.tip_height(), 22);
+    drop(chain);
+    let path = dir.path().join("trusted");
+
+    // starting from a trusted header, the median time past is the same
+    let trusted = |num_prev_timestamps: usize| TrustedHeader {
+        height: 12,
+        header: headers[12].clone(),
+        prev_timestamps: headers[12 - num_prev_timestamps..12]
+            .iter()
+            .map(|header| header.timestamp)
+            .collect(),
+    };
+    match HeaderChain::open_from(&path, network, trusted(1)) {
+        Err(err) => match err.kind() {
+            ErrorKind::InvalidTrustedHeader(_) => {}
+            kind => panic!("unexpected error {:?}", kind),
+        },
+        Ok(_) => panic!("missing timestamps not rejected"),
+    }
+    let mut chain = HeaderChain::open_from(&path, network, trusted(10)).unwrap();
+    assert_eq!(chain.median_time_past(12).unwrap(), headers[7].timestamp);
+    assert_eq!(chain.activation_height(Upgrade::Axion).unwrap(), None);
+    let too_old = mine_header(&headers[12], headers[7].timestamp, network);
+    match chain.add_headers(&[too_old], now).unwrap_err().kind() {
+        ErrorKind::TimeTooOld(_) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    chain.add_headers(&headers[13..], now).unwrap();
+    assert_eq!(chain.median_time_past(20).unwrap(), headers[15].timestamp);
+    assert_eq!(chain.activation_height(Upgrade::Axion).unwrap(), Some(16));
+}
@@ -0,0 +1,296 @@
+use crate::errors::{ErrorKind, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use cirrus_consensus::BlockHeader;
+use error_chain::bail;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+pub const HEADER_SIZE: usize = 80;
+const HEADERS_FILE: &str = "headers.dat";
+const INDEX_DIR: &str = "index";
+const INDEXED_LEN_KEY: &[u8] = b"indexed_len";
+const START_HEIGHT_KEY: &[u8] = b"start_height";
+
+/// Block headers of a chain in a flat file of 80-byte headers, ordered by
+/// height, and a sled index from header hash to height. The first header
+/// is at `start_height`, the headers below aren't stored.
+///
+/// Appends write the file before the index, truncations update the index
+/// before the file. On open, the index is caught up with the file, so after
+/// a crash the store has either the old or the new headers.
+pub struct HeaderStore {
+    /// Locked for reads, as they seek the cursor shared between readers.
+    file: Mutex<File>,
+    index: sled::Db,
+    start_height: usize,
+    len: usize,
+}
+
+impl HeaderStore {
+    /// Opens the store at `path`. A new store starts at `start_height`, an
+    /// existing one keeps its start.
+    pub fn open(path: impl AsRef<Path>, start_height: usize) -> Result<Self> {
+        let path = path.as_ref();
+        std::fs::create_dir_all(path)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.join(HEADERS_FILE))?;
+        let index = sled::open(path.join(INDEX_DIR))?;
+        let file_len = file.metadata()?.len() as usize;
+        let num_headers = file_len / HEADER_SIZE;
+        if file_len != num_headers * HEADER_SIZE {
+            // torn write of the last append
+            file.set_len((num_headers * HEADER_SIZE) as u64)?;
+            file.sync_all()?;
+        }
+        let start_height = match index.get(START_HEIGHT_KEY)? {
+            Some(start_height) => LittleEndian::read_u64(&start_height) as usize,
+            None if num_headers == 0 && index.get(INDEXED_LEN_KEY)?.is_none() => {
+                let mut batch = sled::Batch::default();
+                batch.insert(START_HEIGHT_KEY, &(start_height as u64).to_le_bytes()[..]);
+                batch.insert(INDEXED_LEN_KEY, &(start_height as u64).to_le_bytes()[..]);
+                index.apply_batch(batch)?;
+                index.flush()?;
+                start_height
+            }
+            None => 0,
+        };
+        let len = start_height + num_headers;
+        let indexed_len = match index.get(INDEXED_LEN_KEY)? {
+            Some(indexed_len) => LittleEndian::read_u64(&indexed_len) as usize,
+            None => start_height,
+        };
+        if indexed_len > len {
+            bail!(ErrorKind::CorruptHeaderStore(format!(
+                "{} headers indexed, but only {} stored",
+                indexed_len, len
+            )));
+        }
+        let mut store = HeaderStore {
+            file: Mutex::new(file),
+            index,
+            start_height,
+            len: indexed_len,
+        };
+        let unindexed = store.headers(indexed_len, len)?;
+        store.index_headers(&unindexed)?;
+        store.len = len;
+        Ok(store)
+    }
+
+    /// Height of the first header.
+    pub fn start_height(&self) -> usize {
+        self.start_height
+    }
+
+    /// Height of the tip plus one.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == self.start_height
+    }
+
+    pub fn header(&self, height: usize) -> Result<Option<BlockHeader>> {
+        if height < self.start_height || height >= self.len {
+            return Ok(None);
+        }
+        Ok(self.headers(height, height + 1)?.pop())
+    }
+
+    pub fn tip(&self) -> Result<Option<BlockHeader>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        self.header(self.len - 1)
+    }
+
+    /// Headers from height `start` up to, excluding, `end`.
+    pub fn headers(&self, start: usize, end: usize) -> Result<Vec<BlockHeader>> {
+        let start = start.max(self.start_height);
+        let end = end.min(self.start_height + self.file_len()?);
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        let mut ser = vec![0; (end - start) * HEADER_SIZE];
+        let mut file = self.lock_file();
+        file.seek(SeekFrom::Start(self.file_offset(start)))?;
+        file.read_exact(&mut ser)?;
+        drop(file);
+        let mut stream = ser.as_slice();
+        let mut headers = Vec::with_capacity(end - start);
+        while !stream.is_empty() {
+            headers.push(BlockHeader::from_stream(&mut stream)?);
+        }
+        Ok(headers)
+    }
+
+    /// Height of the header with hash `block_hash`, if stored.
+    pub fn height(&self, block_hash: &[u8; 32]) -> Result<Option<usize>> {
+        match self.index.get(block_hash)? {
+            Some(height) => Ok(Some(LittleEndian::read_u32(&height) as usize)),
+            None => Ok(None),
+        }
+    }
+
+    /// Appends `headers` on top of the tip and syncs them to disk.
+    pub fn append(&mut self, headers: &[BlockHeader]) -> Result<()> {
+        let mut ser = Vec::with_capacity(headers.len() * HEADER_SIZE);
+        for header in headers {
+            header.write_to_stream(&mut ser)?;
+        }
+        let offset = self.file_offset(self.len);
+        let file = self.file.get_mut().unwrap_or_else(|err| err.into_inner());
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&ser)?;
+        file.sync_data()?;
+        self.index_headers(headers)?;
+        self.len += headers.len();
+        Ok(())
+    }
+
+    /// Removes the headers from height `len` up and returns them, e.g. to
+    /// replace them with the headers of a reorg.
+    pub fn truncate(&mut self, len: usize) -> Result<Vec<BlockHeader>> {
+        let len = len.max(self.start_height);
+        if len >= self.len {
+            return Ok(Vec::new());
+        }
+        let removed = self.headers(len, self.len)?;
+        let mut batch = sled::Batch::default();
+        for header in removed.iter() {
+            batch.remove(&header.hash()[..]);
+        }
+        batch.insert(INDEXED_LEN_KEY, &(len as u64).to_le_bytes()[..]);
+        self.index.apply_batch(batch)?;
+        self.index.flush()?;
+        let file = self.lock_file();
+        file.set_len(self.file_offset(len))?;
+        file.sync_all()?;
+        drop(file);
+        self.len = len;
+        Ok(removed)
+    }
+
+    /// Indexes `headers`, stored from height `self.len` up.
+    fn index_headers(&self, headers: &[BlockHeader]) -> Result<()> {
+        if headers.is_empty() {
+            return Ok(());
+        }
+        let mut batch = sled::Batch::default();
+        for (offset, header) in headers.iter().enumerate() {
+            let height = (self.len + offset) as u32;
+            batch.insert(&header.hash()[..], &height.to_le_bytes()[..]);
+        }
+        let indexed_len = (self.len + headers.len()) as u64;
+        batch.insert(INDEXED_LEN_KEY, &indexed_len.to_le_bytes()[..]);
+        self.index.apply_batch(batch)?;
+        Ok(())
+    }
+
+    fn file_offset(&self, height: usize) -> u64 {
+        ((height - self.start_height) * HEADER_SIZE) as u64
+    }
+
+    /// Number of headers in the file.
+    fn file_len(&self) -> Result<usize> {
+        Ok(self.lock_file().metadata()?.len() as usize / HEADER_SIZE)
+    }
+
+    /// A reader panicking while holding the lock leaves the file intact, as
+    /// every access seeks first.
+    fn lock_file(&self) -> MutexGuard<'_, File> {
+        self.file.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[test]
+fn test_header_store() {
+    use crate::test_utils::{reopen, TempDir};
+    use cirrus_consensus::GENESIS;
+    use std::sync::Arc;
+
+    let dir = TempDir::new("header-store");
+    let path = dir.path().join("headers");
+    let mut headers = vec![GENESIS];
+    for i in 1..5 {
+        let mut header = GENESIS;
+        header.prev_block = headers[i - 1].hash();
+        headers.push(header);
+    }
+
+    let mut store = HeaderStore::open(&path, 0).unwrap();
+    assert!(store.is_empty());
+    assert!(store.tip().unwrap().is_none());
+    store.append(&headers[..3]).unwrap();
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.height(&headers[2].hash()).unwrap(), Some(2));
+    assert_eq!(store.header(1).unwrap().unwrap().hash(), headers[1].hash());
+    assert!(store.header(3).unwrap().is_none());
+    drop(store);
+
+    // a torn append is cut off, headers missing in the index are indexed
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(path.join(HEADERS_FILE))
+        .unwrap();
+    headers[3].write_to_stream(&mut file).unwrap();
+    file.write_all(&[0; 40]).unwrap();
+    drop(file);
+    let mut store = reopen(|| HeaderStore::open(&path, 0)).unwrap();
+    assert_eq!(store.len(), 4);
+    assert_eq!(store.height(&headers[3].hash()).unwrap(), Some(3));
+    assert_eq!(store.tip().unwrap().unwrap().hash(), headers[3].hash());
+
+    let removed = store.truncate(2).unwrap();
+    assert_eq!(removed.len(), 2);
+    assert_eq!(removed[0].hash(), headers[2].hash());
+    assert_eq!(store.height(&headers[2].hash()).unwrap(), None);
+    store.append(&headers[2..3]).unwrap();
+    drop(store);
+
+    let store = reopen(|| HeaderStore::open(&path, 0)).unwrap();
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.height(&headers[2].hash()).unwrap(), Some(2));
+    assert_eq!(store.height(&headers[3].hash()).unwrap(), None);
+    assert_eq!(store.headers(0, 10).unwrap().len(), 3);
+
+    // concurrent readers each get the headers they asked for
+    let store = Arc::new(store);
+    let readers = (0..8)
+        .map(|i| {
+            let store = Arc::clone(&store);
+            let expected = headers[i % 3].hash();
+            std::thread::spawn(move || {
+                for _ in 0..200 {
+                    assert_eq!(store.header(i % 3).unwrap().unwrap().hash(), expected);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+
+    // a store starting at a later height keeps its start when reopened
+    let path = dir.path().join("later");
+    let mut store = HeaderStore::open(&path, 1000).unwrap();
+    assert!(store.is_empty());
+    store.append(&headers[..2]).unwrap();
+    assert_eq!(store.start_height(), 1000);
+    assert_eq!(store.len(), 1002);
+    assert_eq!(store.height(&headers[1].hash()).unwrap(), Some(1001));
+    assert!(store.header(999).unwrap().is_none());
+    drop(store);
+    let mut store = reopen(|| HeaderStore::open(&path, 0)).unwrap();
+    assert_eq!(store.start_height(), 1000);
+    assert_eq!(store.tip().unwrap().unwrap().hash(), headers[1].hash());
+    assert_eq!(store.truncate(0).unwrap().len(), 2);
+    assert!(store.is_empty());
+}
@@ -1 +1,18 @@
+mod broadcast;
+mod dex;
+pub mod errors;
+mod header_chain;
+mod header_store;
+mod mempool;
+mod network_time;
+#[cfg(test)]
+mod test_utils;
+mod tx_builder;
 
+pub use broadcast::*;
+pub use dex::*;
+pub use header_chain::*;
+pub use header_store::*;
+pub use mempool::*;
+pub use network_time::*;
+pub use tx_builder::*;
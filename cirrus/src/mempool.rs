@@ -0,0 +1,238 @@
+use cashcontracts::TxOutpoint;
+use cirrus_consensus::{Block, Transaction};
+use cirrus_p2p::{MerkleBlockMessage, TxMessage};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub(crate) type OutpointKey = ([u8; 32], u32);
+
+pub(crate) fn outpoint_key(outpoint: &TxOutpoint) -> OutpointKey {
+    (outpoint.tx_hash, outpoint.vout)
+}
+
+#[derive(Clone, Debug)]
+pub struct MempoolEntry {
+    pub tx: Transaction,
+    pub time_added: Instant,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MempoolInsert {
+    Added,
+    AlreadyKnown,
+    /// The tx spends outputs already spent by these mempool txs and was not added.
+    Conflict(Vec<[u8; 32]>),
+}
+
+pub struct Mempool {
+    entries: HashMap<[u8; 32], MempoolEntry>,
+    spent_by: HashMap<OutpointKey, [u8; 32]>,
+    max_age: Duration,
+}
+
+pub const DEFAULT_MEMPOOL_EXPIRY: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+impl Mempool {
+    pub fn new(max_age: Duration) -> Self {
+        Mempool {
+            entries: HashMap::new(),
+            spent_by: HashMap::new(),
+            max_age,
+        }
+    }
+
+    pub fn add_tx_message(&mut self, msg: TxMessage) -> MempoolInsert {
+        self.add_tx(msg.tx, Instant::now())
+    }
+
+    pub fn add_tx(&mut self, tx: Transaction, time_added: Instant) -> MempoolInsert {
+        let tx_hash = tx.hash();
+        if self.entries.contains_key(&tx_hash) {
+            return MempoolInsert::AlreadyKnown;
+        }
+        let conflicts = self.conflicts(&tx);
+        if !conflicts.is_empty() {
+            return MempoolInsert::Conflict(conflicts);
+        }
+        for input in tx.inputs.iter() {
+            self.spent_by.insert(outpoint_key(&input.prev_out), tx_hash);
+        }
+        self.entries
+            .insert(tx_hash, MempoolEntry { tx, time_added });
+        MempoolInsert::Added
+    }
+
+    /// Mempool txs spending any of the outputs `tx` spends.
+    pub fn conflicts(&self, tx: &Transaction) -> Vec<[u8; 32]> {
+        let mut conflicts = Vec::new();
+        for input in tx.inputs.iter() {
+            if let Some(spender) = self.spent_by.get(&outpoint_key(&input.prev_out)) {
+                if !conflicts.contains(spender) {
+                    conflicts.push(*spender);
+                }
+            }
+        }
+        conflicts
+    }
+
+    pub fn get(&self, tx_hash: &[u8; 32]) -> Option<&MempoolEntry> {
+        self.entries.get(tx_hash)
+    }
+
+    pub fn contains(&self, tx_hash: &[u8; 32]) -> bool {
+        self.entries.contains_key(tx_hash)
+    }
+
+    pub fn spender(&self, outpoint: &TxOutpoint) -> Option<&[u8; 32]> {
+        self.spent_by.get(&outpoint_key(outpoint))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&[u8; 32], &MempoolEntry)> {
+        self.entries.iter()
+    }
+
+    fn remove_entry(&mut self, tx_hash: &[u8; 32]) -> Option<MempoolEntry> {
+        let entry = self.entries.remove(tx_hash)?;
+        for input in entry.tx.inputs.iter() {
+            let key = outpoint_key(&input.prev_out);
+            if self.spent_by.get(&key) == Some(tx_hash) {
+                self.spent_by.remove(&key);
+            }
+        }
+        Some(entry)
+    }
+
+    /// Removes the tx and all mempool txs depending on it, returning their hashes.
+    pub fn remove_tx(&mut self, tx_hash: &[u8; 32]) -> Vec<[u8; 32]> {
+        let mut removed = Vec::new();
+        let mut pending = vec![*tx_hash];
+        while let Some(tx_hash) = pending.pop() {
+            let entry = match self.remove_entry(&tx_hash) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            for vout in 0..entry.tx.outputs.len() as u32 {
+                if let Some(child) = self.spent_by.get(&(tx_hash, vout)) {
+                    pending.push(*child);
+                }
+            }
+            removed.push(tx_hash);
+        }
+        removed
+    }
+
+    /// Evicts the txs confirmed in `block` and every mempool tx double-spending
+    /// one of them (including descendants). Returns the hashes of all evicted txs.
+    pub fn remove_block(&mut self, block: &Block) -> Vec<[u8; 32]> {
+        let mut removed = Vec::new();
+        for tx in block.txs.iter() {
+            let tx_hash = tx.hash();
+            if self.remove_entry(&tx_hash).is_some() {
+                removed.push(tx_hash);
+                continue;
+            }
+            for conflict in self.conflicts(tx) {
+                removed.extend(self.remove_tx(&conflict));
+            }
+        }
+        removed
+    }
+
+    /// Evicts the txs of a filtered block. Only matched txs are known, so
+    /// conflicts cannot be detected here; they will eventually expire.
+    pub fn remove_merkle_block(&mut self, merkle_block: &MerkleBlockMessage) -> Vec<[u8; 32]> {
+        let matches = match merkle_block.matched_tx_hashes() {
+            Some(matches) => matches,
+            None => return vec![],
+        };
+        matches
+            .into_iter()
+            .filter(|tx_hash| self.remove_entry(tx_hash).is_some())
+            .collect()
+    }
+
+    /// Evicts txs older than the configured max age, including descendants.
+    pub fn expire(&mut self, now: Instant) -> Vec<[u8; 32]> {
+        let max_age = self.max_age;
+        let expired = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| now.saturating_duration_since(entry.time_added) > max_age)
+            .map(|(tx_hash, _)| *tx_hash)
+            .collect::<Vec<_>>();
+        let mut removed = Vec::new();
+        for tx_hash in expired {
+            removed.extend(self.remove_tx(&tx_hash));
+        }
+        removed
+    }
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Mempool::new(DEFAULT_MEMPOOL_EXPIRY)
+    }
+}
+
+#[test]
+fn test_mempool() {
+    use cirrus_consensus::{TxInput, TxOutput, GENESIS};
+    let make_tx = |prev_hash: [u8; 32], value: u64| Transaction {
+        version: 1,
+        inputs: vec![TxInput {
+            prev_out: TxOutpoint {
+                tx_hash: prev_hash,
+                vout: 0,
+            },
+            script: vec![],
+            sequence: 0xffff_ffff,
+        }],
+        outputs: vec![TxOutput {
+            value,
+            script: vec![],
+            token: None,
+        }],
+        lock_time: 0,
+    };
+    let now = Instant::now();
+    let mut mempool = Mempool::new(Duration::from_secs(60));
+    let parent = make_tx([1; 32], 1000);
+    let child = make_tx(parent.hash(), 900);
+    let double_spend = make_tx([1; 32], 800);
+    assert_eq!(mempool.add_tx(parent.clone(), now), MempoolInsert::Added);
+    assert_eq!(
+        mempool.add_tx(parent.clone(), now),
+        MempoolInsert::AlreadyKnown
+    );
+    assert_eq!(mempool.add_tx(child.clone(), now), MempoolInsert::Added);
+    assert_eq!(
+        mempool.add_tx(double_spend.clone(), now),
+        MempoolInsert::Conflict(vec![parent.hash()]),
+    );
+
+    let block = Block {
+        header: GENESIS,
+        txs: vec![double_spend],
+    };
+    let mut removed = mempool.remove_block(&block);
+    removed.sort();
+    let mut expected = vec![parent.hash(), child.hash()];
+    expected.sort();
+    assert_eq!(removed, expected);
+    assert!(mempool.is_empty());
+
+    mempool.add_tx(parent.clone(), now);
+    assert!(mempool.expire(now + Duration::from_secs(30)).is_empty());
+    assert_eq!(
+        mempool.expire(now + Duration::from_secs(61)),
+        vec![parent.hash()]
+    );
+}
@@ -0,0 +1,85 @@
+use cirrus_p2p::VersionMessage;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Peer clocks further off than this, in seconds, are assumed to be wrong
+/// and don't adjust the time.
+pub const MAX_TIME_ADJUSTMENT: i64 = 70 * 60;
+const MIN_TIME_SAMPLES: usize = 5;
+const MAX_TIME_SAMPLES: usize = 200;
+
+/// Local clock adjusted by the median offset of the peers' clocks, as
+/// reported in their version messages. One sample per peer ip.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkTime {
+    offsets: HashMap<IpAddr, i64>,
+}
+
+pub fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+impl NetworkTime {
+    pub fn new() -> Self {
+        NetworkTime::default()
+    }
+
+    /// Adds the offset of the clock of `peer`, which sent `version` just now.
+    pub fn add_version_message(&mut self, peer: IpAddr, version: &VersionMessage) {
+        self.add_offset(peer, version.timestamp - unix_time());
+    }
+
+    pub fn add_offset(&mut self, peer: IpAddr, offset: i64) {
+        if self.offsets.len() >= MAX_TIME_SAMPLES && !self.offsets.contains_key(&peer) {
+            return;
+        }
+        self.offsets.insert(peer, offset);
+    }
+
+    /// Median offset of the peers' clocks, 0 if there are too few peers or
+    /// the median is beyond `MAX_TIME_ADJUSTMENT`.
+    pub fn offset(&self) -> i64 {
+        if self.offsets.len() < MIN_TIME_SAMPLES {
+            return 0;
+        }
+        let mut offsets = self.offsets.values().cloned().collect::<Vec<_>>();
+        offsets.sort_unstable();
+        let median = offsets[offsets.len() / 2];
+        if median.abs() > MAX_TIME_ADJUSTMENT {
+            return 0;
+        }
+        median
+    }
+
+    /// Network-adjusted time in unix seconds.
+    pub fn adjusted_time(&self) -> i64 {
+        unix_time() + self.offset()
+    }
+}
+
+#[test]
+fn test_network_time() {
+    let ip = |i: u8| IpAddr::from([10, 0, 0, i]);
+    let mut network_time = NetworkTime::new();
+    for i in 0..4 {
+        network_time.add_offset(ip(i), 60);
+    }
+    assert_eq!(network_time.offset(), 0);
+    network_time.add_offset(ip(4), -30);
+    assert_eq!(network_time.offset(), 60);
+    // a peer only counts once
+    network_time.add_offset(ip(4), 60);
+    network_time.add_offset(ip(5), -30);
+    network_time.add_offset(ip(6), -30);
+    assert_eq!(network_time.offset(), 60);
+    for i in 7..20 {
+        network_time.add_offset(ip(i), 2 * MAX_TIME_ADJUSTMENT);
+    }
+    assert_eq!(network_time.offset(), 0);
+    let time = network_time.adjusted_time();
+    assert!((time - unix_time()).abs() <= 1);
+}
@@ -0,0 +1,45 @@
+use crate::errors::{ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Directory for a test's files, removed when dropped, even if the test
+/// panics.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new(name: &str) -> TempDir {
+        let path =
+            std::env::temp_dir().join(format!("cirrus-{}-test-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Reopens a store whose sled index was just dropped. sled's background
+/// threads release the index's file lock a moment after the drop, until
+/// then opening fails with an I/O error.
+pub(crate) fn reopen<T>(open: impl Fn() -> Result<T>) -> Result<T> {
+    for _ in 0..200 {
+        match open() {
+            Err(err) => match err.kind() {
+                ErrorKind::Db(sled::Error::Io(_)) => std::thread::sleep(Duration::from_millis(10)),
+                _ => return Err(err),
+            },
+            result => return result,
+        }
+    }
+    open()
+}
@@ -0,0 +1,589 @@
+use crate::errors::{ErrorKind, Result};
+use cirrus_consensus::script::opcodes::{OP_0, OP_1, OP_CHECKMULTISIG};
+use cirrus_consensus::script::{
+    encode_push, sighash_cached, ScriptFlags, SigHashCache, SigHashType, MAX_SCRIPT_ELEMENT_SIZE,
+    SIGHASH_ALL, SIGHASH_FORKID,
+};
+use cirrus_consensus::{Transaction, TxInput, TxOutput};
+use cirrus_crypto::{Address, Network, PrivateKey, PublicKey};
+use cirrus_utxo::Utxo;
+use error_chain::bail;
+use std::cmp::Reverse;
+
+/// Outputs below this amount aren't relayed.
+pub const DUST_LIMIT: u64 = 546;
+pub const DEFAULT_FEE_RATE: u64 = 1;
+/// 15 compressed keys keep the redeem script within the push size limit.
+pub const MAX_MULTISIG_PUBKEYS: usize = 15;
+
+pub(crate) const TX_VERSION: i32 = 2;
+pub(crate) const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+
+/// Upper bounds of serialized signatures, including the sighash byte.
+const MAX_ECDSA_SIG_SIZE: usize = 72 + 1;
+const SCHNORR_SIG_SIZE: usize = 64 + 1;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignatureType {
+    Ecdsa,
+    Schnorr,
+}
+
+/// How to unlock a utxo.
+#[derive(Clone, Debug)]
+pub enum UtxoSigner {
+    P2PKH(PrivateKey),
+    /// Bare m-of-n multisig wrapped in P2SH, m being `threshold`.
+    /// `private_keys` must belong to `threshold` of the `public_keys`.
+    P2SHMultisig {
+        threshold: usize,
+        public_keys: Vec<PublicKey>,
+        private_keys: Vec<PrivateKey>,
+    },
+}
+
+/// Builds and signs a tx spending utxos selected from the added ones,
+/// sending the change (if not dust) back to `change_address`.
+pub struct TransactionBuilder {
+    utxos: Vec<(Utxo, UtxoSigner)>,
+    /// Inputs signed by someone else, with their index and spent amount.
+    signed_inputs: Vec<(usize, TxInput, u64)>,
+    outputs: Vec<TxOutput>,
+    change_address: Address,
+    fee_rate: u64,
+    signature_type: SignatureType,
+    lock_time: u32,
+}
+
+/// OP_m <pubkey>... OP_n OP_CHECKMULTISIG
+///
+/// As a redeem script, it has to fit a single push, which limits the number
+/// of uncompressed keys further.
+pub fn multisig_script(threshold: usize, public_keys: &[PublicKey]) -> Result<Vec<u8>> {
+    if threshold == 0 || threshold > public_keys.len() || public_keys.len() > MAX_MULTISIG_PUBKEYS {
+        bail!(ErrorKind::InvalidMultisig(threshold, public_keys.len()));
+    }
+    let mut script = vec![OP_1 + threshold as u8 - 1];
+    for public_key in public_keys {
+        script.extend_from_slice(&encode_push(&public_key.serialize()));
+    }
+    script.push(OP_1 + public_keys.len() as u8 - 1);
+    script.push(OP_CHECKMULTISIG);
+    if script.len() > MAX_SCRIPT_ELEMENT_SIZE {
+        bail!(ErrorKind::InvalidMultisig(threshold, public_keys.len()));
+    }
+    Ok(script)
+}
+
+fn var_int_size(value: usize) -> usize {
+    match value {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+fn push_size(data_len: usize) -> usize {
+    match data_len {
+        0..=0x4b => 1 + data_len,
+        0x4c..=0xff => 2 + data_len,
+        0x100..=0xffff => 3 + data_len,
+        _ => 5 + data_len,
+    }
+}
+
+/// Like `encode_push`, but uses OP_1 to OP_16 for single bytes, as
+/// required by MINIMALDATA.
+fn encode_minimal_push(data: &[u8]) -> Vec<u8> {
+    match data {
+        [value @ 1..=16] => vec![OP_1 + value - 1],
+        _ => encode_push(data),
+    }
+}
+
+fn checked_sum(amounts: impl IntoIterator<Item = u64>) -> Result<u64> {
+    amounts
+        .into_iter()
+        .try_fold(0u64, |sum, amount| sum.checked_add(amount))
+        .ok_or_else(|| ErrorKind::AmountOverflow.into())
+}
+
+fn output_size(output: &TxOutput) -> usize {
+    8 + var_int_size(output.script.len()) + output.script.len()
+}
+
+fn input_size(input: &TxInput) -> usize {
+    32 + 4 + var_int_size(input.script.len()) + input.script.len() + 4
+}
+
+impl UtxoSigner {
+    /// The script the signatures commit to, and the redeem script for P2SH.
+    fn script_code(&self) -> Result<Vec<u8>> {
+        match self {
+            UtxoSigner::P2PKH(private_key) => {
+                Ok(Address::p2pkh(Network::Mainnet, &private_key.public_key()).to_script())
+            }
+            UtxoSigner::P2SHMultisig {
+                threshold,
+                public_keys,
+                ..
+            } => multisig_script(*threshold, public_keys),
+        }
+    }
+
+    /// The output script locking utxos this signer can spend.
+    pub fn script_pubkey(&self) -> Result<Vec<u8>> {
+        match self {
+            UtxoSigner::P2PKH(_) => self.script_code(),
+            UtxoSigner::P2SHMultisig { .. } => {
+                Ok(Address::p2sh(Network::Mainnet, &self.script_code()?).to_script())
+            }
+        }
+    }
+
+    /// Upper bound of the size of the signed input spending with this signer.
+    pub fn estimate_input_size(&self, signature_type: SignatureType) -> Result<usize> {
+        let sig_size = match signature_type {
+            SignatureType::Ecdsa => MAX_ECDSA_SIG_SIZE,
+            SignatureType::Schnorr => SCHNORR_SIG_SIZE,
+        };
+        let script_sig_size = match self {
+            UtxoSigner::P2PKH(private_key) => {
+                push_size(sig_size) + push_size(private_key.public_key().serialize().len())
+            }
+            UtxoSigner::P2SHMultisig {
+                threshold,
+                public_keys,
+                ..
+            } => {
+                let dummy_size = match signature_type {
+                    SignatureType::Ecdsa => 1,
+                    SignatureType::Schnorr => push_size(public_keys.len().div_ceil(8)),
+                };
+                let redeem_script = multisig_script(*threshold, public_keys)?;
+                dummy_size + threshold * push_size(sig_size) + push_size(redeem_script.len())
+            }
+        };
+        Ok(32 + 4 + var_int_size(script_sig_size) + script_sig_size + 4)
+    }
+}
+
+impl TransactionBuilder {
+    pub fn new(change_address: Address) -> Self {
+        TransactionBuilder {
+            utxos: Vec::new(),
+            signed_inputs: Vec::new(),
+            outputs: Vec::new(),
+            change_address,
+            fee_rate: DEFAULT_FEE_RATE,
+            signature_type: SignatureType::Schnorr,
+            lock_time: 0,
+        }
+    }
+
+    /// Fee in satoshis per byte of the signed tx.
+    pub fn set_fee_rate(&mut self, fee_rate: u64) {
+        self.fee_rate = fee_rate;
+    }
+
+    pub fn set_signature_type(&mut self, signature_type: SignatureType) {
+        self.signature_type = signature_type;
+    }
+
+    pub fn set_lock_time(&mut self, lock_time: u32) {
+        self.lock_time = lock_time;
+    }
+
+    /// Adds a utxo the builder may select as input. Utxos holding
+    /// CashTokens are rejected, as the builder would burn them.
+    pub fn add_utxo(&mut self, utxo: Utxo, signer: UtxoSigner) -> Result<()> {
+        if utxo.script != signer.script_pubkey()? {
+            bail!(ErrorKind::SignerMismatch(
+                utxo.outpoint.tx_hash,
+                utxo.outpoint.vout
+            ));
+        }
+        if utxo.token.is_some() {
+            bail!(ErrorKind::TokenUtxo(
+                utxo.outpoint.tx_hash,
+                utxo.outpoint.vout
+            ));
+        }
+        self.utxos.push((utxo, signer));
+        Ok(())
+    }
+
+    /// The utxos added by `add_utxo`.
+    pub fn utxos(&self) -> impl Iterator<Item = &Utxo> {
+        self.utxos.iter().map(|(utxo, _)| utxo)
+    }
+
+    /// Places an already signed input at `index` of the tx, e.g. the maker's
+    /// half of a trade. The signature must not commit to the other inputs.
+    pub fn insert_signed_input(&mut self, index: usize, input: TxInput, amount: u64) {
+        self.signed_inputs.push((index, input, amount));
+    }
+
+    pub fn add_output(&mut self, address: &Address, amount: u64) -> Result<()> {
+        if amount < DUST_LIMIT {
+            bail!(ErrorKind::DustOutput(amount));
+        }
+        self.add_output_script(address.to_script(), amount);
+        Ok(())
+    }
+
+    /// Adds an output with an arbitrary script, e.g. an SLP OP_RETURN.
+    /// Outputs are kept in the order they're added, change comes last.
+    pub fn add_output_script(&mut self, script: Vec<u8>, amount: u64) {
+        self.outputs.push(TxOutput {
+            value: amount,
+            script,
+            token: None,
+        });
+    }
+
+    /// Like `add_output_script`, but places the output at `index`.
+    pub fn insert_output_script(&mut self, index: usize, script: Vec<u8>, amount: u64) {
+        self.outputs.insert(
+            index,
+            TxOutput {
+                value: amount,
+                script,
+                token: None,
+            },
+        );
+    }
+
+    fn estimate_size(
+        &self,
+        selected: &[&(Utxo, UtxoSigner)],
+        outputs: &[TxOutput],
+    ) -> Result<usize> {
+        let num_inputs = selected.len() + self.signed_inputs.len();
+        let mut size = 4 + var_int_size(num_inputs) + var_int_size(outputs.len()) + 4;
+        for (_, signer) in selected {
+            size += signer.estimate_input_size(self.signature_type)?;
+        }
+        for (_, input, _) in self.signed_inputs.iter() {
+            size += input_size(input);
+        }
+        size += outputs.iter().map(output_size).sum::<usize>();
+        Ok(size)
+    }
+
+    /// Selects utxos, largest first, until they pay for the outputs and the
+    /// fee, then signs the selected inputs with SIGHASH_ALL | SIGHASH_FORKID.
+    /// At least one utxo is selected, even if signed inputs pay for all.
+    pub fn build(&self) -> Result<Transaction> {
+        let output_sum = checked_sum(self.outputs.iter().map(|output| output.value))?;
+        let signed_sum = checked_sum(self.signed_inputs.iter().map(|(_, _, amount)| *amount))?;
+        let available = checked_sum(
+            self.utxos
+                .iter()
+                .map(|(utxo, _)| utxo.amount)
+                .chain(Some(signed_sum)),
+        )?;
+        let change_output = TxOutput {
+            value: 0,
+            script: self.change_address.to_script(),
+            token: None,
+        };
+        let mut outputs_with_change = self.outputs.clone();
+        outputs_with_change.push(change_output);
+
+        let mut candidates = self.utxos.iter().collect::<Vec<_>>();
+        candidates.sort_by_key(|(utxo, _)| Reverse(utxo.amount));
+        let mut selected = Vec::new();
+        let mut input_sum = signed_sum;
+        for candidate in candidates {
+            selected.push(candidate);
+            input_sum += candidate.0.amount;
+            let fee = self.estimate_size(&selected, &self.outputs)? as u64 * self.fee_rate;
+            if input_sum < output_sum.saturating_add(fee) {
+                continue;
+            }
+            let mut outputs = self.outputs.clone();
+            let fee_with_change =
+                self.estimate_size(&selected, &outputs_with_change)? as u64 * self.fee_rate;
+            if input_sum >= output_sum.saturating_add(fee_with_change + DUST_LIMIT) {
+                let mut change_output = outputs_with_change.last().unwrap().clone();
+                change_output.value = input_sum - output_sum - fee_with_change;
+                outputs.push(change_output);
+            }
+            return self.sign(&selected, outputs);
+        }
+        let fee = self.estimate_size(&selected, &self.outputs)? as u64 * self.fee_rate;
+        bail!(ErrorKind::InsufficientFunds(
+            output_sum.saturating_add(fee),
+            available
+        ))
+    }
+
+    fn sign(
+        &self,
+        selected: &[&(Utxo, UtxoSigner)],
+        outputs: Vec<TxOutput>,
+    ) -> Result<Transaction> {
+        let mut inputs = selected
+            .iter()
+            .map(|utxo_signer| {
+                let input = TxInput {
+                    prev_out: utxo_signer.0.outpoint.clone(),
+                    script: vec![],
+                    sequence: if self.lock_time == 0 {
+                        SEQUENCE_FINAL
+                    } else {
+                        SEQUENCE_FINAL - 1
+                    },
+                };
+                (Some(*utxo_signer), input)
+            })
+            .collect::<Vec<_>>();
+        let mut signed_inputs = self.signed_inputs.iter().collect::<Vec<_>>();
+        signed_inputs.sort_by_key(|(index, _, _)| *index);
+        for (index, input, _) in signed_inputs {
+            if *index > inputs.len() {
+                bail!(ErrorKind::InvalidInputIndex(*index, inputs.len()));
+            }
+            inputs.insert(*index, (None, input.clone()));
+        }
+        let (signers, inputs): (Vec<_>, Vec<_>) = inputs.into_iter().unzip();
+        let mut tx = Transaction {
+            version: TX_VERSION,
+            inputs,
+            outputs,
+            lock_time: self.lock_time,
+        };
+        let cache = SigHashCache::new(&tx);
+        let sighash_type = SigHashType::from(SIGHASH_ALL | SIGHASH_FORKID);
+        let mut script_sigs = Vec::with_capacity(selected.len());
+        for (input_index, utxo_signer) in signers.into_iter().enumerate() {
+            let (utxo, signer) = match utxo_signer {
+                Some(utxo_signer) => utxo_signer,
+                None => continue,
+            };
+            let script_code = signer.script_code()?;
+            let msg_hash = sighash_cached(
+                &cache,
+                &tx,
+                input_index,
+                &script_code,
+                utxo.amount,
+                sighash_type,
+                ScriptFlags::ENABLE_SIGHASH_FORKID,
+            )?;
+            let sign = |private_key: &PrivateKey| {
+                let mut sig = match self.signature_type {
+                    SignatureType::Ecdsa => private_key.sign_ecdsa(&msg_hash).to_der(),
+                    SignatureType::Schnorr => {
+                        private_key.sign_schnorr(&msg_hash).to_bytes().to_vec()
+                    }
+                };
+                sig.push(sighash_type.to_byte());
+                sig
+            };
+            let mut script_sig = Vec::new();
+            match signer {
+                UtxoSigner::P2PKH(private_key) => {
+                    script_sig.extend_from_slice(&encode_push(&sign(private_key)));
+                    script_sig
+                        .extend_from_slice(&encode_push(&private_key.public_key().serialize()));
+                }
+                UtxoSigner::P2SHMultisig {
+                    threshold,
+                    public_keys,
+                    private_keys,
+                } => {
+                    // Signatures have to be in the order of the public keys.
+                    let mut checkbits = 0u32;
+                    let mut sigs = Vec::with_capacity(*threshold);
+                    for (key_index, public_key) in public_keys.iter().enumerate() {
+                        if sigs.len() == *threshold {
+                            break;
+                        }
+                        let private_key = private_keys.iter().find(|private_key| {
+                            private_key.public_key().compressed() == public_key.compressed()
+                        });
+                        if let Some(private_key) = private_key {
+                            checkbits |= 1 << key_index;
+                            sigs.push(sign(private_key));
+                        }
+                    }
+                    if sigs.len() < *threshold {
+                        bail!(ErrorKind::InvalidMultisig(*threshold, sigs.len()));
+                    }
+                    match self.signature_type {
+                        SignatureType::Ecdsa => script_sig.push(OP_0),
+                        SignatureType::Schnorr => {
+                            let num_bytes = public_keys.len().div_ceil(8);
+                            script_sig.extend_from_slice(&encode_minimal_push(
+                                &checkbits.to_le_bytes()[..num_bytes],
+                            ));
+                        }
+                    }
+                    for sig in sigs {
+                        script_sig.extend_from_slice(&encode_push(&sig));
+                    }
+                    script_sig.extend_from_slice(&encode_push(&script_code));
+                }
+            }
+            script_sigs.push((input_index, script_sig));
+        }
+        for (input_index, script_sig) in script_sigs {
+            tx.inputs[input_index].script = script_sig;
+        }
+        Ok(tx)
+    }
+}
+
+#[test]
+fn test_tx_builder() {
+    use cashcontracts::TxOutpoint;
+    use cirrus_consensus::script::{verify_script, ScriptExecutionContext, UPGRADE9_HEIGHT};
+
+    let key = |i: u8| PrivateKey::from_slice(&[i; 32]).unwrap();
+    let utxo = |i: u8, amount: u64, script: Vec<u8>| Utxo {
+        outpoint: TxOutpoint {
+            tx_hash: [i; 32],
+            vout: i as u32,
+        },
+        amount,
+        script,
+        token: None,
+        block_height: 600_000,
+        flags: 0,
+        slp_token: None,
+    };
+    let multisig = UtxoSigner::P2SHMultisig {
+        threshold: 2,
+        public_keys: vec![
+            key(3).public_key(),
+            key(4).public_key(),
+            key(5).public_key(),
+        ],
+        private_keys: vec![key(5), key(3)],
+    };
+    let change_address = Address::p2pkh(Network::Mainnet, &key(9).public_key());
+    let recipient = Address::p2pkh(Network::Mainnet, &key(8).public_key());
+    let flags = ScriptFlags::for_height(UPGRADE9_HEIGHT);
+
+    for signature_type in &[SignatureType::Ecdsa, SignatureType::Schnorr] {
+        let mut builder = TransactionBuilder::new(change_address.clone());
+        builder.set_signature_type(*signature_type);
+        builder.set_fee_rate(2);
+        let p2pkh = UtxoSigner::P2PKH(key(1));
+        builder
+            .add_utxo(utxo(1, 10_000, p2pkh.script_pubkey().unwrap()), p2pkh)
+            .unwrap();
+        builder
+            .add_utxo(
+                utxo(2, 20_000, multisig.script_pubkey().unwrap()),
+                multisig.clone(),
+            )
+            .unwrap();
+        builder.add_output(&recipient, 25_000).unwrap();
+        let tx = builder.build().unwrap();
+
+        // the largest utxo comes first
+        assert_eq!(tx.inputs[0].prev_out.vout, 2);
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.outputs[1].script, change_address.to_script());
+        let estimated_size = builder
+            .estimate_size(&builder.utxos.iter().rev().collect::<Vec<_>>(), &tx.outputs)
+            .unwrap();
+        let size = tx.serialize().len();
+        // DER signatures can be shorter than estimated, Schnorr ones can't;
+        // the multisig checkbits 0b101 are pushed as OP_5, saving a byte
+        match signature_type {
+            SignatureType::Ecdsa => assert!(size <= estimated_size),
+            SignatureType::Schnorr => assert_eq!(size + 1, estimated_size),
+        }
+        let fee = 30_000 - tx.outputs.iter().map(|output| output.value).sum::<u64>();
+        assert_eq!(fee, estimated_size as u64 * 2);
+
+        let spent_outputs = [
+            TxOutput {
+                value: 20_000,
+                script: multisig.script_pubkey().unwrap(),
+                token: None,
+            },
+            TxOutput {
+                value: 10_000,
+                script: builder.utxos[0].0.script.clone(),
+                token: None,
+            },
+        ];
+        for input_index in 0..2 {
+            let context = ScriptExecutionContext {
+                tx: &tx,
+                input_index,
+                spent_outputs: &spent_outputs,
+                sighash_cache: None,
+            };
+            let script_sig = &tx.inputs[input_index].script;
+            verify_script(
+                script_sig,
+                &spent_outputs[input_index].script,
+                flags,
+                &context,
+            )
+            .unwrap();
+        }
+    }
+
+    // change below the dust limit is left to the miners
+    let mut builder = TransactionBuilder::new(change_address.clone());
+    let p2pkh = UtxoSigner::P2PKH(key(1));
+    builder
+        .add_utxo(
+            utxo(1, 10_000, p2pkh.script_pubkey().unwrap()),
+            p2pkh.clone(),
+        )
+        .unwrap();
+    builder.add_output(&recipient, 9_500).unwrap();
+    assert_eq!(builder.build().unwrap().outputs.len(), 1);
+
+    builder.add_output(&recipient, 1_000).unwrap();
+    match builder.build().unwrap_err().kind() {
+        ErrorKind::InsufficientFunds(_, 10_000) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+    assert!(builder.add_output(&recipient, 100).is_err());
+    assert!(builder
+        .add_utxo(utxo(2, 10_000, vec![]), p2pkh.clone())
+        .is_err());
+    let mut token_utxo = utxo(2, 10_000, p2pkh.script_pubkey().unwrap());
+    token_utxo.token = Some(cirrus_consensus::TokenData {
+        category: [5; 32],
+        nft: None,
+        amount: 1000,
+    });
+    match builder
+        .add_utxo(token_utxo, p2pkh.clone())
+        .unwrap_err()
+        .kind()
+    {
+        ErrorKind::TokenUtxo(..) => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+
+    // amounts summing beyond u64 are an error, not a wrap around
+    builder.add_output(&recipient, u64::MAX).unwrap();
+    match builder.build().unwrap_err().kind() {
+        ErrorKind::AmountOverflow => {}
+        kind => panic!("unexpected error {:?}", kind),
+    }
+
+    // redeem scripts have to fit a single push
+    let public_keys = (1..=16).map(|i| key(i).public_key()).collect::<Vec<_>>();
+    let script = multisig_script(15, &public_keys[..15]).unwrap();
+    assert!(script.len() <= MAX_SCRIPT_ELEMENT_SIZE);
+    assert!(multisig_script(16, &public_keys).is_err());
+    let uncompressed = public_keys[..8]
+        .iter()
+        .map(PublicKey::uncompressed)
+        .collect::<Vec<_>>();
+    assert!(multisig_script(1, &uncompressed[..7]).is_ok());
+    assert!(multisig_script(1, &uncompressed).is_err());
+}
@@ -0,0 +1,20 @@
+#!/bin/sh
+# Downloads the upstream test vectors the ignored vector tests run against.
+# Commit the files unchanged, then drop the #[ignore] of their tests.
+set -e
+cd "$(dirname "$0")/.."
+
+BCHN=https://gitlab.com/bitcoin-cash-node/bitcoin-cash-node/-/raw/master/src/test/data
+
+fetch() {
+    mkdir -p "$(dirname "$2")"
+    curl -fsSL "$1" -o "$2"
+}
+
+fetch "$BCHN/script_tests.json" cirrus-consensus/tests/data/script_tests.json
+fetch "$BCHN/sighash.json" cirrus-consensus/tests/data/sighash.json
+
+SLP=https://raw.githubusercontent.com/simpleledger/slp-unit-test-data/master
+
+fetch "$SLP/script_tests.json" cirrus-slp/tests/data/script_tests.json
+fetch "$SLP/tx_input_tests.json" cirrus-slp/tests/data/tx_input_tests.json